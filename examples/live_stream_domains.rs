@@ -42,7 +42,7 @@ async fn main() {
                                 print!("{}", d);
                                 first = false;
                             }
-                            print!("\n");
+                            println!();
                         }
                     }))
                     .await;
@@ -1,11 +1,13 @@
 use std::process::exit;
 use std::time::{Duration, SystemTime};
 
+use base64::prelude::{BASE64_STANDARD, Engine as _};
+
 use openssl::x509::X509;
 
-use ctclient_async::CTClient;
 use ctclient_async::google_log_list::LogList;
 use ctclient_async::utils::u8_to_hex;
+use ctclient_async::{CTClient, LogApi, check_cert_scts};
 
 #[tokio::main]
 async fn main() {
@@ -23,21 +25,23 @@ async fn main() {
         eprintln!("Expected at least 2 certs.");
         exit(1);
     }
-    let sct_list = ctclient_async::SignedCertificateTimestamp::from_cert_sct_extension(
-        chain[0].as_ref(),
-        chain[1].as_ref(),
-    )
-    .expect("Unable to parse sct list");
-    if sct_list.is_empty() {
-        println!("Did not found any SCTs in the certificate.");
-        exit(0);
-    }
     let ll = LogList::get()
         .await
         .expect("Unable to fetch log list from Google.");
-    for (i, sct) in sct_list.iter().enumerate() {
+    let now = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap()
+        .as_millis() as u64;
+    let checks = check_cert_scts(chain[0].as_ref(), chain[1].as_ref(), &ll, now)
+        .expect("Unable to parse sct list");
+    if checks.is_empty() {
+        println!("Did not found any SCTs in the certificate.");
+        exit(0);
+    }
+    for (i, check) in checks.iter().enumerate() {
+        let sct = &check.sct;
         println!("SCT {}:", i + 1);
-        let log_id_b64 = base64::encode(&sct.log_id);
+        let log_id_b64 = BASE64_STANDARD.encode(sct.log_id);
         println!("  log_id = {}", log_id_b64);
         let timestamp = sct.timestamp;
         let time = SystemTime::UNIX_EPOCH
@@ -46,35 +50,38 @@ async fn main() {
         println!(
             "  timestamp = {} ({} days ago)",
             timestamp,
-            (time.elapsed().unwrap().as_secs_f32() / 60f32 / 60f32 / 24f32).round()
+            (time.elapsed().unwrap_or_default().as_secs_f32() / 60f32 / 60f32 / 24f32).round()
         );
         let leaf_hash = sct.derive_leaf_hash();
         println!("  calculated leaf hash: {}", u8_to_hex(&leaf_hash));
-        let log = ll.find_by_id(&sct.log_id);
-        if let Some(log) = log {
-            println!("  log is {}", log.base_url);
-            if let Err(e) =
-                sct.verify(&openssl::pkey::PKey::public_key_from_der(&log.pub_key).unwrap())
-            {
-                println!("  Error: unable to verify SCT signature: {}", e);
+        for problem in check.problems.iter() {
+            println!("  Error: {}", problem);
+        }
+        let log = match &check.log {
+            Some(log) => log,
+            None => continue,
+        };
+        println!("  log is {}", log.base_url);
+        let lc = match log.api {
+            LogApi::Rfc6962 => CTClient::new_from_latest_th(&log.base_url, &log.pub_key).await,
+            LogApi::StaticCt => {
+                CTClient::new_static_ct_from_latest_th(&log.base_url, &log.pub_key).await
             }
-            let lc = CTClient::new_from_latest_th(&log.base_url, &log.pub_key).await;
-            if lc.is_err() {
-                println!("    unable to connect to log: {}", lc.unwrap_err());
+        };
+        let lc = match lc {
+            Ok(lc) => lc,
+            Err(e) => {
+                println!("    unable to connect to log: {}", e);
                 continue;
             }
-            let lc = lc.unwrap();
-            match lc.check_inclusion_proof_for_sct(&sct).await {
-                Ok(index) => {
-                    println!("    inclusion proof checked, leaf index is {}", index);
-                }
-                Err(e) => {
-                    println!("    inclusion proof errored: {}", e);
-                }
+        };
+        match lc.check_inclusion_proof_for_sct(sct).await {
+            Ok(index) => {
+                println!("    inclusion proof checked, leaf index is {}", index);
+            }
+            Err(e) => {
+                println!("    inclusion proof errored: {}", e);
             }
-        } else {
-            println!("  log is not known.");
         }
-        // todo: move the inclusion proof check code into CTClient?
     }
 }
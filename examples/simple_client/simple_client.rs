@@ -36,7 +36,7 @@ async fn main() {
         )
         .optional()
         .unwrap()
-        == None
+        .is_none()
     {
         save_db
             .execute_batch(include_str!("save_db_init.sql"))
@@ -79,7 +79,7 @@ async fn main() {
         }
       };
       dns_names.sort_unstable();
-      dns_names.dedup_by(|a, b| a.eq_ignore_ascii_case(&b));
+      dns_names.dedup_by(|a, b| a.eq_ignore_ascii_case(b));
       for n in dns_names.iter_mut() {
         *n = n.to_ascii_lowercase();
         if n.ends_with(".merkleforest.xyz") || n == "merkleforest.xyz" {
@@ -0,0 +1,14 @@
+#![no_main]
+#[macro_use] extern crate libfuzzer_sys;
+extern crate ctclient;
+
+use ctclient::internal::{encode_sct_list, parse_sct_list, RawSct};
+
+fuzz_target!(|data: &[u8]| {
+	if let Ok(scts) = parse_sct_list(data) {
+		assert_eq!(encode_sct_list(&scts).unwrap(), data);
+	}
+	if let Ok(sct) = RawSct::parse(data) {
+		assert_eq!(sct.to_bytes().unwrap(), data);
+	}
+});
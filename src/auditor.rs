@@ -0,0 +1,573 @@
+//! Checking that logs incorporate the certificates they issued SCTs for.
+//!
+//! A SCT is a promise by the log to add the certificate to its tree within the log's maximum
+//! merge delay (MMD). [`SctAuditor`] queues SCTs, waits until `timestamp + MMD` has passed, and
+//! then asks the log for an inclusion proof in a tree head it signed after that deadline (or in
+//! the final tree head of a read-only log). If the log keeps failing to give one, the SCT and that
+//! tree head, both signed by the log, are evidence that it broke its promise. They are returned
+//! as a [`MmdViolation`].
+//!
+//! The auditor does not sleep by itself. Call [`SctAuditor::poll`] around the time returned by
+//! [`SctAuditor::next_due`]:
+//!
+//! ```no_run
+//! # use ctclient_async::auditor::{AuditOutcome, SctAuditor};
+//! # use ctclient_async::google_log_list::LogList;
+//! # use std::time::{Duration, SystemTime};
+//! # tokio_test::block_on(async {
+//! let mut auditor = SctAuditor::new(&LogList::get().await.unwrap());
+//! // auditor.add(sct).unwrap();
+//! while let Some(due) = auditor.next_due() {
+//!     let now = SystemTime::now()
+//!         .duration_since(SystemTime::UNIX_EPOCH)
+//!         .unwrap()
+//!         .as_millis() as u64;
+//!     tokio::time::sleep(Duration::from_millis(due.saturating_sub(now))).await;
+//!     for outcome in auditor.poll(due.max(now)).await {
+//!         if let AuditOutcome::Violation(v) = outcome {
+//!             println!("{}", v.to_json().unwrap());
+//!         }
+//!     }
+//! }
+//! # });
+//! ```
+
+use std::collections::HashMap;
+
+use base64::prelude::{BASE64_STANDARD, Engine as _};
+use log::{debug, warn};
+use openssl::pkey::PKey;
+use serde::Serialize;
+
+use crate::google_log_list::{Log, LogList, LogState};
+use crate::{
+    CTClient, Error, LogApi, SctEntry, SignedCertificateTimestamp, SignedTreeHead, SthResult, jsons,
+};
+
+/// What happened to a SCT given to [`SctAuditor::add`].
+#[derive(Debug, Clone)]
+pub enum AuditOutcome {
+    /// The log proved that the SCT is in `sth`, at `leaf_index`.
+    Included {
+        sct: SignedCertificateTimestamp,
+        leaf_index: u64,
+        sth: SignedTreeHead,
+    },
+    /// The log did not include the SCT by its deadline.
+    Violation(MmdViolation),
+    /// The SCT could not be audited, for example because the log is retired or kept failing.
+    Failed {
+        sct: SignedCertificateTimestamp,
+        reason: String,
+    },
+}
+
+/// Evidence that a log did not incorporate a SCT within its maximum merge delay.
+#[derive(Debug, Clone)]
+pub struct MmdViolation {
+    pub sct: SignedCertificateTimestamp,
+    /// `sct.timestamp` plus the MMD of the log, in ms since the epoch.
+    pub deadline: u64,
+    /// A tree head signed by the log which does not contain the SCT. It is signed no earlier than
+    /// `deadline`, unless it is the final tree head of a read-only log, which has to contain all
+    /// the SCTs the log issued.
+    pub sth: SignedTreeHead,
+    /// Why the inclusion check failed.
+    pub reason: String,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "snake_case")]
+enum EvidenceEntry {
+    X509(String),
+    Precert {
+        tbs: String,
+        issuer_key_hash: String,
+    },
+}
+
+#[derive(Serialize)]
+struct Evidence {
+    log_id: String,
+    deadline: u64,
+    leaf_hash: String,
+    entry: EvidenceEntry,
+    sct: jsons::AddChainResponse,
+    sth: jsons::STH,
+    reason: String,
+}
+
+impl MmdViolation {
+    /// Check the signatures on both the SCT and the tree head.
+    pub fn verify(&self, log_pub_key: &PKey<openssl::pkey::Public>) -> Result<(), Error> {
+        self.sct.verify(log_pub_key)?;
+        self.sth.verify(log_pub_key)
+    }
+
+    /// The evidence as a JSON object, with the SCT in the format of an `add-chain` response and
+    /// the tree head in the format of `get-sth`. Binary fields are base64 encoded.
+    pub fn to_json(&self) -> Result<String, Error> {
+        let entry = match &self.sct.entry {
+            SctEntry::X509(der) => EvidenceEntry::X509(BASE64_STANDARD.encode(der)),
+            SctEntry::PreCert {
+                tbs,
+                issuer_key_hash,
+            } => EvidenceEntry::Precert {
+                tbs: BASE64_STANDARD.encode(tbs),
+                issuer_key_hash: BASE64_STANDARD.encode(issuer_key_hash),
+            },
+        };
+        let evidence = Evidence {
+            log_id: BASE64_STANDARD.encode(self.sct.log_id),
+            deadline: self.deadline,
+            leaf_hash: BASE64_STANDARD.encode(self.sct.derive_leaf_hash()),
+            entry,
+            sct: self.sct.to_add_chain_response()?,
+            sth: jsons::STH {
+                tree_size: self.sth.tree_size,
+                timestamp: self.sth.timestamp,
+                sha256_root_hash: BASE64_STANDARD.encode(self.sth.root_hash),
+                tree_head_signature: BASE64_STANDARD.encode(&self.sth.signature),
+            },
+            reason: self.reason.clone(),
+        };
+        serde_json::to_string(&evidence)
+            .map_err(|e| Error::Unknown(format!("Unable to serialize evidence: {}", e)))
+    }
+}
+
+struct AuditedLog {
+    log: Log,
+    client: Option<CTClient>,
+    /// The latest tree head `client` has checked.
+    latest_sth: Option<SignedTreeHead>,
+}
+
+impl AuditedLog {
+    fn new(log: Log) -> Self {
+        AuditedLog {
+            log,
+            client: None,
+            latest_sth: None,
+        }
+    }
+
+    /// Whether we need a newer tree head than the one we have to audit a SCT due at `deadline`.
+    fn needs_sth(&self, deadline: u64) -> bool {
+        match &self.latest_sth {
+            None => true,
+            // A read-only log will not sign anything newer, and its final tree head has to contain
+            // every SCT it issued, whatever the deadline.
+            Some(_) if self.log.state == LogState::Readonly => false,
+            Some(sth) => sth.timestamp < deadline,
+        }
+    }
+
+    async fn refresh(&mut self) -> Result<(), Error> {
+        if self.client.is_none() {
+            let mut client = match self.log.api {
+                LogApi::Rfc6962 => {
+                    CTClient::new_from_latest_th(&self.log.base_url, &self.log.pub_key).await?
+                }
+                LogApi::StaticCt => {
+                    CTClient::new_static_ct_from_latest_th(&self.log.base_url, &self.log.pub_key)
+                        .await?
+                }
+            };
+            client.set_log_state(Some(self.log.state), self.log.final_tree_head)?;
+            self.client = Some(client);
+        }
+        match self.client.as_mut().unwrap().light_update().await {
+            SthResult::Ok(sth) => {
+                self.latest_sth = Some(sth);
+                Ok(())
+            }
+            other => Err(other.unwrap_err()),
+        }
+    }
+}
+
+struct QueuedSct {
+    sct: SignedCertificateTimestamp,
+    deadline: u64,
+    attempts: u32,
+    /// Number of times the log failed to prove the SCT is included.
+    not_included: u32,
+    next_try: u64,
+}
+
+/// Whether `e`, returned when checking the inclusion of a SCT in a tree head, says that the SCT
+/// is not in the tree, as opposed to a problem talking to the log.
+///
+/// Log frontends also give these answers transiently, for example when the replica answering is
+/// behind the tree head, so they have to persist before they count as a violation.
+fn is_not_included(e: &Error) -> bool {
+    match e {
+        Error::InvalidInclusionProof { .. } => true,
+        // Static CT API, the leaf index of the SCT is beyond the tree size.
+        Error::InvalidArgument(_) => true,
+        // get-proof-by-hash, the hash is not in the tree.
+        Error::InvalidResponseStatus(status) => {
+            *status == reqwest::StatusCode::BAD_REQUEST || *status == reqwest::StatusCode::NOT_FOUND
+        }
+        _ => false,
+    }
+}
+
+/// Queues SCTs and checks that each one is incorporated by its log within the MMD. See the
+/// [module documentation](self).
+pub struct SctAuditor {
+    logs: HashMap<Vec<u8>, AuditedLog>,
+    queue: Vec<QueuedSct>,
+    initial_backoff: u64,
+    max_backoff: u64,
+    max_attempts: u32,
+    violation_attempts: u32,
+}
+
+impl SctAuditor {
+    /// Audit SCTs from the logs in `log_list`. The MMD of each log is taken from the list.
+    pub fn new(log_list: &LogList) -> Self {
+        SctAuditor {
+            logs: log_list
+                .map_id_to_log
+                .iter()
+                .map(|(id, log)| (id.clone(), AuditedLog::new(log.clone())))
+                .collect(),
+            queue: Vec::new(),
+            initial_backoff: 60 * 1000,
+            max_backoff: 60 * 60 * 1000,
+            max_attempts: 20,
+            violation_attempts: 3,
+        }
+    }
+
+    /// Take the logs from a newer list, for example one from a
+    /// [`LogListWatcher`](crate::google_log_list::LogListWatcher). Connections to logs still in
+    /// the list are kept.
+    pub fn update_log_list(&mut self, log_list: &LogList) {
+        let mut old = std::mem::take(&mut self.logs);
+        for (id, log) in log_list.map_id_to_log.iter() {
+            let audited = match old.remove(id) {
+                Some(mut audited) => {
+                    if let Some(client) = audited.client.as_mut()
+                        && client
+                            .set_log_state(Some(log.state), log.final_tree_head)
+                            .is_err()
+                    {
+                        audited.client = None;
+                        audited.latest_sth = None;
+                    }
+                    audited.log = log.clone();
+                    audited
+                }
+                None => AuditedLog::new(log.clone()),
+            };
+            self.logs.insert(id.clone(), audited);
+        }
+    }
+
+    /// After a transient failure, wait `initial` ms before trying again, doubling every time up
+    /// to `max` ms. Defaults to 1 minute and 1 hour.
+    pub fn set_backoff(&mut self, initial: u64, max: u64) {
+        self.initial_backoff = initial;
+        self.max_backoff = max;
+    }
+
+    /// Give up on a SCT after this many failed tries, with [`AuditOutcome::Failed`]. Defaults to
+    /// 20.
+    pub fn set_max_attempts(&mut self, max_attempts: u32) {
+        self.max_attempts = max_attempts;
+    }
+
+    /// Only report a [`MmdViolation`] once the log has failed to prove that a SCT is included this
+    /// many times, each after a backoff like other failures. Defaults to 3.
+    pub fn set_violation_attempts(&mut self, violation_attempts: u32) {
+        self.violation_attempts = violation_attempts.max(1);
+    }
+
+    /// Queue `sct` to be audited, and return its deadline in ms since the epoch.
+    ///
+    /// The signature on the SCT is not checked here. Use
+    /// [`SignedCertificateTimestamp::verify`] or [`check_scts`](crate::check_scts) first.
+    pub fn add(&mut self, sct: SignedCertificateTimestamp) -> Result<u64, Error> {
+        let log = self
+            .logs
+            .get(&sct.log_id[..])
+            .ok_or_else(|| Error::UnknownLog(BASE64_STANDARD.encode(sct.log_id)))?;
+        let deadline = sct
+            .timestamp
+            .saturating_add(log.log.mmd.saturating_mul(1000));
+        self.queue.push(QueuedSct {
+            sct,
+            deadline,
+            attempts: 0,
+            not_included: 0,
+            next_try: deadline,
+        });
+        Ok(deadline)
+    }
+
+    /// Number of SCTs waiting to be audited.
+    pub fn pending_count(&self) -> usize {
+        self.queue.len()
+    }
+
+    /// The time, in ms since the epoch, when [`Self::poll`] next has something to do, or `None`
+    /// if nothing is queued.
+    pub fn next_due(&self) -> Option<u64> {
+        self.queue.iter().map(|q| q.next_try).min()
+    }
+
+    /// Audit all SCTs that are due at `now` (in ms since the epoch), and return what happened to
+    /// them. SCTs that hit a transient failure are queued again with a backoff, and are not in the
+    /// returned list.
+    ///
+    /// Each log is asked for a new tree head at most once per call.
+    pub async fn poll(&mut self, now: u64) -> Vec<AuditOutcome> {
+        let (due, waiting): (Vec<_>, Vec<_>) = std::mem::take(&mut self.queue)
+            .into_iter()
+            .partition(|q| q.next_try <= now);
+        self.queue = waiting;
+
+        let mut refresh_errors: HashMap<Vec<u8>, String> = HashMap::new();
+        let mut refreshed = Vec::new();
+        for q in due.iter() {
+            let id = &q.sct.log_id[..];
+            if refreshed.iter().any(|r: &Vec<u8>| &r[..] == id) {
+                continue;
+            }
+            if let Some(log) = self.logs.get_mut(id)
+                && log.log.state != LogState::Retired
+                && log.needs_sth(q.deadline)
+            {
+                refreshed.push(id.to_vec());
+                if let Err(e) = log.refresh().await {
+                    warn!("Unable to get a tree head from {}: {}", log.log.base_url, e);
+                    refresh_errors.insert(id.to_vec(), e.to_string());
+                }
+            }
+        }
+
+        let mut outcomes = Vec::new();
+        let mut retries = Vec::new();
+        for mut q in due {
+            let log = match self.logs.get(&q.sct.log_id[..]) {
+                Some(log) => log,
+                None => {
+                    outcomes.push(AuditOutcome::Failed {
+                        sct: q.sct,
+                        reason: "The log is no longer in the log list.".to_owned(),
+                    });
+                    continue;
+                }
+            };
+            if log.log.state == LogState::Retired {
+                outcomes.push(AuditOutcome::Failed {
+                    sct: q.sct,
+                    reason: "The log is retired.".to_owned(),
+                });
+                continue;
+            }
+            let (client, sth) = match (&log.client, &log.latest_sth) {
+                (Some(client), Some(sth)) if !log.needs_sth(q.deadline) => (client, sth.clone()),
+                _ => {
+                    let reason = refresh_errors
+                        .get(&q.sct.log_id[..])
+                        .cloned()
+                        .unwrap_or_else(|| {
+                            "The log has not signed a tree head after the deadline yet.".to_owned()
+                        });
+                    retries.push((q, reason));
+                    continue;
+                }
+            };
+            match client.check_inclusion_proof_for_sct(&q.sct).await {
+                Ok(leaf_index) => {
+                    debug!("SCT from {} included at {}", log.log.base_url, leaf_index);
+                    outcomes.push(AuditOutcome::Included {
+                        sct: q.sct,
+                        leaf_index,
+                        sth,
+                    });
+                }
+                Err(e) if is_not_included(&e) => {
+                    q.not_included += 1;
+                    if q.not_included < self.violation_attempts {
+                        debug!(
+                            "{} did not prove a SCT is included ({} times): {}",
+                            log.log.base_url, q.not_included, e
+                        );
+                        retries.push((q, e.to_string()));
+                        continue;
+                    }
+                    warn!("{} did not include a SCT in time: {}", log.log.base_url, e);
+                    outcomes.push(AuditOutcome::Violation(MmdViolation {
+                        sct: q.sct,
+                        deadline: q.deadline,
+                        sth,
+                        reason: e.to_string(),
+                    }));
+                }
+                Err(e @ Error::BadSct(_)) => {
+                    outcomes.push(AuditOutcome::Failed {
+                        sct: q.sct,
+                        reason: e.to_string(),
+                    });
+                }
+                Err(e) => retries.push((q, e.to_string())),
+            }
+        }
+        for (q, reason) in retries {
+            outcomes.extend(self.retry(q, now, reason));
+        }
+        outcomes
+    }
+
+    /// Queue `q` again after a backoff, or give up on it.
+    fn retry(&mut self, mut q: QueuedSct, now: u64, reason: String) -> Option<AuditOutcome> {
+        q.attempts += 1;
+        if q.attempts >= self.max_attempts {
+            return Some(AuditOutcome::Failed { sct: q.sct, reason });
+        }
+        let backoff = self
+            .initial_backoff
+            .saturating_mul(1u64 << (q.attempts - 1).min(32))
+            .min(self.max_backoff);
+        q.next_try = now.saturating_add(backoff);
+        debug!("Will audit SCT again at {}: {}", q.next_try, reason);
+        self.queue.push(q);
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock_log::MockLog;
+    use crate::test_logs;
+
+    fn test_list(state: LogState) -> LogList {
+        test_logs::list(vec![test_logs::log(test_logs::key_der(0), "A", state, 0)])
+    }
+
+    #[test]
+    fn queue_test() {
+        let mut scts = test_logs::scts().1.into_iter();
+        let sct = scts.next().unwrap();
+        let other_log_sct = scts.next().unwrap();
+        let mut auditor = SctAuditor::new(&test_list(LogState::Usable));
+        assert_eq!(auditor.next_due(), None);
+        let deadline = auditor.add(sct.clone()).unwrap();
+        assert_eq!(deadline, sct.timestamp + 86400 * 1000);
+        assert!(matches!(
+            auditor.add(other_log_sct),
+            Err(Error::UnknownLog(_))
+        ));
+        assert_eq!(auditor.pending_count(), 1);
+        assert_eq!(auditor.next_due(), Some(deadline));
+
+        // Nothing is due yet, so this does not touch the network.
+        let outcomes = tokio_test::block_on(auditor.poll(deadline - 1));
+        assert!(outcomes.is_empty());
+        assert_eq!(auditor.pending_count(), 1);
+
+        auditor.update_log_list(&test_list(LogState::Retired));
+        let outcomes = tokio_test::block_on(auditor.poll(deadline));
+        assert!(matches!(&outcomes[..], [AuditOutcome::Failed { .. }]));
+        assert_eq!(auditor.pending_count(), 0);
+    }
+
+    #[tokio::test]
+    async fn poll_test() {
+        let log = MockLog::new(100);
+        let mut mock = test_logs::log(log.pub_key.clone(), "A", LogState::Usable, 0);
+        mock.base_url = log.url.clone();
+        mock.mmd = 0;
+        let mut auditor = SctAuditor::new(&test_logs::list(vec![mock]));
+        auditor.set_backoff(1000, 4000);
+        // Leaf 42 of the log, and a SCT the log never added.
+        let included = log.sct(1735689600042);
+        let missing = log.sct(1735689599999);
+        auditor.add(included).unwrap();
+        auditor.add(missing.clone()).unwrap();
+        let now = 1735689700000;
+
+        // A transient failure is retried after a backoff.
+        log.set_fail_proofs(true);
+        assert!(auditor.poll(now).await.is_empty());
+        assert_eq!(auditor.pending_count(), 2);
+        assert_eq!(auditor.next_due(), Some(now + 1000));
+        log.set_fail_proofs(false);
+
+        let outcomes = auditor.poll(now + 1000).await;
+        match &outcomes[..] {
+            [
+                AuditOutcome::Included {
+                    leaf_index, sth, ..
+                },
+            ] => {
+                assert_eq!(*leaf_index, 42);
+                assert_eq!(sth.tree_size, 100);
+            }
+            other => panic!("Expected one included SCT, got {:?}", other),
+        }
+
+        // The missing SCT is only reported once the log failed to find it 3 times.
+        assert_eq!(auditor.pending_count(), 1);
+        assert_eq!(auditor.next_due(), Some(now + 3000));
+        assert!(auditor.poll(now + 3000).await.is_empty());
+        assert_eq!(auditor.next_due(), Some(now + 7000));
+        let outcomes = auditor.poll(now + 7000).await;
+        match &outcomes[..] {
+            [AuditOutcome::Violation(v)] => {
+                assert_eq!(v.deadline, missing.timestamp);
+                assert!(v.sth.timestamp >= v.deadline);
+                v.verify(&PKey::public_key_from_der(&log.pub_key).unwrap())
+                    .unwrap();
+            }
+            other => panic!("Expected a violation, got {:?}", other),
+        }
+        assert_eq!(auditor.pending_count(), 0);
+    }
+
+    #[test]
+    fn evidence_test() {
+        let sct = test_logs::scts().1.into_iter().next().unwrap();
+        let violation = MmdViolation {
+            deadline: sct.timestamp + 86400 * 1000,
+            sct,
+            sth: SignedTreeHead {
+                tree_size: 10,
+                timestamp: 1735776000000,
+                root_hash: [1u8; 32],
+                signature: vec![4, 3, 0, 0],
+            },
+            reason: "not found".to_owned(),
+        };
+        let pub_key = test_logs::key(0);
+        violation.sct.verify(&pub_key).unwrap();
+        violation
+            .verify(&pub_key)
+            .expect_err("STH signature is bogus");
+
+        let json: serde_json::Value = serde_json::from_str(&violation.to_json().unwrap()).unwrap();
+        assert_eq!(json["deadline"], violation.deadline);
+        assert_eq!(json["sct"]["timestamp"], violation.sct.timestamp);
+        assert_eq!(json["sth"]["tree_size"], 10);
+        assert!(json["entry"]["x509"].is_string());
+        assert_eq!(
+            BASE64_STANDARD
+                .decode(json["leaf_hash"].as_str().unwrap())
+                .unwrap(),
+            violation.sct.derive_leaf_hash()
+        );
+
+        assert!(is_not_included(&Error::InvalidResponseStatus(
+            reqwest::StatusCode::BAD_REQUEST
+        )));
+        assert!(!is_not_included(&Error::InvalidResponseStatus(
+            reqwest::StatusCode::SERVICE_UNAVAILABLE
+        )));
+    }
+}
@@ -1,6 +1,7 @@
 //! Verious utilities for checking the content of a certificate.
 
 use crate::Error;
+use openssl::asn1::{Asn1Time, Asn1TimeRef};
 use openssl::x509::X509Ref;
 
 /// Return the common name of the certificate. Usually will only contains one. May be empty.
@@ -49,3 +50,35 @@ pub fn get_dns_names<R: AsRef<X509Ref>>(cert: &R) -> Result<Vec<String>, Error>
     }
     Ok(names)
 }
+
+fn asn1_time_to_ms(t: &Asn1TimeRef) -> Result<u64, Error> {
+    let epoch =
+        Asn1Time::from_unix(0).map_err(|e| Error::Unknown(format!("ASN1_TIME_set: {}", &e)))?;
+    let diff = epoch
+        .diff(t)
+        .map_err(|e| Error::BadCertificate(format!("Invalid time {}: {}", t, &e)))?;
+    let secs = i64::from(diff.days) * 86400 + i64::from(diff.secs);
+    if secs < 0 {
+        return Err(Error::BadCertificate(format!("Time {} is before 1970", t)));
+    }
+    Ok(secs as u64 * 1000)
+}
+
+/// Return the notBefore of the certificate, in ms since the epoch.
+pub fn get_not_before<R: AsRef<X509Ref>>(cert: &R) -> Result<u64, Error> {
+    asn1_time_to_ms(cert.as_ref().not_before())
+}
+
+/// Return the notAfter of the certificate, in ms since the epoch. This is what temporal shards
+/// go by, see [`crate::google_log_list::LogList::logs_accepting`].
+pub fn get_not_after<R: AsRef<X509Ref>>(cert: &R) -> Result<u64, Error> {
+    asn1_time_to_ms(cert.as_ref().not_after())
+}
+
+#[test]
+fn validity_test() {
+    let cert =
+        openssl::x509::X509::from_pem(include_bytes!("test_data/precert-signing-ca.pem")).unwrap();
+    assert_eq!(get_not_before(&cert).unwrap(), 1590263471000);
+    assert_eq!(get_not_after(&cert).unwrap(), 1597471566000);
+}
@@ -0,0 +1,635 @@
+//! [Signed notes](https://c2sp.org/signed-note) and [checkpoints](https://c2sp.org/tlog-checkpoint).
+//!
+//! A checkpoint is the tree head format used by tiled logs (see [`internal::tiles`](crate::internal::tiles))
+//! and transparency witnesses. It is a piece of text like
+//!
+//! ```text
+//! example.com/log2025
+//! 12345
+//! 2vUQsVV8MoxqBWCrOAJBGo5LyQsMj2sYxCSDlNunfWo=
+//!
+//! — example.com/log2025 Az3grlgtzPICa5OS8npVmf1Myq/5IZniMp+ZJurmRDeOoRDe4URYN7u5/Zhcyv2q1gGzGku9nTo+zyWE+xeMcTOAYQ8=
+//! ```
+//!
+//! where the first part (origin, tree size, root hash and optional extension lines) is signed by
+//! each signature line after the blank line. A CT log signs its checkpoints with a RFC 6962 note
+//! signature, which wraps an ordinary [`SignedTreeHead`] signature, so a checkpoint can be turned
+//! into a [`SignedTreeHead`] and back.
+//!
+//! Checkpoints can also be cosigned by [witnesses](https://c2sp.org/tlog-witness), which promise
+//! that they have checked the checkpoint to be consistent with every other checkpoint of the log
+//! they have seen. [`WitnessPolicy`] checks that enough of a set of witnesses did so.
+
+use std::convert::TryInto;
+use std::fmt;
+
+use base64::prelude::{BASE64_STANDARD, Engine as _};
+use log::warn;
+use openssl::pkey::PKey;
+
+use crate::utils::sha256;
+use crate::{Error, SignedTreeHead};
+
+/// Signature type byte of a RFC 6962 note signature, used to compute its key ID.
+pub const SIG_TYPE_RFC6962: u8 = 0x05;
+
+/// Signature type byte of a [timestamped Ed25519 cosignature](https://c2sp.org/tlog-cosignature),
+/// used to compute its key ID.
+pub const SIG_TYPE_COSIGNATURE: u8 = 0x04;
+
+/// Compute the key ID of a note verifier key: `SHA-256(name || 0x0A || sig_type || key)[:4]`.
+pub fn key_id(name: &str, sig_type: u8, key: &[u8]) -> [u8; 4] {
+    let mut data = Vec::with_capacity(name.len() + 2 + key.len());
+    data.extend_from_slice(name.as_bytes());
+    data.push(b'\n');
+    data.push(sig_type);
+    data.extend_from_slice(key);
+    sha256(&data)[..4].try_into().unwrap()
+}
+
+/// Key ID of the RFC 6962 note signature a CT log with this origin and key would make.
+///
+/// The key of such a signature is the log ID, which is `SHA-256(SubjectPublicKeyInfo)`, not
+/// the public key itself.
+pub fn rfc6962_key_id(
+    origin: &str,
+    pub_key: &PKey<openssl::pkey::Public>,
+) -> Result<[u8; 4], Error> {
+    let spki = pub_key
+        .public_key_to_der()
+        .map_err(|e| Error::Unknown(format!("While encoding public key: {}", &e)))?;
+    Ok(key_id(origin, SIG_TYPE_RFC6962, &sha256(&spki)))
+}
+
+/// A signature line of a signed note.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NoteSignature {
+    /// Name of the key that made this signature.
+    pub name: String,
+    pub key_id: [u8; 4],
+    /// The signature, without the key ID. Its format depends on the type of the key.
+    pub signature: Vec<u8>,
+}
+
+impl fmt::Display for NoteSignature {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut sig = Vec::with_capacity(4 + self.signature.len());
+        sig.extend_from_slice(&self.key_id);
+        sig.extend_from_slice(&self.signature);
+        writeln!(f, "\u{2014} {} {}", self.name, BASE64_STANDARD.encode(&sig))
+    }
+}
+
+/// A signed note, which is a text and some signatures on it. Signatures are not verified by
+/// parsing.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SignedNote {
+    /// The signed text, including its final newline.
+    pub text: String,
+    pub signatures: Vec<NoteSignature>,
+}
+
+impl SignedNote {
+    /// Parse a note. The text and signatures are separated by the last blank line.
+    pub fn parse(note: &str) -> Result<Self, Error> {
+        fn err_invalid(desc: &str) -> Error {
+            Error::BadCheckpoint(format!("Invalid note: {}", desc))
+        }
+        if note.chars().any(|c| c.is_control() && c != '\n') {
+            return Err(err_invalid("contains control characters"));
+        }
+        if !note.ends_with('\n') {
+            return Err(err_invalid("does not end with a newline"));
+        }
+        let split = note
+            .rfind("\n\n")
+            .ok_or_else(|| err_invalid("no signature"))?;
+        let text = &note[..split + 1];
+        let mut signatures = Vec::new();
+        for line in note[split + 2..].lines() {
+            let (name, sig) = line
+                .strip_prefix("\u{2014} ")
+                .and_then(|l| l.split_once(' '))
+                .ok_or_else(|| err_invalid("bad signature line"))?;
+            if name.is_empty() || name.contains('+') {
+                return Err(err_invalid("bad key name"));
+            }
+            let sig = BASE64_STANDARD
+                .decode(sig)
+                .map_err(|_| err_invalid("bad signature encoding"))?;
+            if sig.len() < 4 {
+                return Err(err_invalid("signature too short"));
+            }
+            signatures.push(NoteSignature {
+                name: name.to_owned(),
+                key_id: sig[..4].try_into().unwrap(),
+                signature: sig[4..].to_vec(),
+            });
+        }
+        if signatures.is_empty() {
+            return Err(err_invalid("no signature"));
+        }
+        Ok(SignedNote {
+            text: text.to_owned(),
+            signatures,
+        })
+    }
+
+    /// Signatures made by the key with this name and ID.
+    pub fn signatures_by<'a>(
+        &'a self,
+        name: &'a str,
+        key_id: &'a [u8; 4],
+    ) -> impl Iterator<Item = &'a NoteSignature> + 'a {
+        self.signatures
+            .iter()
+            .filter(move |s| s.name == name && &s.key_id == key_id)
+    }
+
+    /// Parse the text of this note as a checkpoint.
+    pub fn checkpoint(&self) -> Result<Checkpoint, Error> {
+        Checkpoint::parse(&self.text)
+    }
+
+    /// Check that this note is a checkpoint signed by the CT log with key `pub_key`, and return
+    /// the tree head it stands for.
+    ///
+    /// The log is identified by the checkpoint's origin line. Signatures from other keys (such as
+    /// witnesses) are ignored.
+    pub fn verify_rfc6962(
+        &self,
+        pub_key: &PKey<openssl::pkey::Public>,
+    ) -> Result<SignedTreeHead, Error> {
+        let checkpoint = self.checkpoint()?;
+        let key_id = rfc6962_key_id(&checkpoint.origin, pub_key)?;
+        let sig = self
+            .signatures_by(&checkpoint.origin, &key_id)
+            .next()
+            .ok_or_else(|| {
+                Error::InvalidSignature(format!(
+                    "Checkpoint for {} is not signed by the log's key.",
+                    &checkpoint.origin
+                ))
+            })?;
+        // timestamp || TreeHeadSignature digitally-signed struct
+        if sig.signature.len() < 8 {
+            return Err(Error::BadCheckpoint(
+                "RFC 6962 note signature too short.".to_owned(),
+            ));
+        }
+        let sth = SignedTreeHead {
+            tree_size: checkpoint.tree_size,
+            timestamp: u64::from_be_bytes(sig.signature[..8].try_into().unwrap()),
+            root_hash: checkpoint.root_hash,
+            signature: sig.signature[8..].to_vec(),
+        };
+        sth.verify(pub_key)?;
+        Ok(sth)
+    }
+
+    /// Encode a tree head as a checkpoint signed by the log, with the given origin.
+    ///
+    /// This does not need the log's private key, since the note signature is just the STH
+    /// signature with the timestamp prepended. The signature is not checked.
+    pub fn from_sth(
+        origin: &str,
+        sth: &SignedTreeHead,
+        pub_key: &PKey<openssl::pkey::Public>,
+    ) -> Result<Self, Error> {
+        let mut signature = Vec::with_capacity(8 + sth.signature.len());
+        signature.extend_from_slice(&sth.timestamp.to_be_bytes());
+        signature.extend_from_slice(&sth.signature);
+        Ok(SignedNote {
+            text: Checkpoint::from_sth(origin, sth).to_string(),
+            signatures: vec![NoteSignature {
+                name: origin.to_owned(),
+                key_id: rfc6962_key_id(origin, pub_key)?,
+                signature,
+            }],
+        })
+    }
+}
+
+impl fmt::Display for SignedNote {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{}", &self.text)?;
+        for sig in self.signatures.iter() {
+            write!(f, "{}", sig)?;
+        }
+        Ok(())
+    }
+}
+
+/// The text of a checkpoint note.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Checkpoint {
+    /// Unique identifier of the log, like `example.com/log2025`.
+    pub origin: String,
+    pub tree_size: u64,
+    pub root_hash: [u8; 32],
+    /// Optional extra lines after the root hash.
+    pub extensions: Vec<String>,
+}
+
+impl Checkpoint {
+    /// Parse the text of a checkpoint note (not including signatures).
+    pub fn parse(text: &str) -> Result<Self, Error> {
+        fn err_invalid(desc: &str) -> Error {
+            Error::BadCheckpoint(format!("Invalid checkpoint: {}", desc))
+        }
+        let lines: Vec<&str> = text
+            .strip_suffix('\n')
+            .ok_or_else(|| err_invalid("does not end with a newline"))?
+            .split('\n')
+            .collect();
+        if lines.len() < 3 {
+            return Err(err_invalid("too few lines"));
+        }
+        if lines.iter().any(|l| l.is_empty()) {
+            return Err(err_invalid("empty line"));
+        }
+        let size_line = lines[1];
+        if !size_line.bytes().all(|b| b.is_ascii_digit())
+            || (size_line.len() > 1 && size_line.starts_with('0'))
+        {
+            return Err(err_invalid("bad tree size"));
+        }
+        let tree_size = size_line
+            .parse()
+            .map_err(|_| err_invalid("bad tree size"))?;
+        let root_hash = BASE64_STANDARD
+            .decode(lines[2])
+            .ok()
+            .and_then(|h| h[..].try_into().ok())
+            .ok_or_else(|| err_invalid("bad root hash"))?;
+        Ok(Checkpoint {
+            origin: lines[0].to_owned(),
+            tree_size,
+            root_hash,
+            extensions: lines[3..].iter().map(|l| (*l).to_owned()).collect(),
+        })
+    }
+
+    /// The checkpoint for a tree head, with no extensions.
+    pub fn from_sth(origin: &str, sth: &SignedTreeHead) -> Self {
+        Checkpoint {
+            origin: origin.to_owned(),
+            tree_size: sth.tree_size,
+            root_hash: sth.root_hash,
+            extensions: Vec::new(),
+        }
+    }
+
+    /// Whether this checkpoint describes the same tree as `sth`.
+    pub fn matches_sth(&self, sth: &SignedTreeHead) -> bool {
+        self.tree_size == sth.tree_size && self.root_hash == sth.root_hash
+    }
+}
+
+impl fmt::Display for Checkpoint {
+    /// Format as the text of a checkpoint note, ending with a newline.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{}", &self.origin)?;
+        writeln!(f, "{}", self.tree_size)?;
+        writeln!(f, "{}", BASE64_STANDARD.encode(self.root_hash))?;
+        for ext in self.extensions.iter() {
+            writeln!(f, "{}", ext)?;
+        }
+        Ok(())
+    }
+}
+
+/// The message signed by a witness cosignature made at `timestamp` (seconds since the epoch) over
+/// the checkpoint `text`.
+pub fn cosignature_message(text: &str, timestamp: u64) -> Vec<u8> {
+    format!("cosignature/v1\ntime {}\n{}", timestamp, text).into_bytes()
+}
+
+/// The Ed25519 public key of a witness.
+#[derive(Debug, Clone)]
+pub struct WitnessKey {
+    pub name: String,
+    pub key_id: [u8; 4],
+    key: PKey<openssl::pkey::Public>,
+}
+
+impl WitnessKey {
+    /// Construct from the witness's name and raw 32-byte Ed25519 public key.
+    pub fn new(name: &str, ed25519_key: &[u8]) -> Result<Self, Error> {
+        if name.is_empty() || name.contains(|c: char| c == '+' || c.is_whitespace()) {
+            return Err(Error::InvalidArgument(format!(
+                "Invalid witness name {:?}",
+                name
+            )));
+        }
+        let key = PKey::public_key_from_raw_bytes(ed25519_key, openssl::pkey::Id::ED25519)
+            .map_err(|e| Error::InvalidArgument(format!("Invalid Ed25519 key: {}", &e)))?;
+        Ok(WitnessKey {
+            name: name.to_owned(),
+            key_id: key_id(name, SIG_TYPE_COSIGNATURE, ed25519_key),
+            key,
+        })
+    }
+
+    /// Parse a verifier key string like `witness.example+1234abcd+BASE64`, where the base64 part
+    /// is the cosignature type byte (`0x04`) followed by the Ed25519 public key.
+    pub fn from_vkey(vkey: &str) -> Result<Self, Error> {
+        fn err_invalid() -> Error {
+            Error::InvalidArgument("Invalid witness verifier key.".to_owned())
+        }
+        // Names can't contain '+', but base64 can.
+        let mut parts = vkey.splitn(3, '+');
+        let (name, hex_id, key) = match (parts.next(), parts.next(), parts.next()) {
+            (Some(n), Some(h), Some(k)) => (n, h, k),
+            _ => return Err(err_invalid()),
+        };
+        let key = BASE64_STANDARD.decode(key).map_err(|_| err_invalid())?;
+        if key.len() != 33 || key[0] != SIG_TYPE_COSIGNATURE {
+            return Err(err_invalid());
+        }
+        let witness = WitnessKey::new(name, &key[1..])?;
+        if hex_id.len() != 8 || crate::utils::u8_to_hex(&witness.key_id) != hex_id {
+            return Err(Error::InvalidArgument(
+                "Witness verifier key has the wrong key ID.".to_owned(),
+            ));
+        }
+        Ok(witness)
+    }
+
+    /// Look for a cosignature from this witness on `note`, and return its timestamp.
+    ///
+    /// Returns `Ok(None)` if the witness did not sign the note, and an error if it did but the
+    /// signature is invalid.
+    pub fn verify(&self, note: &SignedNote) -> Result<Option<u64>, Error> {
+        let sig = match note.signatures_by(&self.name, &self.key_id).next() {
+            Some(s) => s,
+            None => return Ok(None),
+        };
+        // timestamp || Ed25519 signature
+        if sig.signature.len() != 8 + 64 {
+            return Err(Error::InvalidSignature(format!(
+                "Cosignature from {} has the wrong length.",
+                &self.name
+            )));
+        }
+        let timestamp = u64::from_be_bytes(sig.signature[..8].try_into().unwrap());
+        let mut verifier = openssl::sign::Verifier::new_without_digest(&self.key)
+            .map_err(|e| Error::Unknown(format!("EVP_DigestVerifyInit: {}", &e)))?;
+        let valid = verifier
+            .verify_oneshot(
+                &sig.signature[8..],
+                &cosignature_message(&note.text, timestamp),
+            )
+            .unwrap_or(false);
+        if !valid {
+            return Err(Error::InvalidSignature(format!(
+                "Invalid cosignature from {}.",
+                &self.name
+            )));
+        }
+        Ok(Some(timestamp))
+    }
+}
+
+/// Require a checkpoint to be cosigned by at least `quorum` out of a set of witnesses.
+#[derive(Debug, Clone)]
+pub struct WitnessPolicy {
+    pub witnesses: Vec<WitnessKey>,
+    pub quorum: usize,
+}
+
+impl WitnessPolicy {
+    pub fn new(witnesses: Vec<WitnessKey>, quorum: usize) -> Result<Self, Error> {
+        if quorum == 0 || quorum > witnesses.len() {
+            return Err(Error::InvalidArgument(format!(
+                "Can't require {} out of {} witnesses.",
+                quorum,
+                witnesses.len()
+            )));
+        }
+        Ok(WitnessPolicy { witnesses, quorum })
+    }
+
+    /// Check that `note` is cosigned by enough witnesses, and return the names of those that did.
+    ///
+    /// An invalid cosignature does not count, but does not make the check fail either, so one
+    /// broken witness can't hold back a note the others cosigned.
+    ///
+    /// This does not check the log's own signature. Use [`SignedNote::verify_rfc6962`] for that.
+    pub fn check(&self, note: &SignedNote) -> Result<Vec<&str>, Error> {
+        let mut cosigned_by = Vec::new();
+        for witness in self.witnesses.iter() {
+            match witness.verify(note) {
+                Ok(Some(_)) => cosigned_by.push(&witness.name[..]),
+                Ok(None) => {}
+                Err(e) => warn!("Ignoring cosignature: {}", e),
+            }
+        }
+        if cosigned_by.len() < self.quorum {
+            return Err(Error::NotWitnessed(format!(
+                "cosigned by {} out of {} required witnesses",
+                cosigned_by.len(),
+                self.quorum
+            )));
+        }
+        Ok(cosigned_by)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use openssl::ec::{EcGroup, EcKey};
+    use openssl::hash::MessageDigest;
+    use openssl::nid::Nid;
+    use openssl::sign::Signer;
+
+    fn signed_sth(key: &PKey<openssl::pkey::Private>) -> SignedTreeHead {
+        let (timestamp, tree_size, root_hash) = (1700000000000u64, 12345u64, [7u8; 32]);
+        let mut body = vec![0u8, 1u8];
+        body.extend_from_slice(&timestamp.to_be_bytes());
+        body.extend_from_slice(&tree_size.to_be_bytes());
+        body.extend_from_slice(&root_hash);
+        let mut signer = Signer::new(MessageDigest::sha256(), key).unwrap();
+        let raw = signer.sign_oneshot_to_vec(&body).unwrap();
+        // sha256, ecdsa
+        let mut signature = vec![4u8, 3u8];
+        signature.extend_from_slice(&(raw.len() as u16).to_be_bytes());
+        signature.extend_from_slice(&raw);
+        SignedTreeHead {
+            tree_size,
+            timestamp,
+            root_hash,
+            signature,
+        }
+    }
+
+    #[test]
+    fn rfc6962_checkpoint_roundtrip() {
+        let key =
+            EcKey::generate(&EcGroup::from_curve_name(Nid::X9_62_PRIME256V1).unwrap()).unwrap();
+        let key = PKey::from_ec_key(key).unwrap();
+        let pub_key = PKey::public_key_from_der(&key.public_key_to_der().unwrap()).unwrap();
+        let sth = signed_sth(&key);
+
+        let note = SignedNote::from_sth("example.com/log", &sth, &pub_key).unwrap();
+        let text = note.to_string();
+        assert!(text.starts_with("example.com/log\n12345\n"));
+        let parsed = SignedNote::parse(&text).unwrap();
+        assert_eq!(parsed, note);
+        assert_eq!(parsed.verify_rfc6962(&pub_key).unwrap(), sth);
+        assert!(parsed.checkpoint().unwrap().matches_sth(&sth));
+
+        // A signature from some other key is ignored.
+        let mut with_other = parsed.clone();
+        with_other.signatures.insert(
+            0,
+            NoteSignature {
+                name: "witness.example".to_owned(),
+                key_id: [1, 2, 3, 4],
+                signature: vec![0; 72],
+            },
+        );
+        let with_other = SignedNote::parse(&with_other.to_string()).unwrap();
+        assert_eq!(with_other.verify_rfc6962(&pub_key).unwrap(), sth);
+
+        // Changing the tree makes the signature invalid.
+        let tampered = text.replace("\n12345\n", "\n12346\n");
+        SignedNote::parse(&tampered)
+            .unwrap()
+            .verify_rfc6962(&pub_key)
+            .expect_err("");
+        // And signing with a different origin makes the key ID different.
+        let other_origin = SignedNote::from_sth("example.com/other", &sth, &pub_key).unwrap();
+        let mut mixed = parsed.clone();
+        mixed.signatures = other_origin.signatures;
+        mixed.verify_rfc6962(&pub_key).expect_err("");
+    }
+
+    #[test]
+    fn rfc6962_key_id_test() {
+        // Argon 2019, whose log ID is Y/Lbzeg7zCzPC3KEJ1drM6SNYXePvXWmOLHHaFRL2I0=
+        let spki = crate::utils::hex_to_u8(
+            "3059301306072a8648ce3d020106082a8648ce3d030107034200042373109be1f35ef6986b6995961078ce49dbb404fc712c5a92606825c04a1aa1b0612d1b8714a9baf00133591d0530e94215e755d72af8b4a2ba45c946918756",
+        );
+        let pub_key = PKey::public_key_from_der(&spki).unwrap();
+        assert_eq!(
+            BASE64_STANDARD.encode(sha256(&spki)),
+            "Y/Lbzeg7zCzPC3KEJ1drM6SNYXePvXWmOLHHaFRL2I0="
+        );
+        // SHA-256("ct.googleapis.com/logs/argon2019" || 0x0A || 0x05 || log ID)[:4]
+        assert_eq!(
+            rfc6962_key_id("ct.googleapis.com/logs/argon2019", &pub_key).unwrap(),
+            [0x8b, 0x35, 0x28, 0x0c]
+        );
+    }
+
+    fn cosign(
+        note: &mut SignedNote,
+        name: &str,
+        key: &PKey<openssl::pkey::Private>,
+        timestamp: u64,
+    ) {
+        let mut signer = Signer::new_without_digest(key).unwrap();
+        let mut signature = timestamp.to_be_bytes().to_vec();
+        signature.extend_from_slice(
+            &signer
+                .sign_oneshot_to_vec(&cosignature_message(&note.text, timestamp))
+                .unwrap(),
+        );
+        note.signatures.push(NoteSignature {
+            name: name.to_owned(),
+            key_id: key_id(name, SIG_TYPE_COSIGNATURE, &key.raw_public_key().unwrap()),
+            signature,
+        });
+    }
+
+    #[test]
+    fn witness_policy_test() {
+        let keys: Vec<_> = (0..3).map(|_| PKey::generate_ed25519().unwrap()).collect();
+        let names = ["w0.example", "w1.example", "w2.example"];
+        let witnesses: Vec<WitnessKey> = keys
+            .iter()
+            .zip(names.iter())
+            .map(|(k, n)| {
+                let mut vkey = vec![SIG_TYPE_COSIGNATURE];
+                vkey.extend_from_slice(&k.raw_public_key().unwrap());
+                let id = key_id(n, SIG_TYPE_COSIGNATURE, &vkey[1..]);
+                WitnessKey::from_vkey(&format!(
+                    "{}+{}+{}",
+                    n,
+                    crate::utils::u8_to_hex(&id),
+                    BASE64_STANDARD.encode(&vkey)
+                ))
+                .unwrap()
+            })
+            .collect();
+        let policy = WitnessPolicy::new(witnesses.clone(), 2).unwrap();
+        WitnessPolicy::new(witnesses.clone(), 4).expect_err("");
+
+        let mut note = SignedNote {
+            text: "example.com/log\n1\nAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA=\n".to_owned(),
+            signatures: Vec::new(),
+        };
+        cosign(&mut note, names[0], &keys[0], 1700000000);
+        assert!(matches!(policy.check(&note), Err(Error::NotWitnessed(_))));
+        cosign(&mut note, names[2], &keys[2], 1700000001);
+        let note = SignedNote::parse(&note.to_string()).unwrap();
+        assert_eq!(policy.check(&note).unwrap(), vec![names[0], names[2]]);
+        assert_eq!(witnesses[2].verify(&note).unwrap(), Some(1700000001));
+        assert_eq!(witnesses[1].verify(&note).unwrap(), None);
+
+        // A cosignature over a different checkpoint is rejected.
+        let mut forged = note.clone();
+        forged.text = forged.text.replace("\n1\n", "\n2\n");
+        witnesses[0].verify(&forged).expect_err("");
+        assert!(matches!(policy.check(&forged), Err(Error::NotWitnessed(_))));
+
+        // An invalid cosignature is skipped, and the others can still make the quorum.
+        let mut with_invalid = note.clone();
+        with_invalid.signatures.insert(
+            0,
+            NoteSignature {
+                name: names[1].to_owned(),
+                key_id: witnesses[1].key_id,
+                signature: forged.signatures[0].signature.clone(),
+            },
+        );
+        witnesses[1].verify(&with_invalid).expect_err("");
+        assert_eq!(
+            policy.check(&with_invalid).unwrap(),
+            vec![names[0], names[2]]
+        );
+        // But it does not count towards it.
+        with_invalid.signatures.pop();
+        assert!(matches!(
+            policy.check(&with_invalid),
+            Err(Error::NotWitnessed(_))
+        ));
+
+        WitnessKey::from_vkey("w0.example+00000000+AAAA").expect_err("");
+        WitnessKey::from_vkey("w0.example").expect_err("");
+    }
+
+    #[test]
+    fn checkpoint_parse_test() {
+        let text = "example.com/log\n0\nAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA=\nextra\n";
+        let c = Checkpoint::parse(text).unwrap();
+        assert_eq!(c.tree_size, 0);
+        assert_eq!(c.extensions, vec!["extra".to_owned()]);
+        assert_eq!(c.to_string(), text);
+        for bad in [
+            "example.com/log\n0\n",
+            "example.com/log\n01\nAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA=\n",
+            "example.com/log\n+1\nAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA=\n",
+            "example.com/log\n1\nAAAA\n",
+            "example.com/log\n1\nAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA=",
+        ] {
+            Checkpoint::parse(bad).expect_err(bad);
+        }
+        SignedNote::parse(text).expect_err("no signatures");
+        SignedNote::parse(&format!("{}\n\u{2014} k AAAAAA==\n", text)).unwrap();
+        SignedNote::parse(&format!("{}\n\u{2014} k AAAA\n", text)).expect_err("short sig");
+        SignedNote::parse(&format!("{}\n- k AAAAAAAA\n", text)).expect_err("bad line");
+    }
+}
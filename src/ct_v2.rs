@@ -0,0 +1,1020 @@
+//! Certificate Transparency version 2.0 ([RFC 9162](https://www.rfc-editor.org/rfc/rfc9162)).
+//!
+//! Everything a v2 log hands out (SCTs, tree heads, proofs) is a [`TransItem`]. Compared to
+//! RFC 6962:
+//!
+//! * Logs are identified by an OID ([`LogId`]) instead of the hash of their key.
+//! * Signatures are plain signatures rather than `DigitallySigned` structs, and the algorithm is a
+//!   parameter of the log ([`SignatureScheme`]), which may be Ed25519.
+//! * The hash algorithm is also a parameter of the log ([`HashAlgorithm`]), so node hashes are of
+//!   variable length.
+//! * SCTs sign the TBSCertificate for both certificates and precertificates.
+//!
+//! The rest of this crate only deals with v1, so v2 has its own, self-contained verification
+//! functions here, starting from [`LogV2`].
+
+use std::convert::TryInto;
+use std::fmt;
+
+use openssl::hash::MessageDigest;
+use openssl::pkey::PKey;
+
+use crate::Error;
+
+/// Hash algorithm used by a log for its Merkle tree.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HashAlgorithm {
+    Sha256,
+}
+
+impl HashAlgorithm {
+    /// The value of this algorithm in the IANA "Hash Algorithms" registry of RFC 9162.
+    pub fn id(self) -> u8 {
+        match self {
+            HashAlgorithm::Sha256 => 0x00,
+        }
+    }
+
+    pub fn from_id(id: u8) -> Option<Self> {
+        match id {
+            0x00 => Some(HashAlgorithm::Sha256),
+            _ => None,
+        }
+    }
+
+    pub fn output_len(self) -> usize {
+        match self {
+            HashAlgorithm::Sha256 => 32,
+        }
+    }
+
+    pub fn hash(self, data: &[u8]) -> Vec<u8> {
+        match self {
+            HashAlgorithm::Sha256 => crate::utils::sha256(data).to_vec(),
+        }
+    }
+
+    /// `HASH(0x00 || leaf)`
+    pub fn leaf_hash(self, leaf: &[u8]) -> Vec<u8> {
+        let mut data = Vec::with_capacity(1 + leaf.len());
+        data.push(0u8);
+        data.extend_from_slice(leaf);
+        self.hash(&data)
+    }
+
+    /// `HASH(0x01 || left || right)`
+    pub fn node_hash(self, left: &[u8], right: &[u8]) -> Vec<u8> {
+        let mut data = Vec::with_capacity(1 + left.len() + right.len());
+        data.push(1u8);
+        data.extend_from_slice(left);
+        data.extend_from_slice(right);
+        self.hash(&data)
+    }
+}
+
+/// Signature algorithm used by a log.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SignatureScheme {
+    EcdsaSecp256r1Sha256,
+    Ed25519,
+}
+
+impl SignatureScheme {
+    /// The value of this scheme in the TLS SignatureScheme registry.
+    pub fn id(self) -> u16 {
+        match self {
+            SignatureScheme::EcdsaSecp256r1Sha256 => 0x0403,
+            SignatureScheme::Ed25519 => 0x0807,
+        }
+    }
+
+    pub fn from_id(id: u16) -> Option<Self> {
+        match id {
+            0x0403 => Some(SignatureScheme::EcdsaSecp256r1Sha256),
+            0x0807 => Some(SignatureScheme::Ed25519),
+            _ => None,
+        }
+    }
+}
+
+/// A log ID, which is an OID. Holds the contents octets of its DER encoding (without the tag and
+/// length).
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct LogId(pub Vec<u8>);
+
+impl LogId {
+    /// Parse a dotted OID, like `1.3.101.8373.2.1`.
+    pub fn from_oid(oid: &str) -> Result<Self, Error> {
+        let err = || Error::InvalidArgument(format!("Invalid OID {}", oid));
+        let arcs = oid
+            .split('.')
+            .map(|a| a.parse::<u64>())
+            .collect::<Result<Vec<u64>, _>>()
+            .map_err(|_| err())?;
+        if arcs.len() < 2 || arcs[0] > 2 || (arcs[0] < 2 && arcs[1] >= 40) {
+            return Err(err());
+        }
+        let first = arcs[0]
+            .checked_mul(40)
+            .and_then(|a| a.checked_add(arcs[1]))
+            .ok_or_else(err)?;
+        let mut der = Vec::new();
+        for arc in std::iter::once(first).chain(arcs[2..].iter().copied()) {
+            let mut groups = vec![(arc & 0x7f) as u8];
+            let mut rest = arc >> 7;
+            while rest > 0 {
+                groups.push((rest & 0x7f) as u8 | 0x80);
+                rest >>= 7;
+            }
+            der.extend(groups.into_iter().rev());
+        }
+        let id = LogId(der);
+        id.check()?;
+        Ok(id)
+    }
+
+    fn check(&self) -> Result<(), Error> {
+        // LogID is opaque<2..127>, and must be a valid OID encoding.
+        if self.0.len() < 2 || self.0.len() > 127 || self.0.last().unwrap() & 0x80 != 0 {
+            return Err(Error::MalformedResponseBody("Invalid log ID.".to_owned()));
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for LogId {
+    /// Format as a dotted OID.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut arcs = Vec::new();
+        let mut current = 0u64;
+        for b in self.0.iter() {
+            current = (current << 7) | (b & 0x7f) as u64;
+            if b & 0x80 == 0 {
+                arcs.push(current);
+                current = 0;
+            }
+        }
+        if let Some(&first) = arcs.first() {
+            let (a, b) = if first < 80 {
+                (first / 40, first % 40)
+            } else {
+                (2, first - 80)
+            };
+            write!(f, "{}.{}", a, b)?;
+        }
+        for arc in arcs.iter().skip(1) {
+            write!(f, ".{}", arc)?;
+        }
+        Ok(())
+    }
+}
+
+/// A SCT or STH extension.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Extension {
+    pub extension_type: u16,
+    pub data: Vec<u8>,
+}
+
+/// The data of a `x509_entry_v2` or `precert_entry_v2`, which is what a SCT signs and what is
+/// hashed into the tree.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TimestampedCertificateEntryDataV2 {
+    pub timestamp: u64,
+    pub issuer_key_hash: Vec<u8>,
+    /// DER TBSCertificate.
+    pub tbs_certificate: Vec<u8>,
+    pub sct_extensions: Vec<Extension>,
+}
+
+/// The data of a `x509_sct_v2` or `precert_sct_v2`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SignedCertificateTimestampDataV2 {
+    pub log_id: LogId,
+    pub timestamp: u64,
+    pub sct_extensions: Vec<Extension>,
+    pub signature: Vec<u8>,
+}
+
+/// A tree head, which is what a [`SignedTreeHeadDataV2`] signs.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TreeHeadDataV2 {
+    pub timestamp: u64,
+    pub tree_size: u64,
+    pub root_hash: Vec<u8>,
+    pub sth_extensions: Vec<Extension>,
+}
+
+/// The data of a `signed_tree_head_v2`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SignedTreeHeadDataV2 {
+    pub log_id: LogId,
+    pub tree_head: TreeHeadDataV2,
+    pub signature: Vec<u8>,
+}
+
+/// The data of a `consistency_proof_v2`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConsistencyProofDataV2 {
+    pub log_id: LogId,
+    pub tree_size_1: u64,
+    pub tree_size_2: u64,
+    pub consistency_path: Vec<Vec<u8>>,
+}
+
+/// The data of an `inclusion_proof_v2`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InclusionProofDataV2 {
+    pub log_id: LogId,
+    pub tree_size: u64,
+    pub leaf_index: u64,
+    pub inclusion_path: Vec<Vec<u8>>,
+}
+
+/// A `TransItem`, the container for all v2 structures.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TransItem {
+    X509EntryV2(TimestampedCertificateEntryDataV2),
+    PrecertEntryV2(TimestampedCertificateEntryDataV2),
+    X509SctV2(SignedCertificateTimestampDataV2),
+    PrecertSctV2(SignedCertificateTimestampDataV2),
+    SignedTreeHeadV2(SignedTreeHeadDataV2),
+    ConsistencyProofV2(ConsistencyProofDataV2),
+    InclusionProofV2(InclusionProofDataV2),
+}
+
+fn err_invalid(desc: &str) -> Error {
+    Error::MalformedResponseBody(format!("Invalid TransItem: {}", desc))
+}
+
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], Error> {
+        if self.0.len() < len {
+            return Err(err_invalid("too short"));
+        }
+        let (taken, rest) = self.0.split_at(len);
+        self.0 = rest;
+        Ok(taken)
+    }
+
+    fn u16(&mut self) -> Result<u16, Error> {
+        Ok(u16::from_be_bytes(self.take(2)?.try_into().unwrap()))
+    }
+
+    fn u64(&mut self) -> Result<u64, Error> {
+        Ok(u64::from_be_bytes(self.take(8)?.try_into().unwrap()))
+    }
+
+    /// Read a vector with a length prefix of `len_bytes` bytes.
+    fn vec(&mut self, len_bytes: usize) -> Result<&'a [u8], Error> {
+        let mut len = 0usize;
+        for b in self.take(len_bytes)? {
+            len = (len << 8) | *b as usize;
+        }
+        self.take(len)
+    }
+
+    fn log_id(&mut self) -> Result<LogId, Error> {
+        let id = LogId(self.vec(1)?.to_vec());
+        id.check()?;
+        Ok(id)
+    }
+
+    fn extensions(&mut self) -> Result<Vec<Extension>, Error> {
+        let mut r = Reader(self.vec(2)?);
+        let mut exts = Vec::new();
+        while !r.0.is_empty() {
+            exts.push(Extension {
+                extension_type: r.u16()?,
+                data: r.vec(2)?.to_vec(),
+            });
+        }
+        Ok(exts)
+    }
+
+    fn node_hash(&mut self) -> Result<Vec<u8>, Error> {
+        let hash = self.vec(1)?;
+        if hash.len() < 32 {
+            return Err(err_invalid("node hash too short"));
+        }
+        Ok(hash.to_vec())
+    }
+
+    fn node_hashes(&mut self) -> Result<Vec<Vec<u8>>, Error> {
+        let mut r = Reader(self.vec(2)?);
+        let mut hashes = Vec::new();
+        while !r.0.is_empty() {
+            hashes.push(r.node_hash()?);
+        }
+        Ok(hashes)
+    }
+}
+
+fn push_vec(v: &mut Vec<u8>, len_bytes: usize, data: &[u8]) {
+    assert!(data.len() < 1 << (8 * len_bytes));
+    v.extend_from_slice(&(data.len() as u64).to_be_bytes()[8 - len_bytes..]);
+    v.extend_from_slice(data);
+}
+
+fn push_extensions(v: &mut Vec<u8>, exts: &[Extension]) {
+    let mut data = Vec::new();
+    for ext in exts.iter() {
+        data.extend_from_slice(&ext.extension_type.to_be_bytes());
+        push_vec(&mut data, 2, &ext.data);
+    }
+    push_vec(v, 2, &data);
+}
+
+fn push_node_hashes(v: &mut Vec<u8>, hashes: &[Vec<u8>]) {
+    let mut data = Vec::new();
+    for h in hashes.iter() {
+        push_vec(&mut data, 1, h);
+    }
+    push_vec(v, 2, &data);
+}
+
+impl TimestampedCertificateEntryDataV2 {
+    fn parse(r: &mut Reader) -> Result<Self, Error> {
+        let timestamp = r.u64()?;
+        let issuer_key_hash = r.vec(1)?;
+        if issuer_key_hash.len() < 32 {
+            return Err(err_invalid("issuer_key_hash too short"));
+        }
+        Ok(TimestampedCertificateEntryDataV2 {
+            timestamp,
+            issuer_key_hash: issuer_key_hash.to_vec(),
+            tbs_certificate: r.vec(3)?.to_vec(),
+            sct_extensions: r.extensions()?,
+        })
+    }
+
+    fn write(&self, v: &mut Vec<u8>) {
+        v.extend_from_slice(&self.timestamp.to_be_bytes());
+        push_vec(v, 1, &self.issuer_key_hash);
+        push_vec(v, 3, &self.tbs_certificate);
+        push_extensions(v, &self.sct_extensions);
+    }
+}
+
+impl SignedCertificateTimestampDataV2 {
+    fn parse(r: &mut Reader) -> Result<Self, Error> {
+        Ok(SignedCertificateTimestampDataV2 {
+            log_id: r.log_id()?,
+            timestamp: r.u64()?,
+            sct_extensions: r.extensions()?,
+            signature: r.vec(2)?.to_vec(),
+        })
+    }
+
+    fn write(&self, v: &mut Vec<u8>) {
+        push_vec(v, 1, &self.log_id.0);
+        v.extend_from_slice(&self.timestamp.to_be_bytes());
+        push_extensions(v, &self.sct_extensions);
+        push_vec(v, 2, &self.signature);
+    }
+}
+
+impl TreeHeadDataV2 {
+    fn parse(r: &mut Reader) -> Result<Self, Error> {
+        Ok(TreeHeadDataV2 {
+            timestamp: r.u64()?,
+            tree_size: r.u64()?,
+            root_hash: r.node_hash()?,
+            sth_extensions: r.extensions()?,
+        })
+    }
+
+    /// The serialized tree head, which is what the log signs.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut v = Vec::new();
+        v.extend_from_slice(&self.timestamp.to_be_bytes());
+        v.extend_from_slice(&self.tree_size.to_be_bytes());
+        push_vec(&mut v, 1, &self.root_hash);
+        push_extensions(&mut v, &self.sth_extensions);
+        v
+    }
+}
+
+impl TransItem {
+    /// The `VersionedTransType` of this item, as defined in RFC 9162 section 4.5.
+    pub fn versioned_type(&self) -> u16 {
+        match self {
+            TransItem::X509EntryV2(_) => 0x0101,
+            TransItem::PrecertEntryV2(_) => 0x0102,
+            TransItem::X509SctV2(_) => 0x0103,
+            TransItem::PrecertSctV2(_) => 0x0104,
+            TransItem::SignedTreeHeadV2(_) => 0x0105,
+            TransItem::ConsistencyProofV2(_) => 0x0106,
+            TransItem::InclusionProofV2(_) => 0x0107,
+        }
+    }
+
+    /// Parse a TransItem. All of `bytes` must be consumed.
+    pub fn parse(bytes: &[u8]) -> Result<Self, Error> {
+        let mut r = Reader(bytes);
+        let item = match r.u16()? {
+            0x0101 => TransItem::X509EntryV2(TimestampedCertificateEntryDataV2::parse(&mut r)?),
+            0x0102 => TransItem::PrecertEntryV2(TimestampedCertificateEntryDataV2::parse(&mut r)?),
+            0x0103 => TransItem::X509SctV2(SignedCertificateTimestampDataV2::parse(&mut r)?),
+            0x0104 => TransItem::PrecertSctV2(SignedCertificateTimestampDataV2::parse(&mut r)?),
+            0x0105 => TransItem::SignedTreeHeadV2(SignedTreeHeadDataV2 {
+                log_id: r.log_id()?,
+                tree_head: TreeHeadDataV2::parse(&mut r)?,
+                signature: r.vec(2)?.to_vec(),
+            }),
+            0x0106 => TransItem::ConsistencyProofV2(ConsistencyProofDataV2 {
+                log_id: r.log_id()?,
+                tree_size_1: r.u64()?,
+                tree_size_2: r.u64()?,
+                consistency_path: r.node_hashes()?,
+            }),
+            0x0107 => TransItem::InclusionProofV2(InclusionProofDataV2 {
+                log_id: r.log_id()?,
+                tree_size: r.u64()?,
+                leaf_index: r.u64()?,
+                inclusion_path: r.node_hashes()?,
+            }),
+            t => return Err(err_invalid(&format!("unknown type {}", t))),
+        };
+        if !r.0.is_empty() {
+            return Err(err_invalid("trailing data"));
+        }
+        Ok(item)
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut v = Vec::new();
+        v.extend_from_slice(&self.versioned_type().to_be_bytes());
+        match self {
+            TransItem::X509EntryV2(e) | TransItem::PrecertEntryV2(e) => e.write(&mut v),
+            TransItem::X509SctV2(s) | TransItem::PrecertSctV2(s) => s.write(&mut v),
+            TransItem::SignedTreeHeadV2(sth) => {
+                push_vec(&mut v, 1, &sth.log_id.0);
+                v.extend_from_slice(&sth.tree_head.to_bytes());
+                push_vec(&mut v, 2, &sth.signature);
+            }
+            TransItem::ConsistencyProofV2(p) => {
+                push_vec(&mut v, 1, &p.log_id.0);
+                v.extend_from_slice(&p.tree_size_1.to_be_bytes());
+                v.extend_from_slice(&p.tree_size_2.to_be_bytes());
+                push_node_hashes(&mut v, &p.consistency_path);
+            }
+            TransItem::InclusionProofV2(p) => {
+                push_vec(&mut v, 1, &p.log_id.0);
+                v.extend_from_slice(&p.tree_size.to_be_bytes());
+                v.extend_from_slice(&p.leaf_index.to_be_bytes());
+                push_node_hashes(&mut v, &p.inclusion_path);
+            }
+        }
+        v
+    }
+}
+
+/// Compute the root hash from an inclusion proof, following section 2.1.3.2 of RFC 9162.
+pub fn hash_inclusion_path(
+    hash_algorithm: HashAlgorithm,
+    tree_size: u64,
+    leaf_index: u64,
+    leaf_hash: &[u8],
+    inclusion_path: &[Vec<u8>],
+) -> Result<Vec<u8>, String> {
+    if leaf_index >= tree_size {
+        return Err(format!(
+            "leaf_index {} is not in a tree of size {}",
+            leaf_index, tree_size
+        ));
+    }
+    let mut f_n = leaf_index;
+    let mut s_n = tree_size - 1;
+    let mut r = leaf_hash.to_vec();
+    for p in inclusion_path.iter() {
+        if s_n == 0 {
+            return Err("Inclusion path too long.".to_owned());
+        }
+        if f_n & 1 == 1 || f_n == s_n {
+            r = hash_algorithm.node_hash(p, &r);
+            if f_n & 1 == 0 {
+                while f_n & 1 == 0 && f_n != 0 {
+                    f_n >>= 1;
+                    s_n >>= 1;
+                }
+            }
+        } else {
+            r = hash_algorithm.node_hash(&r, p);
+        }
+        f_n >>= 1;
+        s_n >>= 1;
+    }
+    if s_n != 0 {
+        return Err("Inclusion path too short.".to_owned());
+    }
+    Ok(r)
+}
+
+/// Verify a consistency proof, following section 2.1.4.2 of RFC 9162.
+pub fn verify_consistency_path(
+    hash_algorithm: HashAlgorithm,
+    first: u64,
+    second: u64,
+    first_hash: &[u8],
+    second_hash: &[u8],
+    consistency_path: &[Vec<u8>],
+) -> Result<(), String> {
+    if first > second {
+        return Err(format!("Tree size {} is larger than {}.", first, second));
+    }
+    if first == second {
+        if !consistency_path.is_empty() || first_hash != second_hash {
+            return Err("Trees of the same size are not the same.".to_owned());
+        }
+        return Ok(());
+    }
+    if first == 0 {
+        // An empty tree is a subtree of every tree. No need to prove.
+        return if consistency_path.is_empty() {
+            Ok(())
+        } else {
+            Err("Expected an empty proof from an empty tree.".to_owned())
+        };
+    }
+    if consistency_path.is_empty() {
+        return Err("Empty consistency path.".to_owned());
+    }
+    let mut path = Vec::with_capacity(consistency_path.len() + 1);
+    if first.is_power_of_two() {
+        path.push(first_hash);
+    }
+    path.extend(consistency_path.iter().map(|h| &h[..]));
+    let mut f_n = first - 1;
+    let mut s_n = second - 1;
+    while f_n & 1 == 1 {
+        f_n >>= 1;
+        s_n >>= 1;
+    }
+    let mut f_r = path[0].to_vec();
+    let mut s_r = path[0].to_vec();
+    for c in path[1..].iter() {
+        if s_n == 0 {
+            return Err("Consistency path too long.".to_owned());
+        }
+        if f_n & 1 == 1 || f_n == s_n {
+            f_r = hash_algorithm.node_hash(c, &f_r);
+            s_r = hash_algorithm.node_hash(c, &s_r);
+            if f_n & 1 == 0 {
+                while f_n & 1 == 0 && f_n != 0 {
+                    f_n >>= 1;
+                    s_n >>= 1;
+                }
+            }
+        } else {
+            s_r = hash_algorithm.node_hash(&s_r, c);
+        }
+        f_n >>= 1;
+        s_n >>= 1;
+    }
+    if f_r != first_hash {
+        return Err("Consistency path does not yield the first tree hash.".to_owned());
+    }
+    if s_r != second_hash {
+        return Err("Consistency path does not yield the second tree hash.".to_owned());
+    }
+    if s_n != 0 {
+        return Err("Consistency path too short.".to_owned());
+    }
+    Ok(())
+}
+
+/// The parameters of a v2 log needed to verify what it hands out.
+#[derive(Debug, Clone)]
+pub struct LogV2 {
+    pub log_id: LogId,
+    pub pub_key: PKey<openssl::pkey::Public>,
+    pub hash_algorithm: HashAlgorithm,
+    pub signature_scheme: SignatureScheme,
+}
+
+impl LogV2 {
+    /// `pub_key` is in DER (SubjectPublicKeyInfo) format, and must match `signature_scheme`.
+    pub fn new(
+        log_id: LogId,
+        pub_key: &[u8],
+        hash_algorithm: HashAlgorithm,
+        signature_scheme: SignatureScheme,
+    ) -> Result<Self, Error> {
+        let pub_key = PKey::public_key_from_der(pub_key)
+            .map_err(|e| Error::InvalidArgument(format!("Error parsing public key: {}", &e)))?;
+        let expected_id = match signature_scheme {
+            SignatureScheme::EcdsaSecp256r1Sha256 => openssl::pkey::Id::EC,
+            SignatureScheme::Ed25519 => openssl::pkey::Id::ED25519,
+        };
+        if pub_key.id() != expected_id {
+            return Err(Error::InvalidArgument(format!(
+                "Public key does not match {:?}",
+                signature_scheme
+            )));
+        }
+        Ok(LogV2 {
+            log_id,
+            pub_key,
+            hash_algorithm,
+            signature_scheme,
+        })
+    }
+
+    fn verify_signature(&self, signature: &[u8], data: &[u8]) -> Result<(), Error> {
+        let mut verifier = match self.signature_scheme {
+            SignatureScheme::EcdsaSecp256r1Sha256 => {
+                openssl::sign::Verifier::new(MessageDigest::sha256(), &self.pub_key)
+            }
+            SignatureScheme::Ed25519 => openssl::sign::Verifier::new_without_digest(&self.pub_key),
+        }
+        .map_err(|e| Error::Unknown(format!("EVP_DigestVerifyInit: {}", &e)))?;
+        if !verifier.verify_oneshot(signature, data).unwrap_or(false) {
+            return Err(Error::InvalidSignature(format!(
+                "Invalid {:?} signature from log {}",
+                self.signature_scheme, &self.log_id
+            )));
+        }
+        Ok(())
+    }
+
+    fn check_log_id(&self, log_id: &LogId) -> Result<(), Error> {
+        if log_id != &self.log_id {
+            return Err(Error::InvalidArgument(format!(
+                "Item is from log {}, not {}.",
+                log_id, &self.log_id
+            )));
+        }
+        Ok(())
+    }
+
+    /// Verify the signature on a `signed_tree_head_v2`, and return the tree head.
+    pub fn verify_sth<'a>(&self, item: &'a TransItem) -> Result<&'a TreeHeadDataV2, Error> {
+        let sth = match item {
+            TransItem::SignedTreeHeadV2(sth) => sth,
+            _ => return Err(Error::InvalidArgument("Not a tree head.".to_owned())),
+        };
+        self.check_log_id(&sth.log_id)?;
+        if sth.tree_head.root_hash.len() != self.hash_algorithm.output_len() {
+            return Err(Error::MalformedResponseBody(
+                "Root hash has the wrong length.".to_owned(),
+            ));
+        }
+        self.verify_signature(&sth.signature, &sth.tree_head.to_bytes())?;
+        Ok(&sth.tree_head)
+    }
+
+    /// Verify that `sct` (a `x509_sct_v2` or `precert_sct_v2`) is a signature on a certificate.
+    ///
+    /// The entry is built from `issuer_key_hash` and `tbs_certificate`, with the timestamp and
+    /// extensions taken from the SCT. It is returned so that it can be hashed with
+    /// [`Self::leaf_hash`].
+    pub fn verify_sct(
+        &self,
+        sct: &TransItem,
+        issuer_key_hash: &[u8],
+        tbs_certificate: &[u8],
+    ) -> Result<TransItem, Error> {
+        let (data, precert) = match sct {
+            TransItem::X509SctV2(d) => (d, false),
+            TransItem::PrecertSctV2(d) => (d, true),
+            _ => return Err(Error::InvalidArgument("Not a SCT.".to_owned())),
+        };
+        self.check_log_id(&data.log_id)?;
+        let entry = TimestampedCertificateEntryDataV2 {
+            timestamp: data.timestamp,
+            issuer_key_hash: issuer_key_hash.to_vec(),
+            tbs_certificate: tbs_certificate.to_vec(),
+            sct_extensions: data.sct_extensions.clone(),
+        };
+        let entry = if precert {
+            TransItem::PrecertEntryV2(entry)
+        } else {
+            TransItem::X509EntryV2(entry)
+        };
+        self.verify_signature(&data.signature, &entry.to_bytes())
+            .map_err(|e| match e {
+                Error::InvalidSignature(desc) => Error::BadSct(desc),
+                other => other,
+            })?;
+        Ok(entry)
+    }
+
+    /// The leaf hash of an entry (a `x509_entry_v2` or `precert_entry_v2`) in this log's tree.
+    pub fn leaf_hash(&self, entry: &TransItem) -> Vec<u8> {
+        self.hash_algorithm.leaf_hash(&entry.to_bytes())
+    }
+
+    /// Verify an `inclusion_proof_v2` of the leaf with `leaf_hash` against a verified tree head.
+    pub fn verify_inclusion_proof(
+        &self,
+        proof: &TransItem,
+        leaf_hash: &[u8],
+        tree_head: &TreeHeadDataV2,
+    ) -> Result<u64, Error> {
+        let proof = match proof {
+            TransItem::InclusionProofV2(p) => p,
+            _ => return Err(Error::InvalidArgument("Not an inclusion proof.".to_owned())),
+        };
+        self.check_log_id(&proof.log_id)?;
+        let err = |desc: String| Error::InvalidInclusionProof {
+            tree_size: proof.tree_size,
+            leaf_index: proof.leaf_index,
+            desc,
+        };
+        if proof.tree_size != tree_head.tree_size {
+            return Err(err(format!(
+                "Proof is for a tree of size {}, not {}.",
+                proof.tree_size, tree_head.tree_size
+            )));
+        }
+        let root = hash_inclusion_path(
+            self.hash_algorithm,
+            proof.tree_size,
+            proof.leaf_index,
+            leaf_hash,
+            &proof.inclusion_path,
+        )
+        .map_err(err)?;
+        if root != tree_head.root_hash {
+            return Err(err(
+                "Inclusion path does not yield the tree hash.".to_owned()
+            ));
+        }
+        Ok(proof.leaf_index)
+    }
+
+    /// Verify a `consistency_proof_v2` between two verified tree heads.
+    pub fn verify_consistency_proof(
+        &self,
+        proof: &TransItem,
+        first: &TreeHeadDataV2,
+        second: &TreeHeadDataV2,
+    ) -> Result<(), Error> {
+        let proof = match proof {
+            TransItem::ConsistencyProofV2(p) => p,
+            _ => {
+                return Err(Error::InvalidArgument(
+                    "Not a consistency proof.".to_owned(),
+                ));
+            }
+        };
+        self.check_log_id(&proof.log_id)?;
+        let err = |desc: String| Error::InvalidConsistencyProof {
+            prev_size: first.tree_size,
+            new_size: second.tree_size,
+            desc,
+        };
+        if proof.tree_size_1 != first.tree_size || proof.tree_size_2 != second.tree_size {
+            return Err(err(format!(
+                "Proof is from {} to {}.",
+                proof.tree_size_1, proof.tree_size_2
+            )));
+        }
+        verify_consistency_path(
+            self.hash_algorithm,
+            first.tree_size,
+            second.tree_size,
+            &first.root_hash,
+            &second.root_hash,
+            &proof.consistency_path,
+        )
+        .map_err(err)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::internal::{consistency_proof_parts, inclusion_proof_parts};
+    use crate::utils::largest_power_of_2_smaller_than;
+    use openssl::ec::{EcGroup, EcKey};
+    use openssl::nid::Nid;
+    use openssl::sign::Signer;
+
+    fn mth(leafs: &[Vec<u8>]) -> Vec<u8> {
+        if leafs.len() == 1 {
+            return leafs[0].clone();
+        }
+        let k = largest_power_of_2_smaller_than(leafs.len() as u64) as usize;
+        HashAlgorithm::Sha256.node_hash(&mth(&leafs[..k]), &mth(&leafs[k..]))
+    }
+
+    #[test]
+    fn log_id_test() {
+        let id = LogId::from_oid("1.3.101.8373.2.1").unwrap();
+        assert_eq!(id.0, vec![0x2b, 0x65, 0xc1, 0x35, 0x02, 0x01]);
+        assert_eq!(id.to_string(), "1.3.101.8373.2.1");
+        assert_eq!(LogId::from_oid("2.999.1").unwrap().to_string(), "2.999.1");
+        LogId::from_oid("1.40").expect_err("");
+        LogId::from_oid("1").expect_err("");
+        LogId::from_oid("1.a").expect_err("");
+    }
+
+    #[test]
+    fn proofs_test() {
+        let h = HashAlgorithm::Sha256;
+        let leafs: Vec<Vec<u8>> = (0..20u64).map(|i| h.leaf_hash(&i.to_be_bytes())).collect();
+        for tree_size in 1..=leafs.len() as u64 {
+            let root = mth(&leafs[..tree_size as usize]);
+            for leaf_index in 0..tree_size {
+                let path: Vec<Vec<u8>> = inclusion_proof_parts(tree_size, leaf_index)
+                    .into_iter()
+                    .map(|r| mth(&leafs[r.start as usize..r.end as usize]))
+                    .collect();
+                let got = hash_inclusion_path(
+                    h,
+                    tree_size,
+                    leaf_index,
+                    &leafs[leaf_index as usize],
+                    &path,
+                )
+                .unwrap();
+                assert_eq!(got, root);
+                if !path.is_empty() {
+                    hash_inclusion_path(
+                        h,
+                        tree_size,
+                        leaf_index,
+                        &leafs[leaf_index as usize],
+                        &path[1..],
+                    )
+                    .expect_err("");
+                }
+            }
+            for first in 1..tree_size {
+                let mut parts = consistency_proof_parts(first, tree_size);
+                if first.is_power_of_two() {
+                    parts.remove(0);
+                }
+                let path: Vec<Vec<u8>> = parts
+                    .into_iter()
+                    .map(|(a, b)| mth(&leafs[a as usize..b as usize]))
+                    .collect();
+                let first_root = mth(&leafs[..first as usize]);
+                verify_consistency_path(h, first, tree_size, &first_root, &root, &path).unwrap();
+                verify_consistency_path(h, first, tree_size, &root, &root, &path).expect_err("");
+            }
+        }
+    }
+
+    fn sign(key: &PKey<openssl::pkey::Private>, scheme: SignatureScheme, data: &[u8]) -> Vec<u8> {
+        let mut signer = match scheme {
+            SignatureScheme::EcdsaSecp256r1Sha256 => Signer::new(MessageDigest::sha256(), key),
+            SignatureScheme::Ed25519 => Signer::new_without_digest(key),
+        }
+        .unwrap();
+        signer.sign_oneshot_to_vec(data).unwrap()
+    }
+
+    #[test]
+    fn sth_and_sct_test() {
+        let ec_key = PKey::from_ec_key(
+            EcKey::generate(&EcGroup::from_curve_name(Nid::X9_62_PRIME256V1).unwrap()).unwrap(),
+        )
+        .unwrap();
+        let ed_key = PKey::generate_ed25519().unwrap();
+        for (key, scheme) in [
+            (ec_key, SignatureScheme::EcdsaSecp256r1Sha256),
+            (ed_key, SignatureScheme::Ed25519),
+        ] {
+            let log_id = LogId::from_oid("1.3.101.8373.2.1").unwrap();
+            let log = LogV2::new(
+                log_id.clone(),
+                &key.public_key_to_der().unwrap(),
+                HashAlgorithm::Sha256,
+                scheme,
+            )
+            .unwrap();
+
+            let tree_head = TreeHeadDataV2 {
+                timestamp: 1700000000000,
+                tree_size: 1,
+                root_hash: vec![0u8; 32],
+                sth_extensions: vec![],
+            };
+            let sth = TransItem::SignedTreeHeadV2(SignedTreeHeadDataV2 {
+                log_id: log_id.clone(),
+                signature: sign(&key, scheme, &tree_head.to_bytes()),
+                tree_head: tree_head.clone(),
+            });
+            let sth = TransItem::parse(&sth.to_bytes()).unwrap();
+            assert_eq!(log.verify_sth(&sth).unwrap(), &tree_head);
+            let mut bad = sth.clone();
+            if let TransItem::SignedTreeHeadV2(s) = &mut bad {
+                s.tree_head.tree_size = 2;
+            }
+            log.verify_sth(&bad).expect_err("");
+
+            let entry = TransItem::PrecertEntryV2(TimestampedCertificateEntryDataV2 {
+                timestamp: 1700000000000,
+                issuer_key_hash: vec![1u8; 32],
+                tbs_certificate: vec![0x30, 0x00],
+                sct_extensions: vec![Extension {
+                    extension_type: 0,
+                    data: vec![0, 0, 0, 0, 1],
+                }],
+            });
+            let sct = TransItem::PrecertSctV2(SignedCertificateTimestampDataV2 {
+                log_id: log_id.clone(),
+                timestamp: 1700000000000,
+                sct_extensions: vec![Extension {
+                    extension_type: 0,
+                    data: vec![0, 0, 0, 0, 1],
+                }],
+                signature: sign(&key, scheme, &entry.to_bytes()),
+            });
+            let sct = TransItem::parse(&sct.to_bytes()).unwrap();
+            assert_eq!(
+                log.verify_sct(&sct, &[1u8; 32], &[0x30, 0x00]).unwrap(),
+                entry
+            );
+            log.verify_sct(&sct, &[2u8; 32], &[0x30, 0x00])
+                .expect_err("");
+            assert_eq!(TransItem::parse(&entry.to_bytes()).unwrap(), entry);
+
+            let leaf_hash = log.leaf_hash(&entry);
+            let proof = TransItem::InclusionProofV2(InclusionProofDataV2 {
+                log_id: log_id.clone(),
+                tree_size: 1,
+                leaf_index: 0,
+                inclusion_path: vec![],
+            });
+            let proof = TransItem::parse(&proof.to_bytes()).unwrap();
+            let tree_head = TreeHeadDataV2 {
+                root_hash: leaf_hash.clone(),
+                ..tree_head
+            };
+            assert_eq!(
+                log.verify_inclusion_proof(&proof, &leaf_hash, &tree_head)
+                    .unwrap(),
+                0
+            );
+
+            let tree_head_2 = TreeHeadDataV2 {
+                tree_size: 2,
+                root_hash: HashAlgorithm::Sha256.node_hash(&leaf_hash, &leaf_hash),
+                ..tree_head.clone()
+            };
+            let proof = TransItem::ConsistencyProofV2(ConsistencyProofDataV2 {
+                log_id: log_id.clone(),
+                tree_size_1: 1,
+                tree_size_2: 2,
+                consistency_path: vec![leaf_hash.clone()],
+            });
+            let proof = TransItem::parse(&proof.to_bytes()).unwrap();
+            log.verify_consistency_proof(&proof, &tree_head, &tree_head_2)
+                .unwrap();
+            log.verify_consistency_proof(&proof, &tree_head_2, &tree_head)
+                .expect_err("");
+        }
+        TransItem::parse(&[1, 8]).expect_err("");
+        TransItem::parse(&[1, 6, 0]).expect_err("");
+    }
+
+    #[test]
+    fn wire_format_test() {
+        let log_id = LogId::from_oid("1.3.101.8373.2.1").unwrap();
+        // inclusion_proof_v2(0x0107), log_id, tree_size 2, leaf_index 1, one 32-byte hash
+        let mut bytes = vec![0x01, 0x07, 0x06, 0x2b, 0x65, 0xc1, 0x35, 0x02, 0x01];
+        bytes.extend_from_slice(&2u64.to_be_bytes());
+        bytes.extend_from_slice(&1u64.to_be_bytes());
+        bytes.extend_from_slice(&[0x00, 0x21, 0x20]);
+        bytes.extend_from_slice(&[0xaa; 32]);
+        let proof = TransItem::InclusionProofV2(InclusionProofDataV2 {
+            log_id: log_id.clone(),
+            tree_size: 2,
+            leaf_index: 1,
+            inclusion_path: vec![vec![0xaa; 32]],
+        });
+        assert_eq!(TransItem::parse(&bytes).unwrap(), proof);
+        assert_eq!(proof.to_bytes(), bytes);
+
+        // signed_tree_head_v2(0x0105), log_id, timestamp, tree_size 2, root hash, no extensions,
+        // 2-byte signature
+        let mut bytes = vec![0x01, 0x05, 0x06, 0x2b, 0x65, 0xc1, 0x35, 0x02, 0x01];
+        bytes.extend_from_slice(&1700000000000u64.to_be_bytes());
+        bytes.extend_from_slice(&2u64.to_be_bytes());
+        bytes.push(0x20);
+        bytes.extend_from_slice(&[0xbb; 32]);
+        bytes.extend_from_slice(&[0x00, 0x00, 0x00, 0x02, 0xab, 0xcd]);
+        let sth = TransItem::SignedTreeHeadV2(SignedTreeHeadDataV2 {
+            log_id,
+            tree_head: TreeHeadDataV2 {
+                timestamp: 1700000000000,
+                tree_size: 2,
+                root_hash: vec![0xbb; 32],
+                sth_extensions: vec![],
+            },
+            signature: vec![0xab, 0xcd],
+        });
+        assert_eq!(TransItem::parse(&bytes).unwrap(), sth);
+        assert_eq!(sth.to_bytes(), bytes);
+
+        // The types only exist with the 0x01 prefix.
+        bytes[0] = 0x00;
+        TransItem::parse(&bytes).expect_err("");
+    }
+}
@@ -1,46 +1,260 @@
-//! Downloading of log list from Google.
+//! Downloading of log list from Google, or loading it (or Apple's) from disk.
+//!
+//! Follows the [v3 schema](https://www.gstatic.com/ct/log_list/v3/log_list_schema.json). All
+//! timestamps are converted to milliseconds since the epoch, like the rest of this crate.
+//!
+//! Apple's [log list](https://valid.apple.com/ct/log_list/current_log_list.json) is read into the
+//! same [`LogList`] with [`LogList::from_apple_json_bytes`].
+//!
+//! [`LogList::get`] only relies on TLS to authenticate the list. Use [`LogListFetcher`] to also
+//! check Google's signature on it, and to refuse older lists than one seen before.
 
-use crate::Error;
 use crate::internal::new_http_client;
+use crate::{Error, LogApi, utils};
 
+use base64::prelude::{BASE64_STANDARD, Engine as _};
+use log::info;
+use openssl::pkey::PKey;
 use serde::Deserialize;
 use std::collections::HashMap;
+use std::convert::TryInto;
+use std::path::Path;
 
 #[derive(Debug, Deserialize, Clone)]
 struct ResponseJSON {
+    version: String,
+    log_list_timestamp: String,
     operators: Vec<OperatorJSON>,
 }
 
 #[derive(Debug, Deserialize, Clone)]
 struct OperatorJSON {
     name: String,
+    #[serde(default)]
     email: Vec<String>,
+    #[serde(default)]
     logs: Vec<LogJson>,
+    #[serde(default)]
+    tiled_logs: Vec<LogJson>,
 }
 
+/// An entry in either `logs` or `tiled_logs`.
 #[derive(Debug, Deserialize, Clone)]
 struct LogJson {
     key: String,
     log_id: String,
     mmd: u64,
-    url: String,
-    state: HashMap<String, serde_json::Value>,
+    /// Only in `logs`.
+    url: Option<String>,
+    /// Only in `tiled_logs`.
+    submission_url: Option<String>,
+    /// Only in `tiled_logs`.
+    monitoring_url: Option<String>,
+    state: HashMap<String, StateJson>,
     description: String,
+    temporal_interval: Option<TemporalIntervalJson>,
+    log_type: Option<String>,
+    #[serde(default)]
+    previous_operators: Vec<PreviousOperatorJson>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+struct StateJson {
+    timestamp: String,
+    final_tree_head: Option<FinalTreeHeadJson>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+struct FinalTreeHeadJson {
+    sha256_root_hash: String,
+    tree_size: u64,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+struct TemporalIntervalJson {
+    start_inclusive: String,
+    end_exclusive: String,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+struct PreviousOperatorJson {
+    name: String,
+    end_time: String,
+}
+
+/// Apple's list is laid out like Google's, except for `version` sometimes being a number and
+/// `state` sometimes being just a name, with the time in `state_timestamp`. State names are
+/// also spelled differently (e.g. `"ReadOnly"`), see [`normalize_state_name`].
+#[derive(Debug, Deserialize, Clone)]
+struct AppleResponseJSON {
+    version: serde_json::Value,
+    log_list_timestamp: String,
+    operators: Vec<AppleOperatorJSON>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+struct AppleOperatorJSON {
+    name: String,
+    #[serde(default)]
+    email: Vec<String>,
+    #[serde(default)]
+    logs: Vec<AppleLogJson>,
+    #[serde(default)]
+    tiled_logs: Vec<AppleLogJson>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+struct AppleLogJson {
+    key: String,
+    log_id: String,
+    mmd: u64,
+    url: Option<String>,
+    submission_url: Option<String>,
+    monitoring_url: Option<String>,
+    state: AppleStateJson,
+    state_timestamp: Option<String>,
+    description: String,
+    temporal_interval: Option<TemporalIntervalJson>,
+    log_type: Option<String>,
+    #[serde(default)]
+    previous_operators: Vec<PreviousOperatorJson>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+#[serde(untagged)]
+enum AppleStateJson {
+    Object(HashMap<String, StateJson>),
+    Name(String),
+}
+
+/// Map Apple's spelling of a state (`"ReadOnly"`, `"read-only"`, ...) to Google's.
+fn normalize_state_name(name: &str) -> String {
+    name.chars()
+        .filter(|c| *c != '-' && *c != '_')
+        .collect::<String>()
+        .to_ascii_lowercase()
+}
+
+impl AppleResponseJSON {
+    fn into_google(self) -> Result<ResponseJSON, Error> {
+        let version = match self.version {
+            serde_json::Value::String(s) => s,
+            serde_json::Value::Number(n) => n.to_string(),
+            v => {
+                return Err(Error::MalformedResponseBody(format!(
+                    "Invalid version {}",
+                    v
+                )));
+            }
+        };
+        let mut operators = Vec::with_capacity(self.operators.len());
+        for op in self.operators {
+            let convert_logs = |logs: Vec<AppleLogJson>| -> Result<Vec<LogJson>, Error> {
+                logs.into_iter().map(|l| l.into_google()).collect()
+            };
+            operators.push(OperatorJSON {
+                name: op.name,
+                email: op.email,
+                logs: convert_logs(op.logs)?,
+                tiled_logs: convert_logs(op.tiled_logs)?,
+            });
+        }
+        Ok(ResponseJSON {
+            version,
+            log_list_timestamp: self.log_list_timestamp,
+            operators,
+        })
+    }
+}
+
+impl AppleLogJson {
+    fn into_google(self) -> Result<LogJson, Error> {
+        let state = match self.state {
+            AppleStateJson::Object(hm) => hm
+                .into_iter()
+                .map(|(name, state)| (normalize_state_name(&name), state))
+                .collect(),
+            AppleStateJson::Name(name) => {
+                let timestamp = self.state_timestamp.ok_or_else(|| {
+                    Error::MalformedResponseBody(format!(
+                        "Log {} has no state_timestamp.",
+                        &self.description
+                    ))
+                })?;
+                let mut hm = HashMap::with_capacity(1);
+                hm.insert(
+                    normalize_state_name(&name),
+                    StateJson {
+                        timestamp,
+                        final_tree_head: None,
+                    },
+                );
+                hm
+            }
+        };
+        Ok(LogJson {
+            key: self.key,
+            log_id: self.log_id,
+            mmd: self.mmd,
+            url: self.url,
+            submission_url: self.submission_url,
+            monitoring_url: self.monitoring_url,
+            state,
+            description: self.description,
+            temporal_interval: self.temporal_interval,
+            log_type: self.log_type,
+            previous_operators: self.previous_operators,
+        })
+    }
 }
 
 /// A downloaded log list.
 #[derive(Debug, Clone)]
 pub struct LogList {
+    /// Version of the list, like `"42.17"`.
+    pub version: String,
+    /// When this list was published, in ms since the epoch.
+    pub log_list_timestamp: u64,
+    pub operators: Vec<Operator>,
     pub map_id_to_log: HashMap<Vec<u8>, Log>,
 }
 
+/// A log operator in [`LogList`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Operator {
+    pub name: String,
+    pub email: Vec<String>,
+}
+
 /// A log in [`LogList`].
 #[derive(Debug, Clone)]
 pub struct Log {
+    /// SHA-256 of `pub_key`.
+    pub log_id: Vec<u8>,
     pub pub_key: Vec<u8>,
+    /// The URL to monitor the log with, which is the `url` of a RFC 6962 log or the
+    /// `monitoring_url` of a tiled log. Ends with `/`.
     pub base_url: String,
+    /// The URL to submit certificates to. Same as `base_url` for RFC 6962 logs.
+    pub submission_url: String,
+    /// Which API the log serves. [`LogApi::StaticCt`] for entries in `tiled_logs`.
+    pub api: LogApi,
     pub state: LogState,
+    /// When the log entered its current state, in ms since the epoch.
+    pub state_timestamp: u64,
+    /// For [`LogState::Readonly`] logs, the tree head they were frozen at.
+    pub final_tree_head: Option<FinalTreeHead>,
     pub description: String,
+    /// Maximum merge delay, in seconds.
+    pub mmd: u64,
+    /// Name of the current operator.
+    pub operator: String,
+    pub operator_email: Vec<String>,
+    /// For temporal shards, the range of notAfter of the certificates this log accepts.
+    pub temporal_interval: Option<TemporalInterval>,
+    /// `None` if the list does not say.
+    pub log_type: Option<LogType>,
+    pub previous_operators: Vec<PreviousOperator>,
 }
 
 #[derive(Debug, PartialEq, Eq, Copy, Clone)]
@@ -53,6 +267,45 @@ pub enum LogState {
     Rejected,
 }
 
+/// The tree head a read-only log is frozen at.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub struct FinalTreeHead {
+    pub tree_size: u64,
+    pub root_hash: [u8; 32],
+}
+
+/// A range of time, in ms since the epoch. `start_inclusive <= t < end_exclusive`.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub struct TemporalInterval {
+    pub start_inclusive: u64,
+    pub end_exclusive: u64,
+}
+
+impl TemporalInterval {
+    pub fn contains(&self, t: u64) -> bool {
+        self.start_inclusive <= t && t < self.end_exclusive
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum LogType {
+    Prod,
+    Test,
+}
+
+/// An operator that used to run a log.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct PreviousOperator {
+    pub name: String,
+    /// When this operator stopped running the log, in ms since the epoch.
+    pub end_time: u64,
+}
+
+fn parse_time(s: &str) -> Result<u64, Error> {
+    utils::rfc3339_to_ms(s)
+        .ok_or_else(|| Error::MalformedResponseBody(format!("Invalid timestamp {:?}", s)))
+}
+
 impl LogList {
     /// Download the log list at runtime from [`https://www.gstatic.com/ct/log_list/v3/log_list.json`](https://www.gstatic.com/ct/log_list/v3/log_list.json).
     pub async fn get() -> Result<LogList, Error> {
@@ -62,64 +315,638 @@ impl LogList {
     /// Download the log list at runtime.
     pub async fn get_with_url(url: &str) -> Result<LogList, Error> {
         let client = new_http_client()?;
-        let json: ResponseJSON = client
+        let json = client
             .get(url)
             .send()
             .await
             .map_err(Error::NetIO)?
-            .json()
+            .bytes()
             .await
+            .map_err(Error::NetIO)?;
+        LogList::from_json_bytes(&json)
+    }
+
+    /// Parse a log list in Google's v3 format, e.g. a saved copy of `log_list.json`.
+    pub fn from_json_bytes(json: &[u8]) -> Result<LogList, Error> {
+        let json: ResponseJSON = serde_json::from_slice(json)
             .map_err(|e| Error::MalformedResponseBody(format!("{}", e)))?;
-        let mut hm: HashMap<Vec<u8>, Log> =
-            HashMap::with_capacity(json.operators.iter().map(|x| x.logs.len()).sum());
-        fn b64_dec_err(e: base64::DecodeError) -> Error {
-            Error::MalformedResponseBody(format!("Unable to decode base64: {}", e))
-        }
+        LogList::from_json(&json)
+    }
+
+    /// Read a log list in Google's v3 format from a file.
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<LogList, Error> {
+        let path = path.as_ref();
+        let json = std::fs::read(path).map_err(|e| Error::FileIO(path.to_path_buf(), e))?;
+        LogList::from_json_bytes(&json)
+    }
+
+    /// Parse Apple's log list, e.g. a saved copy of
+    /// [`current_log_list.json`](https://valid.apple.com/ct/log_list/current_log_list.json).
+    pub fn from_apple_json_bytes(json: &[u8]) -> Result<LogList, Error> {
+        let json: AppleResponseJSON = serde_json::from_slice(json)
+            .map_err(|e| Error::MalformedResponseBody(format!("{}", e)))?;
+        LogList::from_json(&json.into_google()?)
+    }
+
+    /// Read Apple's log list from a file.
+    pub fn from_apple_file<P: AsRef<Path>>(path: P) -> Result<LogList, Error> {
+        let path = path.as_ref();
+        let json = std::fs::read(path).map_err(|e| Error::FileIO(path.to_path_buf(), e))?;
+        LogList::from_apple_json_bytes(&json)
+    }
+
+    fn from_json(json: &ResponseJSON) -> Result<LogList, Error> {
+        let mut hm: HashMap<Vec<u8>, Log> = HashMap::with_capacity(
+            json.operators
+                .iter()
+                .map(|x| x.logs.len() + x.tiled_logs.len())
+                .sum(),
+        );
+        let mut operators = Vec::with_capacity(json.operators.len());
         for op in json.operators.iter() {
-            for log in op.logs.iter() {
-                let log_id = base64::decode(&log.log_id).map_err(b64_dec_err)?;
-                let pub_key = base64::decode(&log.key).map_err(b64_dec_err)?;
-                let base_url = log.url.to_owned();
-                if hm.contains_key(&log_id) {
+            operators.push(Operator {
+                name: op.name.clone(),
+                email: op.email.clone(),
+            });
+            let logs = op.logs.iter().map(|l| (l, LogApi::Rfc6962));
+            let tiled_logs = op.tiled_logs.iter().map(|l| (l, LogApi::StaticCt));
+            for (log, api) in logs.chain(tiled_logs) {
+                let log = Log::from_json(log, api, op)?;
+                if hm.contains_key(&log.log_id) {
                     return Err(Error::MalformedResponseBody(
                         "Multiple logs returned with the same id.".to_owned(),
                     ));
                 }
-                let state_keys: Vec<&str> = log.state.keys().map(|x| &x[..]).collect();
-                use LogState::*;
-                let log_state = match &state_keys[..] {
-                    ["pending"] => Pending,
-                    ["qualified"] => Qualified,
-                    ["usable"] => Usable,
-                    ["readonly"] => Readonly,
-                    ["retired"] => Retired,
-                    ["rejected"] => Rejected,
-                    _ => {
-                        return Err(Error::MalformedResponseBody(format!(
-                            "Invalid log state object: {:?}",
-                            &log.state
-                        )));
-                    }
-                };
-                hm.insert(
-                    log_id,
-                    Log {
-                        pub_key,
-                        base_url,
-                        state: log_state,
-                        description: log.description.clone(),
-                    },
-                );
+                hm.insert(log.log_id.clone(), log);
             }
         }
 
-        Ok(LogList { map_id_to_log: hm })
+        Ok(LogList {
+            version: json.version.clone(),
+            log_list_timestamp: parse_time(&json.log_list_timestamp)?,
+            operators,
+            map_id_to_log: hm,
+        })
     }
 
     /// Lookup a [`Log`] by its 32-byte `log_id`.
     pub fn find_by_id<'a>(&'a self, id: &[u8]) -> Option<&'a Log> {
         self.map_id_to_log.get(id)
     }
+
+    /// Logs that would accept a certificate expiring at `not_after` at time `at` (both in ms
+    /// since the epoch), ordered by log id. See [`Log::check_accepts`] for the rules.
+    pub fn logs_accepting(
+        &self,
+        not_after: u64,
+        at: u64,
+        states: &[LogState],
+        log_type: Option<LogType>,
+    ) -> Vec<&Log> {
+        let mut logs: Vec<&Log> = self
+            .map_id_to_log
+            .values()
+            .filter(|log| log.check_accepts(not_after, at, states, log_type).is_ok())
+            .collect();
+        logs.sort_by(|a, b| a.log_id.cmp(&b.log_id));
+        logs
+    }
+
+    /// Like [`Self::logs_accepting`], with the notAfter taken from `cert`.
+    pub fn logs_accepting_cert<R: AsRef<openssl::x509::X509Ref>>(
+        &self,
+        cert: &R,
+        at: u64,
+        states: &[LogState],
+        log_type: Option<LogType>,
+    ) -> Result<Vec<&Log>, Error> {
+        let not_after = crate::certutils::get_not_after(cert)?;
+        Ok(self.logs_accepting(not_after, at, states, log_type))
+    }
+
+    /// Everything that changed going from `self` to `newer`, ordered by log id.
+    pub fn diff(&self, newer: &LogList) -> Vec<LogListChange> {
+        let mut ids: Vec<&Vec<u8>> = self
+            .map_id_to_log
+            .keys()
+            .chain(newer.map_id_to_log.keys())
+            .collect();
+        ids.sort();
+        ids.dedup();
+        let mut changes = Vec::new();
+        for id in ids {
+            let (old, new) = match (self.find_by_id(id), newer.find_by_id(id)) {
+                (None, Some(new)) => {
+                    changes.push(LogListChange::Added(new.clone()));
+                    continue;
+                }
+                (Some(old), None) => {
+                    changes.push(LogListChange::Removed(old.clone()));
+                    continue;
+                }
+                (Some(old), Some(new)) => (old, new),
+                (None, None) => unreachable!(),
+            };
+            let log_id = id.clone();
+            if old.state != new.state {
+                changes.push(LogListChange::StateChanged {
+                    log_id: log_id.clone(),
+                    from: old.state,
+                    to: new.state,
+                    timestamp: new.state_timestamp,
+                });
+            }
+            if old.base_url != new.base_url || old.api != new.api {
+                changes.push(LogListChange::UrlChanged {
+                    log_id: log_id.clone(),
+                    from: old.base_url.clone(),
+                    to: new.base_url.clone(),
+                });
+            }
+            if old.submission_url != new.submission_url {
+                changes.push(LogListChange::SubmissionUrlChanged {
+                    log_id: log_id.clone(),
+                    from: old.submission_url.clone(),
+                    to: new.submission_url.clone(),
+                });
+            }
+            if old.pub_key != new.pub_key {
+                changes.push(LogListChange::KeyChanged {
+                    log_id: log_id.clone(),
+                    from: old.pub_key.clone(),
+                    to: new.pub_key.clone(),
+                });
+            }
+            if old.operator != new.operator {
+                changes.push(LogListChange::OperatorChanged {
+                    log_id: log_id.clone(),
+                    from: old.operator.clone(),
+                    to: new.operator.clone(),
+                });
+            }
+            if old.temporal_interval != new.temporal_interval {
+                changes.push(LogListChange::TemporalIntervalChanged {
+                    log_id,
+                    from: old.temporal_interval,
+                    to: new.temporal_interval,
+                });
+            }
+        }
+        changes
+    }
+}
+
+/// A difference between two [`LogList`]s, as found by [`LogList::diff`].
+#[derive(Debug, Clone)]
+pub enum LogListChange {
+    /// A log that is only in the newer list.
+    Added(Log),
+    /// A log that is only in the older list.
+    Removed(Log),
+    /// The log moved to another state (e.g. usable to read-only), at `timestamp`.
+    StateChanged {
+        log_id: Vec<u8>,
+        from: LogState,
+        to: LogState,
+        timestamp: u64,
+    },
+    /// The [`Log::base_url`] changed, which is also the case if the log switched API.
+    UrlChanged {
+        log_id: Vec<u8>,
+        from: String,
+        to: String,
+    },
+    SubmissionUrlChanged {
+        log_id: Vec<u8>,
+        from: String,
+        to: String,
+    },
+    /// The public key changed without the log id changing.
+    ///
+    /// The log id is the SHA-256 hash of the key, so the two can only change together. Lists
+    /// are indexed by log id, and the id is not checked against the key when parsing, so this
+    /// only happens if one of the two lists pairs a log id with a key it was not derived from:
+    /// at least one of the lists is wrong.
+    KeyChanged {
+        log_id: Vec<u8>,
+        from: Vec<u8>,
+        to: Vec<u8>,
+    },
+    /// The log was handed to another operator.
+    OperatorChanged {
+        log_id: Vec<u8>,
+        from: String,
+        to: String,
+    },
+    TemporalIntervalChanged {
+        log_id: Vec<u8>,
+        from: Option<TemporalInterval>,
+        to: Option<TemporalInterval>,
+    },
+}
+
+impl LogListChange {
+    /// The log this change is about.
+    pub fn log_id(&self) -> &[u8] {
+        use LogListChange::*;
+        match self {
+            Added(log) | Removed(log) => &log.log_id,
+            StateChanged { log_id, .. }
+            | UrlChanged { log_id, .. }
+            | SubmissionUrlChanged { log_id, .. }
+            | KeyChanged { log_id, .. }
+            | OperatorChanged { log_id, .. }
+            | TemporalIntervalChanged { log_id, .. } => log_id,
+        }
+    }
+}
+
+/// Why a log would not accept a certificate, see [`Log::check_accepts`].
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum NotAcceptedReason {
+    /// The log is not in one of the wanted states.
+    State(LogState),
+    /// The log only entered its state after the time asked about.
+    StateTooRecent { state_timestamp: u64 },
+    /// The log is of another type than wanted, or the list doesn't say.
+    LogType(Option<LogType>),
+    /// The certificate's notAfter is outside the log's shard.
+    OutsideTemporalInterval(TemporalInterval),
+    /// The certificate has already expired at the time asked about.
+    Expired,
+}
+
+impl std::fmt::Display for NotAcceptedReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            NotAcceptedReason::State(s) => write!(f, "log is {:?}", s),
+            NotAcceptedReason::StateTooRecent { state_timestamp } => {
+                write!(f, "log only entered its state at {}", state_timestamp)
+            }
+            NotAcceptedReason::LogType(Some(t)) => write!(f, "log is a {:?} log", t),
+            NotAcceptedReason::LogType(None) => write!(f, "log type is not known"),
+            NotAcceptedReason::OutsideTemporalInterval(t) => write!(
+                f,
+                "log only accepts certificates expiring in [{}, {})",
+                t.start_inclusive, t.end_exclusive
+            ),
+            NotAcceptedReason::Expired => write!(f, "certificate has expired"),
+        }
+    }
+}
+
+impl Log {
+    /// Check whether this log would accept a certificate expiring at `not_after` at time `at`
+    /// (both in ms since the epoch). That is, if at `at`:
+    ///
+    /// * the log is in one of `states` (e.g. `&[LogState::Usable, LogState::Qualified]`), and
+    ///   was already so,
+    /// * the log is of type `log_type`, if it is given,
+    /// * `not_after` is in the log's temporal interval, if it is a temporal shard, and
+    /// * the certificate has not expired.
+    ///
+    /// This can also explain why a certificate is not in a log.
+    pub fn check_accepts(
+        &self,
+        not_after: u64,
+        at: u64,
+        states: &[LogState],
+        log_type: Option<LogType>,
+    ) -> Result<(), NotAcceptedReason> {
+        if !states.contains(&self.state) {
+            return Err(NotAcceptedReason::State(self.state));
+        }
+        if self.state_timestamp > at {
+            return Err(NotAcceptedReason::StateTooRecent {
+                state_timestamp: self.state_timestamp,
+            });
+        }
+        if log_type.is_some() && self.log_type != log_type {
+            return Err(NotAcceptedReason::LogType(self.log_type));
+        }
+        if let Some(interval) = self.temporal_interval
+            && !interval.contains(not_after)
+        {
+            return Err(NotAcceptedReason::OutsideTemporalInterval(interval));
+        }
+        if not_after <= at {
+            return Err(NotAcceptedReason::Expired);
+        }
+        Ok(())
+    }
+
+    fn from_json(log: &LogJson, api: LogApi, op: &OperatorJSON) -> Result<Log, Error> {
+        fn b64_dec_err(e: base64::DecodeError) -> Error {
+            Error::MalformedResponseBody(format!("Unable to decode base64: {}", e))
+        }
+        let log_id = BASE64_STANDARD.decode(&log.log_id).map_err(b64_dec_err)?;
+        let pub_key = BASE64_STANDARD.decode(&log.key).map_err(b64_dec_err)?;
+        let (base_url, submission_url) = match (api, &log.url) {
+            (LogApi::Rfc6962, Some(url)) => (url.clone(), url.clone()),
+            (LogApi::StaticCt, _) => match (&log.monitoring_url, &log.submission_url) {
+                (Some(m), Some(s)) => (m.clone(), s.clone()),
+                _ => {
+                    return Err(Error::MalformedResponseBody(format!(
+                        "Tiled log {} has no monitoring_url or submission_url.",
+                        &log.description
+                    )));
+                }
+            },
+            _ => {
+                return Err(Error::MalformedResponseBody(format!(
+                    "Log {} has no url.",
+                    &log.description
+                )));
+            }
+        };
+        if log.state.len() != 1 {
+            return Err(Error::MalformedResponseBody(format!(
+                "Invalid log state object: {:?}",
+                &log.state
+            )));
+        }
+        let (state_name, state) = log.state.iter().next().unwrap();
+        use LogState::*;
+        let log_state = match &state_name[..] {
+            "pending" => Pending,
+            "qualified" => Qualified,
+            "usable" => Usable,
+            "readonly" => Readonly,
+            "retired" => Retired,
+            "rejected" => Rejected,
+            _ => {
+                return Err(Error::MalformedResponseBody(format!(
+                    "Invalid log state object: {:?}",
+                    &log.state
+                )));
+            }
+        };
+        let final_tree_head = match &state.final_tree_head {
+            Some(fth) => Some(FinalTreeHead {
+                tree_size: fth.tree_size,
+                root_hash: BASE64_STANDARD
+                    .decode(&fth.sha256_root_hash)
+                    .map_err(b64_dec_err)?[..]
+                    .try_into()
+                    .map_err(|_| {
+                        Error::MalformedResponseBody(
+                            "final_tree_head has an invalid root hash.".to_owned(),
+                        )
+                    })?,
+            }),
+            None => None,
+        };
+        let temporal_interval = match &log.temporal_interval {
+            Some(t) => Some(TemporalInterval {
+                start_inclusive: parse_time(&t.start_inclusive)?,
+                end_exclusive: parse_time(&t.end_exclusive)?,
+            }),
+            None => None,
+        };
+        let log_type = match log.log_type.as_deref() {
+            None => None,
+            Some("prod") => Some(LogType::Prod),
+            Some("test") => Some(LogType::Test),
+            Some(t) => {
+                return Err(Error::MalformedResponseBody(format!(
+                    "Invalid log type {:?}",
+                    t
+                )));
+            }
+        };
+        let mut previous_operators = Vec::with_capacity(log.previous_operators.len());
+        for prev in log.previous_operators.iter() {
+            previous_operators.push(PreviousOperator {
+                name: prev.name.clone(),
+                end_time: parse_time(&prev.end_time)?,
+            });
+        }
+        Ok(Log {
+            log_id,
+            pub_key,
+            base_url,
+            submission_url,
+            api,
+            state: log_state,
+            state_timestamp: parse_time(&state.timestamp)?,
+            final_tree_head,
+            description: log.description.clone(),
+            mmd: log.mmd,
+            operator: op.name.clone(),
+            operator_email: op.email.clone(),
+            temporal_interval,
+            log_type,
+            previous_operators,
+        })
+    }
+}
+
+/// Check the detached signature `sig` (like the content of `log_list.sig`) on the log list
+/// `json`, made with the key `signing_key` (DER public key).
+pub fn verify_log_list_signature(json: &[u8], sig: &[u8], signing_key: &[u8]) -> Result<(), Error> {
+    let key = PKey::public_key_from_der(signing_key)
+        .map_err(|e| Error::InvalidArgument(format!("Error parsing public key: {}", &e)))?;
+    verify_signature(json, sig, &key)
+}
+
+fn verify_signature(
+    json: &[u8],
+    sig: &[u8],
+    key: &PKey<openssl::pkey::Public>,
+) -> Result<(), Error> {
+    let mut verifier = openssl::sign::Verifier::new(openssl::hash::MessageDigest::sha256(), key)
+        .map_err(|e| Error::Unknown(format!("EVP_DigestVerifyInit: {}", &e)))?;
+    if !verifier.verify_oneshot(sig, json).unwrap_or(false) {
+        return Err(Error::InvalidSignature(
+            "Log list signature does not match.".to_owned(),
+        ));
+    }
+    Ok(())
+}
+
+/// Downloads log lists along with their detached signature, and only accepts them if the
+/// signature is valid.
+///
+/// Optionally also refuses lists with a `log_list_timestamp` older than the last accepted one, so
+/// that a stale (but validly signed) list can't be served to us to bring back a removed log.
+pub struct LogListFetcher {
+    list_url: reqwest::Url,
+    sig_url: reqwest::Url,
+    signing_key: PKey<openssl::pkey::Public>,
+    http_client: reqwest::Client,
+    reject_rollback: bool,
+    last_log_list_timestamp: Option<u64>,
+}
+
+impl LogListFetcher {
+    /// Fetch from Google's v3 `log_list.json` and `log_list.sig`.
+    ///
+    /// `signing_key` is the DER public key the list is signed with, which Google publishes as
+    /// [`log_list_pubkey.pem`](https://www.gstatic.com/ct/log_list/v3/log_list_pubkey.pem). It is
+    /// not built into this library so that it can be rotated without an upgrade.
+    pub fn new(signing_key: &[u8]) -> Result<Self, Error> {
+        LogListFetcher::new_with_urls(
+            "https://www.gstatic.com/ct/log_list/v3/log_list.json",
+            "https://www.gstatic.com/ct/log_list/v3/log_list.sig",
+            signing_key,
+        )
+    }
+
+    pub fn new_with_urls(list_url: &str, sig_url: &str, signing_key: &[u8]) -> Result<Self, Error> {
+        let parse_url = |url: &str| {
+            reqwest::Url::parse(url)
+                .map_err(|e| Error::InvalidArgument(format!("Unable to parse url: {}", &e)))
+        };
+        Ok(LogListFetcher {
+            list_url: parse_url(list_url)?,
+            sig_url: parse_url(sig_url)?,
+            signing_key: PKey::public_key_from_der(signing_key)
+                .map_err(|e| Error::InvalidArgument(format!("Error parsing public key: {}", &e)))?,
+            http_client: new_http_client()?,
+            reject_rollback: false,
+            last_log_list_timestamp: None,
+        })
+    }
+
+    /// If set to true, refuse lists older than the last one accepted (or the one set with
+    /// [`Self::set_last_log_list_timestamp`]) with [`Error::LogListRollback`].
+    ///
+    /// Default is false.
+    pub fn set_reject_rollback(&mut self, reject: bool) {
+        self.reject_rollback = reject;
+    }
+
+    /// The `log_list_timestamp` of the last accepted list. Store this somewhere to keep rejecting
+    /// rollbacks across restarts.
+    pub fn get_last_log_list_timestamp(&self) -> Option<u64> {
+        self.last_log_list_timestamp
+    }
+
+    pub fn set_last_log_list_timestamp(&mut self, timestamp: Option<u64>) {
+        self.last_log_list_timestamp = timestamp;
+    }
+
+    /// Download the list and its signature, and check them.
+    pub async fn fetch(&mut self) -> Result<LogList, Error> {
+        let json = crate::internal::get_bytes(&self.http_client, &self.list_url, "").await?;
+        let sig = crate::internal::get_bytes(&self.http_client, &self.sig_url, "").await?;
+        self.accept(&json, &sig)
+    }
+
+    /// Check a list and its signature obtained some other way, as if it was fetched.
+    pub fn accept(&mut self, json: &[u8], sig: &[u8]) -> Result<LogList, Error> {
+        verify_signature(json, sig, &self.signing_key)?;
+        let list = LogList::from_json_bytes(json)?;
+        if let Some(last) = self.last_log_list_timestamp
+            && self.reject_rollback
+            && list.log_list_timestamp < last
+        {
+            return Err(Error::LogListRollback {
+                previous_timestamp: last,
+                timestamp: list.log_list_timestamp,
+            });
+        }
+        if self.last_log_list_timestamp != Some(list.log_list_timestamp) {
+            info!(
+                "Accepted log list version {} ({} logs)",
+                &list.version,
+                list.map_id_to_log.len()
+            );
+        }
+        self.last_log_list_timestamp = Some(u64::max(
+            list.log_list_timestamp,
+            self.last_log_list_timestamp.unwrap_or(0),
+        ));
+        Ok(list)
+    }
+}
+
+enum LogListSource {
+    Url(String),
+    Fetcher(LogListFetcher),
+}
+
+/// Keeps a [`LogList`] up to date and reports what changed on each refresh.
+///
+/// ```no_run
+/// # async fn f() -> Result<(), ctclient_async::Error> {
+/// use ctclient_async::google_log_list::{LogList, LogListChange, LogListWatcher};
+/// use futures::StreamExt;
+/// let watcher = LogListWatcher::new(LogList::get().await?);
+/// let changes = watcher.into_stream(std::time::Duration::from_secs(3600));
+/// futures::pin_mut!(changes);
+/// while let Some(changes) = changes.next().await {
+///     for change in changes? {
+///         if let LogListChange::Added(log) = change {
+///             // start monitoring log...
+///         }
+///     }
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub struct LogListWatcher {
+    current: LogList,
+    source: LogListSource,
+}
+
+impl LogListWatcher {
+    /// Refresh from Google's v3 log list, starting with `current`.
+    pub fn new(current: LogList) -> Self {
+        LogListWatcher::new_with_url(
+            current,
+            "https://www.gstatic.com/ct/log_list/v3/log_list.json",
+        )
+    }
+
+    pub fn new_with_url(current: LogList, url: &str) -> Self {
+        LogListWatcher {
+            current,
+            source: LogListSource::Url(url.to_owned()),
+        }
+    }
+
+    /// Refresh with `fetcher`, so that signature and rollback checks apply.
+    pub fn new_with_fetcher(current: LogList, fetcher: LogListFetcher) -> Self {
+        LogListWatcher {
+            current,
+            source: LogListSource::Fetcher(fetcher),
+        }
+    }
+
+    pub fn current(&self) -> &LogList {
+        &self.current
+    }
+
+    /// Download the list again, replacing the current one, and return what changed.
+    ///
+    /// On error, the current list is kept.
+    pub async fn refresh(&mut self) -> Result<Vec<LogListChange>, Error> {
+        let newer = match &mut self.source {
+            LogListSource::Url(url) => LogList::get_with_url(url).await?,
+            LogListSource::Fetcher(fetcher) => fetcher.fetch().await?,
+        };
+        let changes = self.current.diff(&newer);
+        self.current = newer;
+        Ok(changes)
+    }
+
+    /// Call [`Self::refresh`] every `interval`, yielding its result if it is an error or if
+    /// anything changed. The stream never ends, and keeps going after errors.
+    pub fn into_stream(
+        mut self,
+        interval: std::time::Duration,
+    ) -> impl futures::Stream<Item = Result<Vec<LogListChange>, Error>> {
+        async_stream::stream! {
+            loop {
+                tokio::time::sleep(interval).await;
+                match self.refresh().await {
+                    Ok(changes) if changes.is_empty() => {}
+                    res => yield res,
+                }
+            }
+        }
+    }
 }
 
 #[cfg(test)]
@@ -131,10 +958,238 @@ mod tests {
         let nb_logs = ll.map_id_to_log.len();
         assert!(nb_logs > 0);
         assert_eq!(
-            ll.find_by_id(&base64::decode("2AlVO5RPev/IFhlvlE+Fq7D4/F6HVSYPFdEucrtFSxQ=").unwrap())
-                .unwrap()
-                .base_url,
+            ll.find_by_id(
+                &BASE64_STANDARD
+                    .decode("2AlVO5RPev/IFhlvlE+Fq7D4/F6HVSYPFdEucrtFSxQ=")
+                    .unwrap()
+            )
+            .unwrap()
+            .base_url,
             "https://ct.googleapis.com/logs/eu1/xenon2026h2/"
         );
     }
+
+    #[test]
+    fn parse_v3_test() {
+        let json: ResponseJSON =
+            serde_json::from_slice(include_bytes!("test_data/log_list_v3.json")).unwrap();
+        let ll = LogList::from_json(&json).unwrap();
+        assert_eq!(ll.version, "1.5");
+        assert_eq!(ll.log_list_timestamp, 1704067200000);
+        assert_eq!(ll.operators.len(), 2);
+        assert_eq!(ll.map_id_to_log.len(), 3);
+
+        let rfc_log = ll.find_by_id(&[0x11; 32]).unwrap();
+        assert_eq!(rfc_log.api, LogApi::Rfc6962);
+        assert_eq!(rfc_log.submission_url, rfc_log.base_url);
+        assert_eq!(rfc_log.state, LogState::Usable);
+        assert_eq!(rfc_log.state_timestamp, 1672531200000);
+        assert_eq!(rfc_log.mmd, 86400);
+        assert_eq!(rfc_log.operator, "Example Operator");
+        assert_eq!(rfc_log.operator_email, vec!["ct@example.com".to_owned()]);
+        assert_eq!(rfc_log.log_type, Some(LogType::Prod));
+        let interval = rfc_log.temporal_interval.unwrap();
+        assert!(interval.contains(1735689600000));
+        assert!(!interval.contains(1767225600000));
+        assert_eq!(
+            rfc_log.previous_operators,
+            vec![PreviousOperator {
+                name: "Old Operator".to_owned(),
+                end_time: 1640995200000
+            }]
+        );
+
+        let readonly = ll.find_by_id(&[0x22; 32]).unwrap();
+        assert_eq!(readonly.state, LogState::Readonly);
+        assert_eq!(
+            readonly.final_tree_head,
+            Some(FinalTreeHead {
+                tree_size: 1000,
+                root_hash: [0x33; 32]
+            })
+        );
+        assert_eq!(readonly.temporal_interval, None);
+        assert_eq!(readonly.log_type, None);
+
+        let tiled = ll.find_by_id(&[0x44; 32]).unwrap();
+        assert_eq!(tiled.api, LogApi::StaticCt);
+        assert_eq!(tiled.base_url, "https://mon.example.net/tiled2025h1/");
+        assert_eq!(tiled.submission_url, "https://example.net/tiled2025h1/");
+        assert_eq!(tiled.operator, "Tiled Operator");
+        assert_eq!(tiled.log_type, Some(LogType::Test));
+    }
+
+    #[test]
+    fn from_file_test() {
+        let dir = concat!(env!("CARGO_MANIFEST_DIR"), "/src/test_data/");
+        let ll = LogList::from_file(format!("{}log_list_v3.json", dir)).unwrap();
+        assert_eq!(ll.map_id_to_log.len(), 3);
+        assert!(matches!(
+            LogList::from_file(format!("{}does_not_exist.json", dir)),
+            Err(Error::FileIO(..))
+        ));
+
+        let apple = LogList::from_apple_file(format!("{}log_list_apple.json", dir)).unwrap();
+        assert_eq!(apple.version, "7");
+        assert_eq!(apple.log_list_timestamp, 1709251200000);
+        assert_eq!(apple.map_id_to_log.len(), 2);
+        let usable = apple.find_by_id(&[0x11; 32]).unwrap();
+        assert_eq!(usable.state, LogState::Usable);
+        assert_eq!(usable.state_timestamp, 1672531200000);
+        assert_eq!(usable.api, LogApi::Rfc6962);
+        assert!(usable.temporal_interval.unwrap().contains(1735689600000));
+        let frozen = apple.find_by_id(&[0x22; 32]).unwrap();
+        assert_eq!(frozen.state, LogState::Readonly);
+        assert_eq!(frozen.final_tree_head.unwrap().tree_size, 1000);
+        // Apple and Google list the same key for the same log.
+        assert_eq!(frozen.pub_key, ll.find_by_id(&[0x22; 32]).unwrap().pub_key);
+    }
+
+    #[test]
+    fn logs_accepting_test() {
+        let ll = LogList::from_json_bytes(include_bytes!("test_data/log_list_v3.json")).unwrap();
+        let states = [LogState::Usable, LogState::Qualified];
+        let ms = |t| utils::rfc3339_to_ms(t).unwrap();
+        let jun_2025 = ms("2025-06-01T00:00:00Z");
+        let sep_2025 = ms("2025-09-01T00:00:00Z");
+        let jul_2024 = ms("2024-07-01T00:00:00Z");
+        let ids = |logs: Vec<&Log>| logs.iter().map(|l| l.log_id[0]).collect::<Vec<_>>();
+
+        assert_eq!(
+            ids(ll.logs_accepting(jun_2025, jul_2024, &states, None)),
+            vec![0x11, 0x44]
+        );
+        assert_eq!(
+            ids(ll.logs_accepting(jun_2025, jul_2024, &states, Some(LogType::Prod))),
+            vec![0x11]
+        );
+        assert_eq!(
+            ids(ll.logs_accepting(sep_2025, jul_2024, &states, None)),
+            vec![0x11]
+        );
+        // The tiled log was not qualified yet.
+        assert_eq!(
+            ids(ll.logs_accepting(jun_2025, ms("2024-01-01T00:00:00Z"), &states, None)),
+            vec![0x11]
+        );
+        assert!(
+            ll.logs_accepting(ms("2026-01-01T00:00:00Z"), jul_2024, &states, None)
+                .is_empty()
+        );
+
+        let log = ll.find_by_id(&[0x11; 32]).unwrap();
+        assert_eq!(
+            log.check_accepts(ms("2024-12-31T00:00:00Z"), jul_2024, &states, None),
+            Err(NotAcceptedReason::OutsideTemporalInterval(
+                log.temporal_interval.unwrap()
+            ))
+        );
+        assert_eq!(
+            log.check_accepts(jun_2025, jun_2025, &states, None),
+            Err(NotAcceptedReason::Expired)
+        );
+        let tiled = ll.find_by_id(&[0x44; 32]).unwrap();
+        assert_eq!(
+            tiled.check_accepts(jun_2025, jul_2024, &states, Some(LogType::Prod)),
+            Err(NotAcceptedReason::LogType(Some(LogType::Test)))
+        );
+        let frozen = ll.find_by_id(&[0x22; 32]).unwrap();
+        assert_eq!(
+            frozen.check_accepts(jun_2025, jul_2024, &states, None),
+            Err(NotAcceptedReason::State(LogState::Readonly))
+        );
+    }
+
+    #[test]
+    fn diff_test() {
+        let old = LogList::from_json_bytes(include_bytes!("test_data/log_list_v3.json")).unwrap();
+        assert!(old.diff(&old).is_empty());
+
+        let mut new = old.clone();
+        new.map_id_to_log.remove(&vec![0x44; 32]);
+        let mut added = old.find_by_id(&[0x11; 32]).unwrap().clone();
+        added.log_id = vec![0x55; 32];
+        new.map_id_to_log.insert(added.log_id.clone(), added);
+        let log = new.map_id_to_log.get_mut(&vec![0x11; 32]).unwrap();
+        log.state = LogState::Readonly;
+        log.state_timestamp = 1704067200000;
+        log.operator = "New Operator".to_owned();
+        log.temporal_interval = None;
+        let log = new.map_id_to_log.get_mut(&vec![0x22; 32]).unwrap();
+        log.base_url = "https://ct2.example.com/frozen/".to_owned();
+        log.pub_key = vec![0x30, 0x00];
+
+        let changes = old.diff(&new);
+        assert_eq!(changes.len(), 7);
+        assert!(matches!(
+            &changes[0],
+            LogListChange::StateChanged {
+                from: LogState::Usable,
+                to: LogState::Readonly,
+                timestamp: 1704067200000,
+                ..
+            }
+        ));
+        assert!(
+            matches!(&changes[1], LogListChange::OperatorChanged { from, to, .. } if from == "Example Operator" && to == "New Operator")
+        );
+        assert!(matches!(
+            &changes[2],
+            LogListChange::TemporalIntervalChanged { to: None, .. }
+        ));
+        assert!(
+            matches!(&changes[3], LogListChange::UrlChanged { to, .. } if to == "https://ct2.example.com/frozen/")
+        );
+        assert!(
+            matches!(&changes[4], LogListChange::KeyChanged { from, to, .. } if from == &old.find_by_id(&[0x22; 32]).unwrap().pub_key && to == &[0x30, 0x00])
+        );
+        assert!(matches!(&changes[5], LogListChange::Removed(_)));
+        assert!(matches!(&changes[6], LogListChange::Added(_)));
+        assert_eq!(changes[0].log_id(), &[0x11; 32]);
+        assert_eq!(changes[3].log_id(), &[0x22; 32]);
+        assert_eq!(changes[4].log_id(), &[0x22; 32]);
+        assert_eq!(changes[5].log_id(), &[0x44; 32]);
+        assert_eq!(changes[6].log_id(), &[0x55; 32]);
+    }
+
+    #[test]
+    fn signed_list_test() {
+        use openssl::rsa::Rsa;
+        let key = PKey::from_rsa(Rsa::generate(2048).unwrap()).unwrap();
+        let pub_key = key.public_key_to_der().unwrap();
+        let sign = |data: &[u8]| {
+            let mut signer =
+                openssl::sign::Signer::new(openssl::hash::MessageDigest::sha256(), &key).unwrap();
+            signer.sign_oneshot_to_vec(data).unwrap()
+        };
+        let json = &include_bytes!("test_data/log_list_v3.json")[..];
+        let sig = sign(json);
+        verify_log_list_signature(json, &sig, &pub_key).unwrap();
+
+        let mut fetcher = LogListFetcher::new(&pub_key).unwrap();
+        assert_eq!(fetcher.accept(json, &sig).unwrap().version, "1.5");
+        assert_eq!(fetcher.get_last_log_list_timestamp(), Some(1704067200000));
+        let mut tampered = json.to_vec();
+        let pos = tampered.iter().position(|b| *b == b'5').unwrap();
+        tampered[pos] = b'6';
+        assert!(matches!(
+            fetcher.accept(&tampered, &sig),
+            Err(Error::InvalidSignature(_))
+        ));
+
+        let newer = String::from_utf8(json.to_vec())
+            .unwrap()
+            .replace("2024-01-01T00:00:00Z", "2024-02-01T00:00:00Z")
+            .into_bytes();
+        fetcher.accept(&newer, &sign(&newer)).unwrap();
+        // Without rollback protection, going back is fine.
+        fetcher.accept(json, &sig).unwrap();
+        fetcher.accept(&newer, &sign(&newer)).unwrap();
+        fetcher.set_reject_rollback(true);
+        assert!(matches!(
+            fetcher.accept(json, &sig),
+            Err(Error::LogListRollback { .. })
+        ));
+        fetcher.accept(&newer, &sign(&newer)).unwrap();
+    }
 }
@@ -0,0 +1,217 @@
+use crate::Error;
+use crate::internal::{consistency_proof_parts, verify_consistency_proof};
+use crate::utils::{combine_tree_hash, sha256, u8_to_hex};
+
+/// The part of a Merkle tree needed to compute its root and to append more leaves to it, without
+/// keeping the leaves themselves. Also known as the compact range `[0, size)`, or the Merkle
+/// frontier of the tree.
+///
+/// A tree of `size` leaves splits into one perfect subtree for each bit set in `size`, and only
+/// the hashes of these subtrees are stored, which takes O(log n) memory. For example, the tree of
+/// size 7 is stored as the hashes of the subtrees `[0, 4)`, `[4, 6)` and `[6, 7)`.
+///
+/// [`CTClient::update`](crate::CTClient::update) uses this to check new leaves as they are
+/// streamed from the server.
+///
+/// # Example
+///
+/// ```
+/// # use ctclient_async::internal::CompactRange;
+/// # use ctclient_async::utils::{combine_tree_hash, sha256};
+/// let leaf_hashes: Vec<[u8; 32]> = (0..3u8).map(|i| sha256(&[i])).collect();
+/// let mut range = CompactRange::new();
+/// for h in leaf_hashes.iter() {
+///     range.append(h);
+/// }
+/// assert_eq!(range.size(), 3);
+/// assert_eq!(
+///     range.root(),
+///     combine_tree_hash(&combine_tree_hash(&leaf_hashes[0], &leaf_hashes[1]), &leaf_hashes[2])
+/// );
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct CompactRange {
+    size: u64,
+    /// Hashes of the perfect subtrees, largest (leftmost) first.
+    nodes: Vec<[u8; 32]>,
+}
+
+impl CompactRange {
+    /// The range of an empty tree.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Construct a range from the hashes returned by [`Self::nodes`]. There must be exactly one
+    /// hash for each bit set in `size`.
+    pub fn from_nodes(size: u64, nodes: Vec<[u8; 32]>) -> Result<Self, Error> {
+        if nodes.len() != size.count_ones() as usize {
+            return Err(Error::InvalidArgument(format!(
+                "A tree of size {} needs {} nodes, got {}.",
+                size,
+                size.count_ones(),
+                nodes.len()
+            )));
+        }
+        Ok(CompactRange { size, nodes })
+    }
+
+    /// Recover the range of the tree of size `perv_size` out of a consistency proof to a bigger
+    /// tree, in the format taken by [`verify_consistency_proof`]. The proof is verified first.
+    ///
+    /// This works because the part of a consistency proof covering the old tree is made of
+    /// exactly the subtrees stored in its compact range.
+    pub fn from_consistency_proof(
+        perv_size: u64,
+        next_size: u64,
+        server_provided_proof: &[[u8; 32]],
+        perv_root: &[u8; 32],
+        next_root: &[u8; 32],
+    ) -> Result<Self, String> {
+        if perv_size >= next_size {
+            return Err(format!(
+                "Need a proof to a bigger tree, but {} >= {}",
+                perv_size, next_size
+            ));
+        }
+        verify_consistency_proof(
+            perv_size,
+            next_size,
+            server_provided_proof,
+            perv_root,
+            next_root,
+        )?;
+        if perv_size == 0 {
+            return Ok(Self::new());
+        }
+        let mut hashes = Vec::with_capacity(server_provided_proof.len() + 1);
+        if perv_size.is_power_of_two() {
+            hashes.push(*perv_root);
+        }
+        hashes.extend_from_slice(server_provided_proof);
+        let mut old_parts: Vec<((u64, u64), [u8; 32])> =
+            consistency_proof_parts(perv_size, next_size)
+                .into_iter()
+                .zip(hashes)
+                .filter(|(subtree, _)| subtree.1 <= perv_size)
+                .collect();
+        old_parts.sort_by_key(|(subtree, _)| subtree.0);
+        let range = Self::from_nodes(perv_size, old_parts.into_iter().map(|(_, h)| h).collect())
+            .map_err(|e| e.to_string())?;
+        let root = range.root();
+        if &root != perv_root {
+            return Err(format!(
+                "calculated perv_root {} does not match given perv_root {}",
+                u8_to_hex(&root),
+                u8_to_hex(perv_root)
+            ));
+        }
+        Ok(range)
+    }
+
+    /// Number of leaves in the tree.
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    /// Hashes of the perfect subtrees making up the tree, largest first.
+    pub fn nodes(&self) -> &[[u8; 32]] {
+        &self.nodes
+    }
+
+    /// Add a leaf to the right of the tree.
+    pub fn append(&mut self, leaf_hash: &[u8; 32]) {
+        // Every trailing 1 bit of the size is a subtree that is now completed by the new leaf,
+        // merging into one subtree of double size.
+        let mut hash = *leaf_hash;
+        let mut size = self.size;
+        while size & 1 == 1 {
+            hash = combine_tree_hash(&self.nodes.pop().unwrap(), &hash);
+            size >>= 1;
+        }
+        self.nodes.push(hash);
+        self.size += 1;
+    }
+
+    /// The root hash of the tree.
+    pub fn root(&self) -> [u8; 32] {
+        let mut nodes = self.nodes.iter().rev();
+        let mut root = match nodes.next() {
+            Some(h) => *h,
+            None => return sha256(b""),
+        };
+        for h in nodes {
+            root = combine_tree_hash(h, &root);
+        }
+        root
+    }
+}
+
+#[cfg(test)]
+fn reference_tree_hash(leaf_hashes: &[[u8; 32]]) -> [u8; 32] {
+    use crate::utils::largest_power_of_2_smaller_than;
+    match leaf_hashes.len() {
+        0 => sha256(b""),
+        1 => leaf_hashes[0],
+        n => {
+            let k = largest_power_of_2_smaller_than(n as u64) as usize;
+            combine_tree_hash(
+                &reference_tree_hash(&leaf_hashes[..k]),
+                &reference_tree_hash(&leaf_hashes[k..]),
+            )
+        }
+    }
+}
+
+#[test]
+fn append_test() {
+    let leaf_hashes: Vec<[u8; 32]> = (0..70u8).map(|i| sha256(&[i])).collect();
+    let mut range = CompactRange::new();
+    assert_eq!(range.root(), reference_tree_hash(&[]));
+    for (i, h) in leaf_hashes.iter().enumerate() {
+        range.append(h);
+        assert_eq!(range.size(), i as u64 + 1);
+        assert_eq!(range.nodes().len(), (i + 1).count_ones() as usize);
+        assert_eq!(range.root(), reference_tree_hash(&leaf_hashes[..=i]));
+    }
+    let copy = CompactRange::from_nodes(range.size(), range.nodes().to_vec()).unwrap();
+    assert_eq!(copy, range);
+    CompactRange::from_nodes(range.size() + 1, range.nodes().to_vec()).expect_err("");
+}
+
+#[test]
+fn from_consistency_proof_test() {
+    let leaf_hashes: Vec<[u8; 32]> = (0..24u8).map(|i| sha256(&[i])).collect();
+    let mut expected = CompactRange::new();
+    for perv_size in 0..leaf_hashes.len() as u64 {
+        for next_size in perv_size + 1..=leaf_hashes.len() as u64 {
+            let mut parts = if perv_size == 0 {
+                Vec::new()
+            } else {
+                consistency_proof_parts(perv_size, next_size)
+            };
+            if perv_size.is_power_of_two() {
+                parts.remove(0);
+            }
+            let mut proof: Vec<[u8; 32]> = parts
+                .into_iter()
+                .map(|(start, end)| reference_tree_hash(&leaf_hashes[start as usize..end as usize]))
+                .collect();
+            let perv_root = expected.root();
+            let next_root = reference_tree_hash(&leaf_hashes[..next_size as usize]);
+            let range = CompactRange::from_consistency_proof(
+                perv_size, next_size, &proof, &perv_root, &next_root,
+            )
+            .unwrap();
+            assert_eq!(range, expected);
+            if !proof.is_empty() {
+                proof[0][0] ^= 1;
+                CompactRange::from_consistency_proof(
+                    perv_size, next_size, &proof, &perv_root, &next_root,
+                )
+                .expect_err("");
+            }
+        }
+        expected.append(&leaf_hashes[perv_size as usize]);
+    }
+}
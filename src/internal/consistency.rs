@@ -1,5 +1,7 @@
 use std::convert::TryInto;
 
+use base64::prelude::{BASE64_STANDARD, Engine as _};
+
 use crate::Error;
 use crate::internal::get_json;
 use crate::jsons;
@@ -177,8 +179,7 @@ pub fn verify_consistency_proof(
         ));
     }
 
-    let mut hashes = Vec::new();
-    hashes.reserve(calculated_proof.len());
+    let mut hashes = Vec::with_capacity(calculated_proof.len());
     if omit_first {
         hashes.push(*perv_root);
     }
@@ -320,8 +321,7 @@ impl ConsistencyProofPart {
         }
         let mut round_hashes = Vec::from(leaf_hashes);
         loop {
-            let mut new_round_hashes = Vec::new();
-            new_round_hashes.reserve(round_hashes.len() / 2);
+            let mut new_round_hashes = Vec::with_capacity(round_hashes.len() / 2);
             for i in 0..(round_hashes.len() / 2) {
                 let hash_left = round_hashes[2 * i];
                 let hash_right = round_hashes[2 * i + 1];
@@ -454,6 +454,35 @@ pub async fn check_consistency_proof(
     perv_root: &[u8; 32],
     next_root: &[u8; 32],
 ) -> Result<Vec<ConsistencyProofPart>, Error> {
+    let parsed_server_proof =
+        fetch_consistency_proof(client, base_url, prev_size, next_size).await?;
+    verify_consistency_proof(
+        prev_size,
+        next_size,
+        &parsed_server_proof,
+        perv_root,
+        next_root,
+    )
+    .map_err(|e| Error::InvalidConsistencyProof {
+        prev_size,
+        new_size: next_size,
+        desc: e,
+    })
+}
+
+/// Fetch the consistency proof from prev_size to next_size from the server without verifying
+/// it. The result is in the format taken by [`verify_consistency_proof`] and
+/// [`CompactRange::from_consistency_proof`](crate::internal::CompactRange::from_consistency_proof).
+///
+/// # Panics
+///
+/// ...if prev_size >= next_size
+pub async fn fetch_consistency_proof(
+    client: &reqwest::Client,
+    base_url: &reqwest::Url,
+    prev_size: u64,
+    next_size: u64,
+) -> Result<Vec<[u8; 32]>, Error> {
     assert!(prev_size < next_size);
     let server_consistency_proof: jsons::ConsistencyProof = get_json(
         client,
@@ -465,12 +494,11 @@ pub async fn check_consistency_proof(
     )
     .await?;
     let server_consistency_proof = server_consistency_proof.consistency;
-    let mut parsed_server_proof: Vec<[u8; 32]> = Vec::new();
-    parsed_server_proof.reserve(server_consistency_proof.len());
+    let mut parsed_server_proof: Vec<[u8; 32]> = Vec::with_capacity(server_consistency_proof.len());
     let mut n = 0;
     for i in server_consistency_proof.into_iter() {
         n += 1;
-        let decoded = base64::decode(&i).map_err(|e| {
+        let decoded = BASE64_STANDARD.decode(&i).map_err(|e| {
             Error::MalformedResponseBody(format!(
                 "Can not base64 decode consistency proof element: {}",
                 &e
@@ -484,16 +512,5 @@ pub async fn check_consistency_proof(
         parsed_server_proof.push(decoded[..].try_into().unwrap());
     }
     assert_eq!(parsed_server_proof.len(), n);
-    verify_consistency_proof(
-        prev_size,
-        next_size,
-        &parsed_server_proof,
-        perv_root,
-        next_root,
-    )
-    .map_err(|e| Error::InvalidConsistencyProof {
-        prev_size,
-        new_size: next_size,
-        desc: e,
-    })
+    Ok(parsed_server_proof)
 }
@@ -19,12 +19,12 @@ pub(crate) const SIGSCHEME_RSA_PKCS1_SHA256: u16 = 0x0401;
 /// # Params
 ///
 /// * `dss`: the `DigitallySigned` struct. Often returned as a
-/// base64 "signature" json field by the CT server. De-base64 yourself before
-/// calling.
+///   base64 "signature" json field by the CT server. De-base64 yourself before
+///   calling.
 ///
 /// * `pub_key`: use
-/// [openssl::pkey::PKey::public_key_from_der](openssl::pkey::PKey::public_key_from_der)
-/// to turn the key provided by google's ct log list into openssl key object.
+///   [openssl::pkey::PKey::public_key_from_der](openssl::pkey::PKey::public_key_from_der)
+///   to turn the key provided by google's ct log list into openssl key object.
 ///
 /// * `data`: the stuff to verify against. Server should have signed this.
 pub fn verify_dss(
@@ -32,6 +32,16 @@ pub fn verify_dss(
     pub_key: &PKey<openssl::pkey::Public>,
     data: &[u8],
 ) -> Result<(), Error> {
+    let (signature_algorithm, raw_signature) = parse_dss(dss)?;
+    verify_dss_raw(signature_algorithm, pub_key, raw_signature, data)
+}
+
+/// Split a TLS digitally-signed struct into its signature algorithm and the
+/// raw, ASN.1 encoded signature.
+///
+/// Used by [`verify_dss`], and when we need to store the signature in a
+/// [`SignedCertificateTimestamp`](crate::SignedCertificateTimestamp).
+pub fn parse_dss(dss: &[u8]) -> Result<(SignatureAlgorithm, &[u8]), Error> {
     // rustls crate contain code that parses this structure:
     // 	https://docs.rs/rustls/0.15.2/src/rustls/msgs/handshake.rs.html#1546
     // It shows that the struct begins with two bytes denoting the signature scheme, and
@@ -73,10 +83,38 @@ pub fn verify_dss(
         }
     };
 
-    verify_dss_raw(signature_algorithm, pub_key, rest, data)
+    Ok((signature_algorithm, rest))
 }
 
-use crate::internal::openssl_ffi::SignatureAlgorithm;
+/// The signature algorithms allowed in a digitally-signed struct by RFC 6962.
+#[derive(Debug, Ord, PartialOrd, Eq, PartialEq, Copy, Clone)]
+pub enum SignatureAlgorithm {
+    Sha256Rsa,
+    Sha256Ecdsa,
+}
+
+/// Build a TLS digitally-signed struct out of a raw, ASN.1 encoded signature. The reverse of
+/// [`parse_dss`].
+pub fn encode_dss(
+    signature_algorithm: SignatureAlgorithm,
+    raw_signature: &[u8],
+) -> Result<Vec<u8>, Error> {
+    if raw_signature.len() > u16::MAX as usize {
+        return Err(Error::InvalidArgument(format!(
+            "Signature too long. (len = {})",
+            raw_signature.len()
+        )));
+    }
+    let sig_type = match signature_algorithm {
+        SignatureAlgorithm::Sha256Ecdsa => SIGSCHEME_ECDSA_NISTP256_SHA256,
+        SignatureAlgorithm::Sha256Rsa => SIGSCHEME_RSA_PKCS1_SHA256,
+    };
+    let mut dss = Vec::with_capacity(4 + raw_signature.len());
+    dss.extend_from_slice(&sig_type.to_be_bytes());
+    dss.extend_from_slice(&(raw_signature.len() as u16).to_be_bytes());
+    dss.extend_from_slice(raw_signature);
+    Ok(dss)
+}
 
 /// Verifies a raw, ASN.1 encoded signature.
 pub fn verify_dss_raw(
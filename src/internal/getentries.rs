@@ -1,8 +1,14 @@
 use std::convert::TryFrom;
+use std::future::Future;
 use std::ops::Range;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::task::{Context, Poll};
 
 use async_stream::try_stream;
-use futures::Stream;
+use futures::{Stream, StreamExt, pin_mut};
+use log::debug;
 
 use crate::Error;
 use crate::jsons;
@@ -12,7 +18,18 @@ use super::get_json;
 
 /// Request leaf entries from the CT log. Does not verify if these entries are
 /// consistent with the tree or anything like that. Returns an iterator over the
-/// leaves.
+/// leaves and their index.
+///
+/// Each request asks for up to `batch_size` entries, starting from the first one
+/// not returned yet, since logs may return fewer entries than asked for. A response
+/// with more entries than asked for is an error. If the log returns no entry at
+/// all, the iterator ends early.
+///
+/// Unlike [`get_entries_parallel`], this does not learn the log's batch limit: every request
+/// asks for `batch_size` entries again. Since a request starts right after the last entry
+/// returned, a log that cuts responses at multiples of its cap answers it with a full aligned
+/// batch anyway, while a limit learned from a short response at an unaligned start would be
+/// too small and cost extra requests.
 ///
 /// After the first Err result, the iterator will not produce anything else.
 ///
@@ -22,25 +39,204 @@ pub fn get_entries<'a>(
     base_url: &'a reqwest::Url,
     range: Range<u64>,
     batch_size: u64,
-) -> impl Stream<Item = Result<Leaf, Error>> + 'a {
+) -> impl Stream<Item = Result<(u64, Leaf), Error>> + 'a {
     try_stream! {
+        let batch_size = batch_size.max(1);
         let mut next_index = range.start;
 
         while next_index < range.end {
-            let end = u64::min(next_index + batch_size, range.end);
-            let url = format!("ct/v1/get-entries?start={}&end={}", next_index, end - 1);
-
-            let entries: jsons::GetEntries = get_json(client, base_url, &url).await?;
-            if entries.entries.is_empty() {
+            let end = u64::min(next_index.saturating_add(batch_size), range.end);
+            let leaves = fetch_entries(client, base_url, next_index, end).await?;
+            if leaves.is_empty() {
                 break;
             }
+            next_index += leaves.len() as u64;
 
-            for entry in entries.entries {
-                let leaf = Leaf::try_from(&entry)?;
+            for leaf in leaves {
                 yield leaf;
             }
+        }
+    }
+}
 
-            next_index = end;
+/// The size of the batches planned by [`get_entries_parallel`].
+///
+/// Every log caps the number of entries it returns at once, and some also cut responses at
+/// multiples of that cap, so a response with fewer entries than asked for can be smaller than
+/// the cap, but never bigger. The limit is therefore the largest number of entries returned by
+/// such a short response so far, or `batch_size` before there is one. Responses with all the
+/// entries asked for were limited by the end of the request, so they say nothing about the cap.
+///
+/// Once the log has returned a short response, batches also end at multiples of the limit, to
+/// line up with logs that cut their responses there.
+struct BatchLimit {
+    batch_size: u64,
+    /// Largest number of entries in a short response, or 0 if there was none yet.
+    largest_short: AtomicU64,
+}
+
+impl BatchLimit {
+    fn new(batch_size: u64) -> Self {
+        BatchLimit {
+            batch_size: batch_size.max(1),
+            largest_short: AtomicU64::new(0),
+        }
+    }
+
+    /// End of the batch starting at `start`, but no later than `range_end`.
+    fn batch_end(&self, start: u64, range_end: u64) -> u64 {
+        let end = match self.largest_short.load(Ordering::Relaxed) {
+            0 => start.saturating_add(self.batch_size),
+            limit => {
+                let limit = limit.min(self.batch_size);
+                (start / limit + 1).saturating_mul(limit)
+            }
+        };
+        end.min(range_end)
+    }
+
+    /// The log returned `got` entries when asked for more.
+    fn record_short(&self, got: u64) {
+        self.largest_short.fetch_max(got, Ordering::Relaxed);
+    }
+}
+
+/// Request the entries `[start, end)` with one `get-entries` call, and parse them. The log may
+/// return fewer entries than that, but not more.
+async fn fetch_entries(
+    client: &reqwest::Client,
+    base_url: &reqwest::Url,
+    start: u64,
+    end: u64,
+) -> Result<Vec<(u64, Leaf)>, Error> {
+    let url = format!("ct/v1/get-entries?start={}&end={}", start, end - 1);
+    let entries: jsons::GetEntries = get_json(client, base_url, &url).await?;
+    if entries.entries.len() as u64 > end - start {
+        return Err(Error::MalformedResponseBody(format!(
+            "Asked for {} entries starting at {}, but got {}.",
+            end - start,
+            start,
+            entries.entries.len()
+        )));
+    }
+    (start..)
+        .zip(entries.entries.iter())
+        .map(|(index, entry)| Ok((index, Leaf::try_from(entry)?)))
+        .collect()
+}
+
+/// Request all the entries `[start, end)`, asking for the rest again as long as the log returns
+/// fewer entries than asked for, and tell `batch_limit` about these short responses. Stops early
+/// if the log returns nothing.
+async fn fetch_batch(
+    client: &reqwest::Client,
+    base_url: &reqwest::Url,
+    start: u64,
+    end: u64,
+    batch_limit: &BatchLimit,
+) -> Result<Vec<(u64, Leaf)>, Error> {
+    let mut leaves = Vec::with_capacity((end - start) as usize);
+    let mut next_index = start;
+    while next_index < end {
+        let got = fetch_entries(client, base_url, next_index, end).await?;
+        if got.is_empty() {
+            break;
+        }
+        let asked = end - next_index;
+        if (got.len() as u64) < asked {
+            debug!(
+                "get-entries at {} returned {} of {} entries",
+                next_index,
+                got.len(),
+                asked
+            );
+            batch_limit.record_short(got.len() as u64);
+        }
+        next_index += got.len() as u64;
+        leaves.extend(got);
+    }
+    Ok(leaves)
+}
+
+/// A spawned task which is aborted when dropped, so that requests in flight stop with the stream
+/// that started them.
+struct AbortOnDrop<T>(tokio::task::JoinHandle<T>);
+
+impl<T> Future for AbortOnDrop<T> {
+    type Output = Result<T, tokio::task::JoinError>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        Pin::new(&mut self.0).poll(cx)
+    }
+}
+
+impl<T> Drop for AbortOnDrop<T> {
+    fn drop(&mut self) {
+        self.0.abort();
+    }
+}
+
+/// Like [`get_entries`], but keep up to `parallelism` batches of `batch_size` entries in flight
+/// at once. Leaves are still returned in index order.
+///
+/// A batch the log does not return in one response is completed with more requests. Batches are
+/// planned as they are started, sized by the largest short response the log has returned so
+/// far, so that each batch usually takes a single request.
+///
+/// Batches are fetched and parsed in tasks spawned on the tokio runtime, so they progress while
+/// the caller is busy checking the leaves already returned. This uses memory for up to
+/// `parallelism` batches.
+///
+/// After the first Err result, the iterator will not produce anything else.
+pub fn get_entries_parallel(
+    client: &reqwest::Client,
+    base_url: &reqwest::Url,
+    range: Range<u64>,
+    batch_size: u64,
+    parallelism: usize,
+) -> impl Stream<Item = Result<(u64, Leaf), Error>> + 'static {
+    let client = client.clone();
+    let base_url = base_url.clone();
+    let range_end = range.end;
+    let batch_limit = Arc::new(BatchLimit::new(batch_size));
+    let batch_ranges = {
+        let batch_limit = batch_limit.clone();
+        futures::stream::unfold(range.start, move |start| {
+            let batch_limit = batch_limit.clone();
+            async move {
+                if start >= range_end {
+                    return None;
+                }
+                let end = batch_limit.batch_end(start, range_end);
+                Some(((start, end), end))
+            }
+        })
+    };
+    let batches = batch_ranges
+        .map(move |(start, end)| {
+            let client = client.clone();
+            let base_url = base_url.clone();
+            let batch_limit = batch_limit.clone();
+            AbortOnDrop(tokio::spawn(async move {
+                let leaves = fetch_batch(&client, &base_url, start, end, &batch_limit).await?;
+                Ok::<_, Error>((end, leaves))
+            }))
+        })
+        .buffered(parallelism.max(1));
+    try_stream! {
+        pin_mut!(batches);
+        while let Some(batch) = batches.next().await {
+            let (end, leaves) = batch
+                .map_err(|e| Error::Unknown(format!("get-entries task failed: {}", e)))??;
+            let complete = leaves.last().is_some_and(|(index, _)| index + 1 == end);
+
+            for leaf in leaves {
+                yield leaf;
+            }
+            if !complete {
+                // Don't skip to the next batch if the log stopped returning entries.
+                break;
+            }
         }
     }
 }
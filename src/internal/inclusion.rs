@@ -1,7 +1,8 @@
 use crate::Error;
-use crate::internal::get_json;
-use crate::jsons::AuditProof;
+use crate::internal::{Leaf, get_json};
+use crate::jsons::{AuditProof, EntryAndProof};
 use crate::utils::{combine_tree_hash, u8_to_hex};
+use base64::prelude::{BASE64_STANDARD, Engine as _};
 use std::convert::TryInto;
 use std::ops::Range;
 
@@ -100,7 +101,7 @@ pub async fn fetch_inclusion_proof(
         &format!(
             "ct/v1/get-proof-by-hash?{}",
             serde_urlencoded::to_string(&[
-                ("hash", base64::encode(leaf_hash)),
+                ("hash", BASE64_STANDARD.encode(leaf_hash)),
                 ("tree_size", tree_size.to_string())
             ])
             .map_err(|e| Error::Unknown(format!("{}", e)))?
@@ -127,9 +128,18 @@ pub async fn fetch_inclusion_proof(
             ),
         });
     }
-    let mut provided_proof: Vec<[u8; 32]> = Vec::with_capacity(proof_parts.len());
-    for i in 0..proof_parts.len() {
-        let hash = base64::decode(&json.audit_path[i]).map_err(|e| {
+    let provided_proof = decode_audit_path(&json.audit_path)?;
+    let got_hash = hash_inclusion_proof(&proof_parts, &provided_proof, leaf_hash, leaf_index);
+    Ok(FetchInclusionProofResult {
+        calculated_tree_hash: got_hash,
+        leaf_index,
+    })
+}
+
+fn decode_audit_path(audit_path: &[String]) -> Result<Vec<[u8; 32]>, Error> {
+    let mut provided_proof: Vec<[u8; 32]> = Vec::with_capacity(audit_path.len());
+    for b64 in audit_path.iter() {
+        let hash = BASE64_STANDARD.decode(b64).map_err(|e| {
             Error::MalformedResponseBody(format!("Unable to decode base64 in proof: {}", e))
         })?;
         if hash.len() != 32 {
@@ -139,11 +149,70 @@ pub async fn fetch_inclusion_proof(
         }
         provided_proof.push(hash[..].try_into().unwrap());
     }
-    let got_hash = hash_inclusion_proof(&proof_parts, &provided_proof, leaf_hash, leaf_index);
-    Ok(FetchInclusionProofResult {
-        calculated_tree_hash: got_hash,
-        leaf_index,
-    })
+    Ok(provided_proof)
+}
+
+/// Fetch the leaf at `leaf_index` together with its inclusion proof with `get-entry-and-proof`,
+/// and check that the proof convinces us that the leaf is in the tree with hash `tree_hash` and
+/// size `tree_size`. On success, return the parsed leaf.
+///
+/// The leaf hash is computed by us from the returned leaf data, so a server can't get away with
+/// proving a different leaf.
+pub async fn check_entry_and_proof(
+    client: &reqwest::Client,
+    base_url: &reqwest::Url,
+    tree_size: u64,
+    tree_hash: &[u8; 32],
+    leaf_index: u64,
+) -> Result<Leaf, Error> {
+    if leaf_index >= tree_size {
+        return Err(Error::InvalidArgument(format!(
+            "leaf_index {} is not in a tree of size {}.",
+            leaf_index, tree_size
+        )));
+    }
+    let json: EntryAndProof = get_json(
+        client,
+        base_url,
+        &format!(
+            "ct/v1/get-entry-and-proof?leaf_index={}&tree_size={}",
+            leaf_index, tree_size
+        ),
+    )
+    .await?;
+    let leaf_input = BASE64_STANDARD
+        .decode(&json.leaf_input)
+        .map_err(|e| Error::MalformedResponseBody(format!("base64 decode leaf_input: {}", &e)))?;
+    let extra_data = BASE64_STANDARD
+        .decode(&json.extra_data)
+        .map_err(|e| Error::MalformedResponseBody(format!("base64 decode extra_data: {}", &e)))?;
+    let leaf = Leaf::from_raw(&leaf_input, &extra_data)?;
+    let proof_parts = inclusion_proof_parts(tree_size, leaf_index);
+    if proof_parts.len() != json.audit_path.len() {
+        return Err(Error::InvalidInclusionProof {
+            tree_size,
+            leaf_index,
+            desc: format!(
+                "Expected proof with {} parts, got {}.",
+                proof_parts.len(),
+                json.audit_path.len()
+            ),
+        });
+    }
+    let provided_proof = decode_audit_path(&json.audit_path)?;
+    let got_hash = hash_inclusion_proof(&proof_parts, &provided_proof, &leaf.hash, leaf_index);
+    if &got_hash != tree_hash {
+        return Err(Error::InvalidInclusionProof {
+            tree_size,
+            leaf_index,
+            desc: format!(
+                "Expected the proof to yield a tree hash of {}, but instead got {}.",
+                u8_to_hex(tree_hash),
+                u8_to_hex(&got_hash)
+            ),
+        });
+    }
+    Ok(leaf)
 }
 
 /// Attempt to derive the root hash from the server provided inclusion proof and our calculated proof_parts.
@@ -1,6 +1,8 @@
 use std::convert::{TryFrom, TryInto};
 use std::fmt;
 
+use base64::prelude::{BASE64_STANDARD, Engine as _};
+
 use crate::Error;
 use crate::jsons;
 use crate::utils;
@@ -9,6 +11,7 @@ use crate::utils;
 ///
 /// Parse a JSON get-entries response to this with
 /// `TryFrom<&jsons::LeafEntry>::try_from`.
+#[derive(PartialEq, Eq)]
 pub struct Leaf {
     /// What they call "leaf hash".
     pub hash: [u8; 32],
@@ -226,10 +229,10 @@ impl Leaf {
 impl TryFrom<&jsons::LeafEntry> for Leaf {
     type Error = Error;
     fn try_from(le: &jsons::LeafEntry) -> Result<Self, Error> {
-        let leaf_input = base64::decode(&le.leaf_input).map_err(|e| {
+        let leaf_input = BASE64_STANDARD.decode(&le.leaf_input).map_err(|e| {
             Error::MalformedResponseBody(format!("base64 decode leaf_input: {}", &e))
         })?;
-        let extra_data = base64::decode(&le.extra_data).map_err(|e| {
+        let extra_data = BASE64_STANDARD.decode(&le.extra_data).map_err(|e| {
             Error::MalformedResponseBody(format!("base64 decode extra_data: {}", &e))
         })?;
         Leaf::from_raw(&leaf_input, &extra_data)
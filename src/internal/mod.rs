@@ -4,22 +4,33 @@
 
 use std::convert::TryInto;
 
+use base64::prelude::{BASE64_STANDARD, Engine as _};
+
 use log::{debug, trace};
 use openssl::pkey::PKey;
 
 use crate::{Error, SignedTreeHead, jsons, utils};
+pub use compact_range::*;
 pub use consistency::*;
 pub use digitally_signed_struct::*;
 pub use getentries::*;
 pub use inclusion::*;
 pub use leaf::*;
+pub use roots::*;
+pub use sct_list::*;
+pub use submission::*;
 
+mod compact_range;
 mod consistency;
 mod digitally_signed_struct;
 mod getentries;
 mod inclusion;
 mod leaf;
 pub mod openssl_ffi;
+mod roots;
+mod sct_list;
+mod submission;
+pub mod tiles;
 
 /// Construct a new [`reqwest::Client`] to be used with the
 /// functions in this module. You don't necessary need to use this.
@@ -72,6 +83,54 @@ pub async fn get_json<J: serde::de::DeserializeOwned>(
     Ok(json)
 }
 
+/// Perform a GET request and return the raw response body.
+pub async fn get_bytes(
+    client: &reqwest::Client,
+    base_url: &reqwest::Url,
+    path: &str,
+) -> Result<Vec<u8>, Error> {
+    let url = base_url.join(path).unwrap();
+    let url_str = url.as_str().to_owned();
+    let response = client.get(url).send().await.map_err(Error::NetIO)?;
+    if response.status().as_u16() != 200 {
+        debug!("GET {} -> {}", &url_str, response.status());
+        return Err(Error::InvalidResponseStatus(response.status()));
+    }
+    let response = response.bytes().await.map_err(Error::NetIO)?;
+    trace!("GET {} -> {} bytes", &url_str, response.len());
+    Ok(response.to_vec())
+}
+
+/// Perform a POST request with a JSON body and parse the result as a JSON.
+pub async fn post_json<B: serde::Serialize, J: serde::de::DeserializeOwned>(
+    client: &reqwest::Client,
+    base_url: &reqwest::Url,
+    path: &str,
+    body: &B,
+) -> Result<J, Error> {
+    let url = base_url.join(path).unwrap();
+    let url_str = url.as_str().to_owned();
+    let response = client
+        .post(url)
+        .json(body)
+        .send()
+        .await
+        .map_err(Error::NetIO)?;
+    if response.status().as_u16() != 200 {
+        debug!("POST {} -> {}", &url_str, response.status());
+        return Err(Error::InvalidResponseStatus(response.status()));
+    }
+    let response = response.text().await.map_err(Error::NetIO)?;
+    trace!("POST {} -> {:?}", &url_str, &response);
+    let json = serde_json::from_str(&response).map_err(|e| {
+        Error::MalformedResponseBody(format!(
+            "Unable to decode JSON: {} (response is {:?})",
+            &e, &response
+        ))
+    })?;
+    Ok(json)
+}
+
 /// Check, verify and return the latest tree head from the CT log at
 /// `base_url`.
 ///
@@ -84,31 +143,35 @@ pub async fn get_json<J: serde::de::DeserializeOwned>(
 /// # Params
 ///
 /// * `client`: A [`reqwest::Client`](reqwest::Client) instance. See
-/// [`CTClient::get_reqwest_client`](crate::CTClient::get_reqwest_client)
+///   [`CTClient::get_reqwest_client`](crate::CTClient::get_reqwest_client)
 pub async fn check_tree_head(
     client: &reqwest::Client,
     base_url: &reqwest::Url,
     pub_key: &PKey<openssl::pkey::Public>,
 ) -> Result<SignedTreeHead, Error> {
     let response: jsons::STH = get_json(client, base_url, "ct/v1/get-sth").await?;
-    let root_hash = base64::decode(&response.sha256_root_hash).map_err(|e| {
-        Error::MalformedResponseBody(format!(
-            "base64 decode failure on root sha256: {} (trying to decode {:?})",
-            &e, &response.sha256_root_hash
-        ))
-    })?;
+    let root_hash = BASE64_STANDARD
+        .decode(&response.sha256_root_hash)
+        .map_err(|e| {
+            Error::MalformedResponseBody(format!(
+                "base64 decode failure on root sha256: {} (trying to decode {:?})",
+                &e, &response.sha256_root_hash
+            ))
+        })?;
     if root_hash.len() != 32 {
         return Err(Error::MalformedResponseBody(format!(
             "Invalid server response: sha256_root_hash should have length of 32. Server response is {:?}",
             &response
         )));
     }
-    let dss = base64::decode(&response.tree_head_signature).map_err(|e| {
-        Error::MalformedResponseBody(format!(
-            "base64 decode failure on signature: {} (trying to decode {:?})",
-            &e, &response.tree_head_signature
-        ))
-    })?;
+    let dss = BASE64_STANDARD
+        .decode(&response.tree_head_signature)
+        .map_err(|e| {
+            Error::MalformedResponseBody(format!(
+                "base64 decode failure on signature: {} (trying to decode {:?})",
+                &e, &response.tree_head_signature
+            ))
+        })?;
     let sth = SignedTreeHead {
         tree_size: response.tree_size,
         timestamp: response.timestamp,
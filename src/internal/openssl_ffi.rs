@@ -5,21 +5,17 @@ use std::ptr::null_mut;
 
 use foreign_types::{ForeignType, ForeignTypeRef};
 use openssl::error::ErrorStack;
-use openssl::stack::{Stack, Stackable};
 use openssl::x509::X509Ref;
 use openssl_sys::ASN1_OBJECT;
 
 use foreign::*;
 
+pub use crate::internal::{RawSct, SCTVersion, SignatureAlgorithm};
+
 /// Because `openssl_sys` crate is incomplete.
 #[allow(non_camel_case_types)]
 pub mod foreign {
-    pub enum SCT_LIST {}
-    pub enum SCT {}
-
-    pub type sct_version_t = i32;
-    pub const SCT_VERSION_NOT_SET: sct_version_t = -1;
-    pub const SCT_VERSION_V1: sct_version_t = 0;
+    pub enum OCSP_SINGLERESP {}
 
     unsafe extern "C" {
         pub fn i2d_re_X509_tbs(
@@ -61,30 +57,24 @@ pub mod foreign {
             data: *mut openssl_sys::ASN1_OCTET_STRING,
         ) -> ::std::os::raw::c_int;
 
-        pub fn d2i_SCT_LIST(
-            a: *mut *mut SCT_LIST,
-            pp: *mut *const ::std::os::raw::c_uchar,
-            len: ::std::os::raw::c_long,
-        ) -> *mut SCT_LIST;
-
-        pub fn SCT_LIST_free(a: *mut SCT_LIST);
-
-        pub fn SCT_get_version(sct: *const SCT) -> sct_version_t;
-        pub fn SCT_get0_log_id(
-            sct: *const SCT,
-            log_id: *mut *mut ::std::os::raw::c_uchar,
-        ) -> ::std::os::raw::c_ulong;
-        pub fn SCT_get_timestamp(sct: *const SCT) -> u64;
-        pub fn SCT_get0_extensions(
-            sct: *const SCT,
-            ext: *mut *mut ::std::os::raw::c_uchar,
-        ) -> ::std::os::raw::c_ulong;
-        pub fn SCT_get_signature_nid(sct: *const SCT) -> ::std::os::raw::c_int;
-        pub fn SCT_get0_signature(
-            sct: *const SCT,
-            sig: *mut *mut ::std::os::raw::c_uchar,
-        ) -> ::std::os::raw::c_ulong;
-        pub fn SCT_free(sct: *mut SCT);
+        pub fn OCSP_resp_find(
+            bs: *mut openssl_sys::OCSP_BASICRESP,
+            id: *mut openssl_sys::OCSP_CERTID,
+            last: ::std::os::raw::c_int,
+        ) -> ::std::os::raw::c_int;
+        pub fn OCSP_resp_get0(
+            bs: *mut openssl_sys::OCSP_BASICRESP,
+            idx: ::std::os::raw::c_int,
+        ) -> *mut OCSP_SINGLERESP;
+        pub fn OCSP_SINGLERESP_get_ext_by_OBJ(
+            x: *mut OCSP_SINGLERESP,
+            obj: *const openssl_sys::ASN1_OBJECT,
+            lastpos: ::std::os::raw::c_int,
+        ) -> ::std::os::raw::c_int;
+        pub fn OCSP_SINGLERESP_get_ext(
+            x: *mut OCSP_SINGLERESP,
+            loc: ::std::os::raw::c_int,
+        ) -> *mut openssl_sys::X509_EXTENSION;
 
         pub fn X509_set_issuer_name(
             x: *mut openssl_sys::X509,
@@ -101,22 +91,6 @@ pub mod foreign {
     }
 }
 
-foreign_types::foreign_type! {
-  type CType = foreign::SCT;
-  fn drop = foreign::SCT_free;
-  /// An owned reference to a openssl `SCT` struct.
-  pub struct Sct;
-  /// A reference to a openssl `SCT` struct.
-  pub struct SctRef;
-}
-
-impl Stackable for Sct {
-    type StackType = foreign::SCT_LIST;
-}
-
-/// An owned `STACK_OF(SCT)`.
-pub type SctList = Stack<Sct>;
-
 pub fn x509_clone<R: AsRef<X509Ref>>(src: &R) -> Result<openssl::x509::X509, ErrorStack> {
     unsafe {
         let cloned_ptr = X509_dup(src.as_ref().as_ptr());
@@ -162,8 +136,11 @@ lazy_static! {
         unsafe { oid_to_obj("1.3.6.1.4.1.11129.2.4.3\0") };
     static ref SCT_LIST_ASN1_OBJECT: WrappedObjPointer =
         unsafe { oid_to_obj("1.3.6.1.4.1.11129.2.4.2\0") };
+    static ref OCSP_SCT_LIST_ASN1_OBJECT: WrappedObjPointer =
+        unsafe { oid_to_obj("1.3.6.1.4.1.11129.2.4.5\0") };
     static ref AUTHORITY_KEY_IDENTIFIER: WrappedObjPointer = unsafe { oid_to_obj("2.5.29.35\0") };
     static ref SUBJECT_KEY_IDENTIFIER: WrappedObjPointer = unsafe { oid_to_obj("2.5.29.14\0") };
+    static ref EXTENDED_KEY_USAGE: WrappedObjPointer = unsafe { oid_to_obj("2.5.29.37\0") };
 }
 
 unsafe fn x509_remove_extension_by_obj(
@@ -277,84 +254,48 @@ pub fn x509_to_tbs<R: AsRef<X509Ref>>(cert: &R) -> Result<Vec<u8>, ErrorStack> {
     }
 }
 
-pub fn sct_list_from_x509<R: AsRef<X509Ref>>(cert: &R) -> Result<Option<SctList>, crate::Error> {
+/// Parse the SCT list extension of `cert` (embedded SCTs), if there is one.
+pub fn sct_list_from_x509<R: AsRef<X509Ref>>(
+    cert: &R,
+) -> Result<Option<Vec<RawSct>>, crate::Error> {
     let data = x509_get_ext_data(cert.as_ref(), &SCT_LIST_ASN1_OBJECT)
         .map_err(|e| crate::Error::BadCertificate(format!("{}", e)))?;
-    if data.is_none() {
-        return Ok(None);
-    }
-    let data = data.unwrap();
-    if data.is_empty() {
-        return Ok(None);
+    match data {
+        None => Ok(None),
+        Some(data) => crate::internal::parse_sct_list_extension(data),
     }
-    let mut pp = data.as_ptr();
+}
+
+/// Find the single response about `cert_id` in `resp`, and parse the SCT list in its
+/// `singleExtensions` (RFC 6962, section 3.3), if there is one.
+pub fn sct_list_from_ocsp(
+    resp: &openssl::ocsp::OcspBasicResponseRef,
+    cert_id: &openssl::ocsp::OcspCertIdRef,
+) -> Result<Option<Vec<RawSct>>, crate::Error> {
+    use crate::Error;
     unsafe {
-        let res = d2i_SCT_LIST(
-            std::ptr::null_mut(),
-            &mut pp as *mut _,
-            i64::try_from(data.len()).unwrap(),
-        );
-        if res.is_null() {
-            return Err(crate::Error::BadSct(format!("{}", ErrorStack::get())));
-        }
-        if pp != data.as_ptr().add(data.len()) {
-            return Err(crate::Error::BadSct(
-                "SCT extension data not fully consumed.".to_owned(),
+        let idx = OCSP_resp_find(resp.as_ptr(), cert_id.as_ptr(), -1);
+        if idx < 0 {
+            return Err(Error::InvalidArgument(
+                "The OCSP response is not about this certificate.".to_owned(),
             ));
         }
-        Ok(Some(SctList::from_ptr(res)))
-    }
-}
-
-#[derive(Debug, Eq, PartialEq, Copy, Clone)]
-pub enum SCTVersion {
-    V1,
-}
-#[derive(Debug, Ord, PartialOrd, Eq, PartialEq, Copy, Clone)]
-pub enum SignatureAlgorithm {
-    Sha256Rsa,
-    Sha256Ecdsa,
-}
-
-macro_rules! impl_get_data_fn {
-    ($fnname:ident, $fn:expr) => {
-        pub fn $fnname(&self) -> &[u8] {
-            unsafe {
-                let mut ptr: *mut u8 = std::ptr::null_mut();
-                let size = $fn(self.as_ptr(), &mut ptr as *mut _);
-                &*std::ptr::slice_from_raw_parts(ptr, size as usize)
-            }
+        let single = OCSP_resp_get0(resp.as_ptr(), idx);
+        if single.is_null() {
+            return Err(Error::Unknown(ErrorStack::get().to_string()));
         }
-    };
-}
-
-impl SctRef {
-    pub fn version(&self) -> Option<SCTVersion> {
-        let raw_version = unsafe { SCT_get_version(self.as_ptr()) };
-        match raw_version {
-            SCT_VERSION_V1 => Some(SCTVersion::V1),
-            _ => None,
+        let extpos = OCSP_SINGLERESP_get_ext_by_OBJ(single, OCSP_SCT_LIST_ASN1_OBJECT.0, -1);
+        if extpos == -1 {
+            return Ok(None);
         }
-    }
-
-    impl_get_data_fn!(log_id, SCT_get0_log_id);
-
-    pub fn timestamp(&self) -> u64 {
-        unsafe { SCT_get_timestamp(self.as_ptr()) }
-    }
-
-    impl_get_data_fn!(extensions, SCT_get0_extensions);
-
-    pub fn signature_algorithm(&self) -> Option<SignatureAlgorithm> {
-        let nid = unsafe { SCT_get_signature_nid(self.as_ptr()) };
-        match nid {
-            668 => Some(SignatureAlgorithm::Sha256Rsa),
-            794 => Some(SignatureAlgorithm::Sha256Ecdsa),
-            _ => None,
+        let ext = OCSP_SINGLERESP_get_ext(single, extpos);
+        if ext.is_null() {
+            return Err(Error::Unknown(ErrorStack::get().to_string()));
         }
+        crate::internal::parse_sct_list_extension(asn1_string_to_bytes(
+            X509_EXTENSION_get_data(ext) as *mut _,
+        ))
     }
-
-    impl_get_data_fn!(raw_signature, SCT_get0_signature);
 }
 
 /// Set the issuer name of `dst` to be the subject name of `src`, and also set the authorityKeyIdentifier of a to the
@@ -387,3 +328,67 @@ pub fn x509_make_a_looks_like_issued_by_b(
         Ok(())
     }
 }
+
+/// DER encoding of the Certificate Transparency precertificate signing key purpose,
+/// 1.3.6.1.4.1.11129.2.4.4 (RFC 6962, section 3.1).
+const PRECERT_SIGNING_EKU_DER: &[u8] = &[
+    0x06, 0x0a, 0x2b, 0x06, 0x01, 0x04, 0x01, 0xd6, 0x79, 0x02, 0x04, 0x04,
+];
+
+/// Check whether `cert` is a dedicated precertificate signing certificate, i.e. has the
+/// Certificate Transparency extended key usage.
+///
+/// Such a certificate signs precertificates on behalf of the "true" issuer, which is the next
+/// certificate in the chain.
+pub fn x509_is_precert_signing_cert(cert: &X509Ref) -> Result<bool, crate::Error> {
+    use crate::Error;
+    let data = x509_get_ext_data(cert, &EXTENDED_KEY_USAGE)
+        .map_err(|e| Error::BadCertificate(format!("{}", e)))?;
+    let data = match data {
+        Some(d) => d,
+        None => return Ok(false),
+    };
+    // ExtKeyUsageSyntax ::= SEQUENCE SIZE (1..MAX) OF KeyPurposeId
+    fn err_invalid() -> Result<bool, Error> {
+        Err(Error::BadCertificate(
+            "Invalid extendedKeyUsage extension.".to_owned(),
+        ))
+    }
+    if data.len() < 2 || data[0] != 0x30 {
+        return err_invalid();
+    }
+    let mut rest = match der_read_length(&data[1..]) {
+        Some((len, rest)) if rest.len() == len => rest,
+        _ => return err_invalid(),
+    };
+    while !rest.is_empty() {
+        let tag = rest[0];
+        let (len, after_len) = match der_read_length(&rest[1..]) {
+            Some((len, after_len)) if after_len.len() >= len => (len, after_len),
+            _ => return err_invalid(),
+        };
+        let header_len = rest.len() - after_len.len();
+        if tag == 0x06 && rest[..header_len + len] == *PRECERT_SIGNING_EKU_DER {
+            return Ok(true);
+        }
+        rest = &after_len[len..];
+    }
+    Ok(false)
+}
+
+/// Read a DER length, returning it together with what follows.
+pub(crate) fn der_read_length(data: &[u8]) -> Option<(usize, &[u8])> {
+    let first = *data.first()?;
+    if first & 0x80 == 0 {
+        return Some((first as usize, &data[1..]));
+    }
+    let nb_bytes = (first & 0x7f) as usize;
+    if nb_bytes == 0 || nb_bytes > 4 || data.len() < 1 + nb_bytes {
+        return None;
+    }
+    let mut len = 0usize;
+    for b in &data[1..1 + nb_bytes] {
+        len = (len << 8) | *b as usize;
+    }
+    Some((len, &data[1 + nb_bytes..]))
+}
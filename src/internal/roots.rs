@@ -0,0 +1,152 @@
+use std::collections::BTreeMap;
+
+use base64::prelude::{BASE64_STANDARD, Engine as _};
+use openssl::x509::{X509, X509Ref};
+
+use crate::internal::get_json;
+use crate::{Error, jsons, utils};
+
+/// The set of root certificates a log accepts, as returned by `get-roots`.
+#[derive(Debug, Clone, Default)]
+pub struct AcceptedRoots {
+    /// Keyed by the sha256 of the DER certificate.
+    roots: BTreeMap<[u8; 32], X509>,
+}
+
+/// Difference between two [`AcceptedRoots`].
+#[derive(Debug, Clone, Default)]
+pub struct RootsChange {
+    pub added: Vec<X509>,
+    pub removed: Vec<X509>,
+}
+
+impl RootsChange {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty()
+    }
+}
+
+impl AcceptedRoots {
+    /// Construct a root set from DER certificates.
+    pub fn from_der_certs<I, D>(certs: I) -> Result<Self, Error>
+    where
+        I: IntoIterator<Item = D>,
+        D: AsRef<[u8]>,
+    {
+        let mut roots = BTreeMap::new();
+        for der in certs {
+            let der = der.as_ref();
+            let cert = X509::from_der(der)
+                .map_err(|e| Error::BadCertificate(format!("While decoding root: {}", e)))?;
+            roots.insert(utils::sha256(der), cert);
+        }
+        Ok(AcceptedRoots { roots })
+    }
+
+    pub fn len(&self) -> usize {
+        self.roots.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.roots.is_empty()
+    }
+
+    /// Iterate over the root certificates, ordered by their sha256 fingerprint.
+    pub fn iter(&self) -> impl Iterator<Item = &X509> {
+        self.roots.values()
+    }
+
+    /// Whether `cert` is one of the accepted roots.
+    pub fn contains(&self, cert: &X509Ref) -> bool {
+        match cert.to_der() {
+            Ok(der) => self.roots.contains_key(&utils::sha256(&der)),
+            Err(_) => false,
+        }
+    }
+
+    /// Whether `cert` is either an accepted root, or is directly signed by one.
+    ///
+    /// Logs are allowed to leave the root out of a chain, so the last certificate in a
+    /// [`Leaf::x509_chain`](crate::internal::Leaf::x509_chain) might be an intermediate.
+    pub fn is_accepted_chain_end(&self, cert: &X509Ref) -> Result<bool, Error> {
+        if self.contains(cert) {
+            return Ok(true);
+        }
+        let issuer_name = cert
+            .issuer_name()
+            .to_der()
+            .map_err(|e| Error::BadCertificate(format!("While encoding issuer name: {}", e)))?;
+        for root in self.roots.values() {
+            if root.subject_name().to_der().ok().as_ref() != Some(&issuer_name) {
+                continue;
+            }
+            let root_key = root.public_key().map_err(|e| {
+                Error::BadCertificate(format!("Can't get public key from root: {}", e))
+            })?;
+            if cert.verify(&root_key).unwrap_or(false) {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    /// Return the roots that are in `newer` but not in `self`, and the other way round.
+    pub fn diff(&self, newer: &AcceptedRoots) -> RootsChange {
+        RootsChange {
+            added: newer
+                .roots
+                .iter()
+                .filter(|(k, _)| !self.roots.contains_key(*k))
+                .map(|(_, v)| v.clone())
+                .collect(),
+            removed: self
+                .roots
+                .iter()
+                .filter(|(k, _)| !newer.roots.contains_key(*k))
+                .map(|(_, v)| v.clone())
+                .collect(),
+        }
+    }
+}
+
+/// Fetch the list of root certificates the log at `base_url` accepts.
+pub async fn get_roots(
+    client: &reqwest::Client,
+    base_url: &reqwest::Url,
+) -> Result<AcceptedRoots, Error> {
+    let response: jsons::GetRoots = get_json(client, base_url, "ct/v1/get-roots").await?;
+    let mut ders = Vec::with_capacity(response.certificates.len());
+    for b64 in response.certificates.iter() {
+        ders.push(BASE64_STANDARD.decode(b64).map_err(|e| {
+            Error::MalformedResponseBody(format!("base64 decode failure on root: {}", &e))
+        })?);
+    }
+    AcceptedRoots::from_der_certs(ders).map_err(|e| match e {
+        Error::BadCertificate(desc) => Error::MalformedResponseBody(desc),
+        other => other,
+    })
+}
+
+#[test]
+fn accepted_roots_test() {
+    let chain =
+        X509::stack_from_pem(include_bytes!("../test_data/precert-signing-ca.pem")).unwrap();
+    let root_der = chain[3].to_der().unwrap();
+    let inter_der = chain[2].to_der().unwrap();
+    let roots = AcceptedRoots::from_der_certs([&root_der]).unwrap();
+    assert!(roots.contains(&chain[3]));
+    assert!(!roots.contains(&chain[2]));
+    assert!(roots.is_accepted_chain_end(&chain[3]).unwrap());
+    assert!(roots.is_accepted_chain_end(&chain[2]).unwrap());
+    assert!(!roots.is_accepted_chain_end(&chain[1]).unwrap());
+
+    let newer = AcceptedRoots::from_der_certs([&inter_der, &root_der]).unwrap();
+    let change = roots.diff(&newer);
+    assert_eq!(change.added.len(), 1);
+    assert_eq!(change.added[0].to_der().unwrap(), inter_der);
+    assert!(change.removed.is_empty());
+    let change = newer.diff(&roots);
+    assert!(change.added.is_empty());
+    assert_eq!(change.removed.len(), 1);
+    assert!(roots.diff(&roots).is_empty());
+}
@@ -0,0 +1,270 @@
+//! Encoding and decoding of `SignedCertificateTimestampList` (RFC 6962, section 3.3).
+//!
+//! ```text
+//! opaque SerializedSCT<1..2^16-1>;
+//! struct {
+//!     SerializedSCT sct_list <1..2^16-1>;
+//! } SignedCertificateTimestampList;
+//! ```
+
+use std::convert::TryInto;
+
+use crate::Error;
+use crate::internal::openssl_ffi::der_read_length;
+use crate::internal::{SignatureAlgorithm, encode_dss, parse_dss};
+
+/// Version of a SCT. RFC 6962 only defines v1.
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+pub enum SCTVersion {
+    V1,
+}
+
+/// A SCT as serialized in a `SignedCertificateTimestampList`, which does not include the entry
+/// it signs. See [`SignedCertificateTimestamp`](crate::SignedCertificateTimestamp) for one with
+/// the entry.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RawSct {
+    pub version: SCTVersion,
+    pub log_id: [u8; 32],
+    pub timestamp: u64,
+    pub extensions: Vec<u8>,
+    pub signature_algorithm: SignatureAlgorithm,
+    /// Raw signature encoded in ASN.1
+    pub raw_signature: Vec<u8>,
+}
+
+fn err_trunc(what: &str) -> Error {
+    Error::BadSct(format!("Truncated {}.", what))
+}
+
+/// Split off a big-endian u16 length-prefixed field.
+fn read_u16_prefixed<'a>(input: &mut &'a [u8], what: &str) -> Result<&'a [u8], Error> {
+    if input.len() < 2 {
+        return Err(err_trunc(what));
+    }
+    let len = u16::from_be_bytes([input[0], input[1]]) as usize;
+    if input.len() < 2 + len {
+        return Err(err_trunc(what));
+    }
+    let data = &input[2..2 + len];
+    *input = &input[2 + len..];
+    Ok(data)
+}
+
+fn write_u16_prefixed(out: &mut Vec<u8>, data: &[u8], what: &str) -> Result<(), Error> {
+    if data.len() > u16::MAX as usize {
+        return Err(Error::InvalidArgument(format!(
+            "{} too long. (len = {})",
+            what,
+            data.len()
+        )));
+    }
+    out.extend_from_slice(&(data.len() as u16).to_be_bytes());
+    out.extend_from_slice(data);
+    Ok(())
+}
+
+impl RawSct {
+    /// Parse a single serialized SCT. All of `bytes` must be used.
+    pub fn parse(bytes: &[u8]) -> Result<RawSct, Error> {
+        // struct {
+        //     Version sct_version;
+        //     LogID id;
+        //     uint64 timestamp;
+        //     CtExtensions extensions;
+        //     digitally-signed struct { ... };
+        // } SignedCertificateTimestamp;
+        let mut input = bytes;
+        if input.len() < 1 + 32 + 8 {
+            return Err(err_trunc("SCT"));
+        }
+        if input[0] != 0 {
+            return Err(Error::BadSct(format!("Invalid SCT version {}.", input[0])));
+        }
+        let log_id: [u8; 32] = input[1..33].try_into().unwrap();
+        let timestamp = u64::from_be_bytes(input[33..41].try_into().unwrap());
+        input = &input[41..];
+        let extensions = read_u16_prefixed(&mut input, "SCT extensions")?.to_vec();
+        let (signature_algorithm, raw_signature) =
+            parse_dss(input).map_err(|e| Error::BadSct(format!("{}", e)))?;
+        Ok(RawSct {
+            version: SCTVersion::V1,
+            log_id,
+            timestamp,
+            extensions,
+            signature_algorithm,
+            raw_signature: raw_signature.to_vec(),
+        })
+    }
+
+    /// Serialize this SCT, without the length prefix it has in a list.
+    pub fn to_bytes(&self) -> Result<Vec<u8>, Error> {
+        let mut out = Vec::with_capacity(1 + 32 + 8 + 2 + self.extensions.len() + 4 + 72);
+        out.push(match self.version {
+            SCTVersion::V1 => 0u8,
+        });
+        out.extend_from_slice(&self.log_id);
+        out.extend_from_slice(&self.timestamp.to_be_bytes());
+        write_u16_prefixed(&mut out, &self.extensions, "SCT extensions")?;
+        out.extend_from_slice(&encode_dss(self.signature_algorithm, &self.raw_signature)?);
+        Ok(out)
+    }
+}
+
+/// Parse a TLS-encoded `SignedCertificateTimestampList`, as sent in the
+/// `signed_certificate_timestamp` TLS extension.
+///
+/// Returns [`Error::BadSct`] on malformed input, including an empty list.
+pub fn parse_sct_list(bytes: &[u8]) -> Result<Vec<RawSct>, Error> {
+    let mut input = bytes;
+    let mut list = read_u16_prefixed(&mut input, "SCT list")?;
+    if !input.is_empty() {
+        return Err(Error::BadSct("Trailing data after SCT list.".to_owned()));
+    }
+    if list.is_empty() {
+        return Err(Error::BadSct("Empty SCT list.".to_owned()));
+    }
+    let mut scts = Vec::new();
+    while !list.is_empty() {
+        let sct = read_u16_prefixed(&mut list, "SCT")?;
+        if sct.is_empty() {
+            return Err(Error::BadSct("Empty SCT in list.".to_owned()));
+        }
+        scts.push(RawSct::parse(sct)?);
+    }
+    Ok(scts)
+}
+
+/// Encode `scts` as a TLS `SignedCertificateTimestampList`. The reverse of [`parse_sct_list`].
+pub fn encode_sct_list(scts: &[RawSct]) -> Result<Vec<u8>, Error> {
+    if scts.is_empty() {
+        return Err(Error::InvalidArgument("Empty SCT list.".to_owned()));
+    }
+    let mut list = Vec::new();
+    for sct in scts {
+        write_u16_prefixed(&mut list, &sct.to_bytes()?, "SCT")?;
+    }
+    let mut out = Vec::with_capacity(2 + list.len());
+    write_u16_prefixed(&mut out, &list, "SCT list")?;
+    Ok(out)
+}
+
+/// Parse the value of a X.509 or OCSP SCT list extension, which is a TLS-encoded list wrapped
+/// in a DER OCTET STRING.
+///
+/// Returns `None` if the extension is empty.
+pub fn parse_sct_list_extension(der: &[u8]) -> Result<Option<Vec<RawSct>>, Error> {
+    if der.is_empty() {
+        return Ok(None);
+    }
+    if der[0] != 0x04 {
+        return Err(Error::BadSct(
+            "SCT list extension is not an OCTET STRING.".to_owned(),
+        ));
+    }
+    match der_read_length(&der[1..]) {
+        Some((len, rest)) if rest.len() == len => Ok(Some(parse_sct_list(rest)?)),
+        _ => Err(Error::BadSct(
+            "SCT extension data not fully consumed.".to_owned(),
+        )),
+    }
+}
+
+/// Wrap an encoded `SignedCertificateTimestampList` in a DER OCTET STRING, to be used as the
+/// value of a X.509 or OCSP extension. The reverse of [`parse_sct_list_extension`].
+pub fn encode_sct_list_extension(scts: &[RawSct]) -> Result<Vec<u8>, Error> {
+    let list = encode_sct_list(scts)?;
+    let mut out = Vec::with_capacity(list.len() + 4);
+    out.push(0x04);
+    if list.len() < 0x80 {
+        out.push(list.len() as u8);
+    } else if list.len() < 0x100 {
+        out.extend_from_slice(&[0x81, list.len() as u8]);
+    } else if list.len() <= 0xffff {
+        out.push(0x82);
+        out.extend_from_slice(&(list.len() as u16).to_be_bytes());
+    } else {
+        // At most 2 + 2^16 - 1 bytes. DER needs the shortest length form, so this is the only
+        // case using 3 bytes.
+        out.push(0x83);
+        out.extend_from_slice(&(list.len() as u32).to_be_bytes()[1..]);
+    }
+    out.extend_from_slice(&list);
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip_test() {
+        let bytes = &include_bytes!("../test_data/sct_list.bin")[..];
+        let scts = parse_sct_list(bytes).unwrap();
+        assert_eq!(scts.len(), 2);
+        assert_eq!(scts[0].timestamp, 1735689600000);
+        assert_eq!(scts[1].timestamp, 1735689600001);
+        assert_eq!(scts[0].signature_algorithm, SignatureAlgorithm::Sha256Ecdsa);
+        assert!(scts[0].extensions.is_empty());
+        assert_eq!(encode_sct_list(&scts).unwrap(), bytes);
+
+        let ext = encode_sct_list_extension(&scts).unwrap();
+        assert_eq!(&ext[..2], &[0x04, 0x81]);
+        assert_eq!(parse_sct_list_extension(&ext).unwrap().unwrap(), scts);
+        assert_eq!(parse_sct_list_extension(&[]).unwrap(), None);
+        parse_sct_list_extension(&ext[..ext.len() - 1]).expect_err("");
+
+        let mut big = scts[0].clone();
+        big.extensions = vec![0x42; 300];
+        let ext = encode_sct_list_extension(&[big.clone()]).unwrap();
+        assert_eq!(
+            parse_sct_list_extension(&ext).unwrap().unwrap(),
+            vec![big.clone()]
+        );
+
+        // Lists of 256 to 65535 bytes take a 2-byte length.
+        let mut medium = scts[0].clone();
+        medium.extensions = vec![0x42; 300 - 2 - 49 - scts[0].raw_signature.len()];
+        let list = encode_sct_list(&[medium.clone()]).unwrap();
+        assert_eq!(list.len(), 300);
+        let ext = encode_sct_list_extension(&[medium]).unwrap();
+        assert_eq!(&ext[..4], &[0x04, 0x82, 0x01, 0x2c]);
+        assert_eq!(&ext[4..], &list[..]);
+        // Only bigger ones take 3 bytes.
+        let mut huge = scts[0].clone();
+        huge.extensions = vec![0x42; 0xffff - 49 - scts[0].raw_signature.len()];
+        let ext = encode_sct_list_extension(&[huge]).unwrap();
+        assert_eq!(&ext[..5], &[0x04, 0x83, 0x01, 0x00, 0x01]);
+        big.extensions = vec![0; 1 << 16];
+        encode_sct_list(&[big]).expect_err("");
+        encode_sct_list(&[]).expect_err("");
+    }
+
+    #[test]
+    fn malformed_test() {
+        let bytes = &include_bytes!("../test_data/sct_list.bin")[..];
+        for i in 0..bytes.len() {
+            assert!(matches!(parse_sct_list(&bytes[..i]), Err(Error::BadSct(_))));
+        }
+        let mut trailing = bytes.to_vec();
+        trailing.push(0);
+        assert!(matches!(parse_sct_list(&trailing), Err(Error::BadSct(_))));
+        assert!(matches!(parse_sct_list(&[0, 0]), Err(Error::BadSct(_))));
+        assert!(matches!(
+            parse_sct_list(&[0, 2, 0, 0]),
+            Err(Error::BadSct(_))
+        ));
+        let mut bad_version = bytes.to_vec();
+        bad_version[4] = 1;
+        assert!(matches!(
+            parse_sct_list(&bad_version),
+            Err(Error::BadSct(_))
+        ));
+        let mut bad_sig_alg = bytes.to_vec();
+        bad_sig_alg[4 + 1 + 32 + 8 + 2] = 0x08;
+        assert!(matches!(
+            parse_sct_list(&bad_sig_alg),
+            Err(Error::BadSct(_))
+        ));
+    }
+}
@@ -0,0 +1,95 @@
+use base64::prelude::{BASE64_STANDARD, Engine as _};
+use openssl::pkey::PKey;
+use openssl::x509::X509;
+
+use crate::internal::post_json;
+use crate::{Error, SctEntry, SignedCertificateTimestamp, jsons, utils};
+
+/// Submit a certificate chain to the CT log at `base_url` via `add-chain`, and return the
+/// SCT it issued after checking its signature against `pub_key`.
+///
+/// `chain[0]` is the end-entity certificate, and each following certificate should be the
+/// issuer of the previous one. The root may be omitted.
+///
+/// If you use [`CTClient`](crate::CTClient), call
+/// [`CTClient::add_chain`](crate::CTClient::add_chain) instead.
+pub async fn add_chain(
+    client: &reqwest::Client,
+    base_url: &reqwest::Url,
+    pub_key: &PKey<openssl::pkey::Public>,
+    chain: &[X509],
+) -> Result<SignedCertificateTimestamp, Error> {
+    if chain.is_empty() {
+        return Err(Error::InvalidArgument(
+            "Empty certificate chain.".to_owned(),
+        ));
+    }
+    let entry = SctEntry::X509(
+        chain[0]
+            .to_der()
+            .map_err(|e| Error::BadCertificate(format!("While encoding certificate: {}", e)))?,
+    );
+    submit(client, base_url, pub_key, chain, "ct/v1/add-chain", entry).await
+}
+
+/// Submit a precertificate chain to the CT log at `base_url` via `add-pre-chain`, and
+/// return the SCT it issued after checking its signature against `pub_key`.
+///
+/// `chain[0]` is the precertificate (with the poison extension), `chain[1]` is either
+/// the issuing CA or a precertificate signing certificate, in which case `chain[2]` must
+/// be the issuing CA.
+///
+/// If you use [`CTClient`](crate::CTClient), call
+/// [`CTClient::add_pre_chain`](crate::CTClient::add_pre_chain) instead.
+pub async fn add_pre_chain(
+    client: &reqwest::Client,
+    base_url: &reqwest::Url,
+    pub_key: &PKey<openssl::pkey::Public>,
+    chain: &[X509],
+) -> Result<SignedCertificateTimestamp, Error> {
+    let entry = SctEntry::from_precert_chain(chain)?;
+    submit(
+        client,
+        base_url,
+        pub_key,
+        chain,
+        "ct/v1/add-pre-chain",
+        entry,
+    )
+    .await
+}
+
+async fn submit(
+    client: &reqwest::Client,
+    base_url: &reqwest::Url,
+    pub_key: &PKey<openssl::pkey::Public>,
+    chain: &[X509],
+    path: &str,
+    entry: SctEntry,
+) -> Result<SignedCertificateTimestamp, Error> {
+    let mut req = jsons::AddChainRequest {
+        chain: Vec::with_capacity(chain.len()),
+    };
+    for cert in chain.iter() {
+        let der = cert
+            .to_der()
+            .map_err(|e| Error::BadCertificate(format!("While encoding certificate: {}", e)))?;
+        req.chain.push(BASE64_STANDARD.encode(der));
+    }
+    let response: jsons::AddChainResponse = post_json(client, base_url, path, &req).await?;
+    let sct = SignedCertificateTimestamp::from_add_chain_response(&response, entry)?;
+    let expected_log_id = utils::sha256(
+        &pub_key
+            .public_key_to_der()
+            .map_err(|e| Error::Unknown(format!("While encoding public key: {}", &e)))?,
+    );
+    if sct.log_id != expected_log_id {
+        return Err(Error::BadSct(format!(
+            "SCT is issued by log {}, but we expected {}.",
+            utils::u8_to_hex(&sct.log_id),
+            utils::u8_to_hex(&expected_log_id)
+        )));
+    }
+    sct.verify(pub_key)?;
+    Ok(sct)
+}
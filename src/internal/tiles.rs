@@ -0,0 +1,758 @@
+//! Reading logs that implement the [Static CT API](https://c2sp.org/static-ct-api) ("tiled logs").
+//!
+//! Instead of the `ct/v1/*` JSON endpoints, such a log publishes a signed checkpoint, hash tiles
+//! (the Merkle tree, 256 hashes wide and 8 levels high per tile) and data tiles (the leaves).
+//! There is no endpoint for proofs, so we build consistency and inclusion proofs from the hash
+//! tiles ourselves, and then check them exactly like the ones a RFC 6962 log would have sent.
+//!
+//! The functions here mirror their RFC 6962 counterparts in [`crate::internal`], and produce the
+//! same [`Leaf`] values. All `base_url`s are the log's *monitoring prefix*, ending with `/`.
+
+use std::collections::HashMap;
+use std::convert::TryInto;
+use std::ops::Range;
+
+use async_stream::try_stream;
+use futures::Stream;
+use log::trace;
+use openssl::pkey::PKey;
+
+use crate::checkpoint::SignedNote;
+use crate::internal::{
+    ConsistencyProofPart, FetchInclusionProofResult, Leaf, consistency_proof_parts, get_bytes,
+    hash_inclusion_proof, inclusion_proof_parts, verify_consistency_proof,
+};
+use crate::utils::{combine_tree_hash, largest_power_of_2_smaller_than, sha256, u8_to_hex};
+use crate::{Error, SignedTreeHead};
+
+/// Number of entries in a full tile.
+pub const TILE_WIDTH: u64 = 256;
+
+/// Number of Merkle tree levels covered by one level of hash tiles.
+const TILE_HEIGHT: u32 = 8;
+
+/// Encode a tile index as a path, in groups of three digits with all but the last group
+/// prefixed by `x`.
+///
+/// # Example
+///
+/// ```
+/// # use ctclient_async::internal::tiles::tile_index_path;
+/// assert_eq!(tile_index_path(1234067), "x001/x234/067");
+/// assert_eq!(tile_index_path(42), "042");
+/// ```
+pub fn tile_index_path(mut index: u64) -> String {
+    let mut groups = vec![format!("{:03}", index % 1000)];
+    index /= 1000;
+    while index > 0 {
+        groups.push(format!("x{:03}", index % 1000));
+        index /= 1000;
+    }
+    groups.reverse();
+    groups.join("/")
+}
+
+fn tile_path(level: &str, index: u64, width: u64) -> String {
+    if width == TILE_WIDTH {
+        format!("tile/{}/{}", level, tile_index_path(index))
+    } else {
+        format!("tile/{}/{}.p/{}", level, tile_index_path(index), width)
+    }
+}
+
+/// Fetch a (possibly partial) tile. Partial tiles are deleted once the full tile exists, so
+/// fall back to the full tile if the log has grown since.
+async fn fetch_tile(
+    client: &reqwest::Client,
+    base_url: &reqwest::Url,
+    level: &str,
+    index: u64,
+    width: u64,
+) -> Result<Vec<u8>, Error> {
+    match get_bytes(client, base_url, &tile_path(level, index, width)).await {
+        Err(Error::InvalidResponseStatus(s)) if s.as_u16() == 404 && width < TILE_WIDTH => {
+            get_bytes(client, base_url, &tile_path(level, index, TILE_WIDTH)).await
+        }
+        other => other,
+    }
+}
+
+/// Fetch the checkpoint of the log at `base_url` and verify it against the log's key.
+///
+/// The checkpoint signature is a RFC 6962 tree head signature, so the result is an ordinary
+/// [`SignedTreeHead`].
+pub async fn check_tree_head(
+    client: &reqwest::Client,
+    base_url: &reqwest::Url,
+    pub_key: &PKey<openssl::pkey::Public>,
+) -> Result<SignedTreeHead, Error> {
+    let note = get_bytes(client, base_url, "checkpoint").await?;
+    let note = String::from_utf8(note)
+        .map_err(|_| Error::MalformedResponseBody("Checkpoint is not valid UTF-8.".to_owned()))?;
+    let sth = SignedNote::parse(&note)
+        .and_then(|n| n.verify_rfc6962(pub_key))
+        .map_err(|e| match e {
+            Error::BadCheckpoint(desc) => Error::MalformedResponseBody(desc),
+            other => other,
+        })?;
+    trace!(
+        "{} tree head now on {} {}",
+        base_url.as_str(),
+        sth.tree_size,
+        &u8_to_hex(&sth.root_hash)
+    );
+    Ok(sth)
+}
+
+/// Computes subtree hashes of a tree with a certain size from the log's hash tiles.
+///
+/// Tiles are cached for as long as this reader lives.
+pub struct TileReader<'a> {
+    client: &'a reqwest::Client,
+    base_url: &'a reqwest::Url,
+    tree_size: u64,
+    cache: HashMap<(u32, u64), Vec<[u8; 32]>>,
+}
+
+impl<'a> TileReader<'a> {
+    pub fn new(client: &'a reqwest::Client, base_url: &'a reqwest::Url, tree_size: u64) -> Self {
+        TileReader {
+            client,
+            base_url,
+            tree_size,
+            cache: HashMap::new(),
+        }
+    }
+
+    async fn tile(&mut self, level: u32, index: u64) -> Result<&[[u8; 32]], Error> {
+        if !self.cache.contains_key(&(level, index)) {
+            let nb_hashes = self.tree_size >> (TILE_HEIGHT * level);
+            if index * TILE_WIDTH >= nb_hashes {
+                return Err(Error::InvalidArgument(format!(
+                    "Tile {}/{} is not in a tree of size {}.",
+                    level, index, self.tree_size
+                )));
+            }
+            let width = u64::min(TILE_WIDTH, nb_hashes - index * TILE_WIDTH);
+            let data =
+                fetch_tile(self.client, self.base_url, &level.to_string(), index, width).await?;
+            if data.len() % 32 != 0
+                || (data.len() as u64) < width * 32
+                || (data.len() as u64) > TILE_WIDTH * 32
+            {
+                return Err(Error::MalformedResponseBody(format!(
+                    "Hash tile {}/{} has length {}, expected {} hashes.",
+                    level,
+                    index,
+                    data.len(),
+                    width
+                )));
+            }
+            let hashes = data
+                .chunks_exact(32)
+                .take(width as usize)
+                .map(|h| h.try_into().unwrap())
+                .collect();
+            self.cache.insert((level, index), hashes);
+        }
+        Ok(&self.cache[&(level, index)][..])
+    }
+
+    /// Hash of the complete subtree of `size` leaves starting at `start`. `size` must be a power
+    /// of two, and `start` a multiple of it.
+    async fn perfect_subtree_hash(&mut self, start: u64, size: u64) -> Result<[u8; 32], Error> {
+        assert!(size.is_power_of_two() && start.is_multiple_of(size));
+        let height = size.trailing_zeros();
+        let level = height / TILE_HEIGHT;
+        let first = start >> (TILE_HEIGHT * level);
+        let count = 1usize << (height % TILE_HEIGHT);
+        let offset = (first % TILE_WIDTH) as usize;
+        let tile = self.tile(level, first / TILE_WIDTH).await?;
+        if offset + count > tile.len() {
+            return Err(Error::InvalidArgument(format!(
+                "Subtree [{}, {}) is not complete in a tree of size {}.",
+                start,
+                start + size,
+                self.tree_size
+            )));
+        }
+        let mut hashes = tile[offset..offset + count].to_vec();
+        while hashes.len() > 1 {
+            hashes = hashes
+                .chunks_exact(2)
+                .map(|p| combine_tree_hash(&p[0], &p[1]))
+                .collect();
+        }
+        Ok(hashes[0])
+    }
+
+    /// Hash of the subtree formed by leafs with number [start, end). This is the same kind of
+    /// subtree as the ones in [`consistency_proof_parts`] and [`inclusion_proof_parts`].
+    pub async fn subtree_hash(&mut self, start: u64, end: u64) -> Result<[u8; 32], Error> {
+        if start >= end || end > self.tree_size {
+            return Err(Error::InvalidArgument(format!(
+                "Invalid subtree [{}, {}) for a tree of size {}.",
+                start, end, self.tree_size
+            )));
+        }
+        // A subtree is its left branch, which is complete, and the subtree formed by the rest.
+        let mut complete_parts = Vec::new();
+        let mut rest_start = start;
+        while !(end - rest_start).is_power_of_two() {
+            let left_size = largest_power_of_2_smaller_than(end - rest_start);
+            complete_parts.push((rest_start, left_size));
+            rest_start += left_size;
+        }
+        if !rest_start.is_multiple_of(end - rest_start) {
+            return Err(Error::InvalidArgument(format!(
+                "[{}, {}) is not a subtree.",
+                start, end
+            )));
+        }
+        let mut hash = self
+            .perfect_subtree_hash(rest_start, end - rest_start)
+            .await?;
+        for (part_start, part_size) in complete_parts.into_iter().rev() {
+            if !part_start.is_multiple_of(part_size) {
+                return Err(Error::InvalidArgument(format!(
+                    "[{}, {}) is not a subtree.",
+                    start, end
+                )));
+            }
+            let left = self.perfect_subtree_hash(part_start, part_size).await?;
+            hash = combine_tree_hash(&left, &hash);
+        }
+        Ok(hash)
+    }
+}
+
+/// Build the consistency proof from `prev_size` to `next_size` out of the log's hash tiles and
+/// verify it. Same as the RFC 6962 [`check_consistency_proof`](crate::internal::check_consistency_proof).
+///
+/// # Panics
+///
+/// ...if prev_size >= next_size
+pub async fn check_consistency_proof(
+    client: &reqwest::Client,
+    base_url: &reqwest::Url,
+    prev_size: u64,
+    next_size: u64,
+    perv_root: &[u8; 32],
+    next_root: &[u8; 32],
+) -> Result<Vec<ConsistencyProofPart>, Error> {
+    let proof = fetch_consistency_proof(client, base_url, prev_size, next_size).await?;
+    verify_consistency_proof(prev_size, next_size, &proof, perv_root, next_root).map_err(|e| {
+        Error::InvalidConsistencyProof {
+            prev_size,
+            new_size: next_size,
+            desc: e,
+        }
+    })
+}
+
+/// Build the consistency proof from `prev_size` to `next_size` out of the log's hash tiles
+/// without verifying it. Same as the RFC 6962
+/// [`fetch_consistency_proof`](crate::internal::fetch_consistency_proof).
+///
+/// # Panics
+///
+/// ...if prev_size >= next_size
+pub async fn fetch_consistency_proof(
+    client: &reqwest::Client,
+    base_url: &reqwest::Url,
+    prev_size: u64,
+    next_size: u64,
+) -> Result<Vec<[u8; 32]>, Error> {
+    assert!(prev_size < next_size);
+    let mut proof = Vec::new();
+    if prev_size > 0 {
+        let mut reader = TileReader::new(client, base_url, next_size);
+        let mut parts = consistency_proof_parts(prev_size, next_size);
+        if prev_size.is_power_of_two() {
+            // verify_consistency_proof expects the first part to be omitted, like the server would.
+            parts.remove(0);
+        }
+        for (start, end) in parts.into_iter() {
+            proof.push(reader.subtree_hash(start, end).await?);
+        }
+    }
+    Ok(proof)
+}
+
+/// Build the inclusion proof for the leaf at `leaf_index` out of the log's hash tiles, and
+/// return the tree hash it yields.
+///
+/// Tiled logs have no lookup by hash, so the leaf index must be known. SCTs issued by these logs
+/// carry it, see [`leaf_index_from_extensions`].
+pub async fn fetch_inclusion_proof(
+    client: &reqwest::Client,
+    base_url: &reqwest::Url,
+    tree_size: u64,
+    leaf_index: u64,
+    leaf_hash: &[u8; 32],
+) -> Result<FetchInclusionProofResult, Error> {
+    if leaf_index >= tree_size {
+        return Err(Error::InvalidArgument(format!(
+            "leaf_index {} is not in a tree of size {}.",
+            leaf_index, tree_size
+        )));
+    }
+    let mut reader = TileReader::new(client, base_url, tree_size);
+    let proof_parts = inclusion_proof_parts(tree_size, leaf_index);
+    let mut proof = Vec::with_capacity(proof_parts.len());
+    for part in proof_parts.iter() {
+        proof.push(reader.subtree_hash(part.start, part.end).await?);
+    }
+    Ok(FetchInclusionProofResult {
+        calculated_tree_hash: hash_inclusion_proof(&proof_parts, &proof, leaf_hash, leaf_index),
+        leaf_index,
+    })
+}
+
+/// Check that the leaf with `leaf_hash` is at `leaf_index` in the tree with hash `tree_hash` and
+/// size `tree_size`. Returns the leaf index, like [`check_inclusion_proof`](crate::internal::check_inclusion_proof).
+pub async fn check_inclusion_proof(
+    client: &reqwest::Client,
+    base_url: &reqwest::Url,
+    tree_size: u64,
+    tree_hash: &[u8; 32],
+    leaf_index: u64,
+    leaf_hash: &[u8; 32],
+) -> Result<u64, Error> {
+    let res = fetch_inclusion_proof(client, base_url, tree_size, leaf_index, leaf_hash).await?;
+    if &res.calculated_tree_hash != tree_hash {
+        return Err(Error::InvalidInclusionProof {
+            tree_size,
+            leaf_index,
+            desc: format!(
+                "Expected the proof to yield a tree hash of {}, but instead got {}.",
+                u8_to_hex(tree_hash),
+                u8_to_hex(&res.calculated_tree_hash)
+            ),
+        });
+    }
+    Ok(leaf_index)
+}
+
+/// Find the `leaf_index` extension in the extensions of a SCT or leaf issued by a tiled log.
+///
+/// `extensions` does not include the length prefix, like
+/// [`SignedCertificateTimestamp::extensions_data`](crate::SignedCertificateTimestamp::extensions_data).
+pub fn leaf_index_from_extensions(extensions: &[u8]) -> Option<u64> {
+    /*
+      struct {
+          ExtensionType extension_type; // uint8, leaf_index(0)
+          opaque extension_data<0..2^16-1>;
+      } Extension;
+    */
+    let mut rest = extensions;
+    while rest.len() >= 3 {
+        let ext_type = rest[0];
+        let len = u16::from_be_bytes([rest[1], rest[2]]) as usize;
+        rest = &rest[3..];
+        if rest.len() < len {
+            return None;
+        }
+        let data = &rest[..len];
+        rest = &rest[len..];
+        if ext_type == 0 {
+            // uint40
+            if data.len() != 5 {
+                return None;
+            }
+            let mut buf = [0u8; 8];
+            buf[3..].copy_from_slice(data);
+            return Some(u64::from_be_bytes(buf));
+        }
+    }
+    None
+}
+
+/// An entry in a data tile.
+#[derive(Debug, Clone)]
+pub struct TileLeaf {
+    /// The `MerkleTreeLeaf` this entry stands for, as it would appear in a RFC 6962 `leaf_input`.
+    pub leaf_input: Vec<u8>,
+    /// The precertificate, for precert entries.
+    pub pre_certificate: Option<Vec<u8>>,
+    /// SHA-256 of each certificate in the rest of the chain, which can be fetched from the log's
+    /// `issuer/` endpoint.
+    pub chain_fingerprints: Vec<[u8; 32]>,
+}
+
+impl TileLeaf {
+    /// Turn this into a [`Leaf`], given the certificates behind `chain_fingerprints`.
+    pub fn to_leaf(&self, chain: &[Vec<u8>]) -> Result<Leaf, Error> {
+        fn push_u24_prefixed(v: &mut Vec<u8>, data: &[u8]) {
+            v.extend_from_slice(&(data.len() as u32).to_be_bytes()[1..4]);
+            v.extend_from_slice(data);
+        }
+        let mut chain_data = Vec::new();
+        for cert in chain.iter() {
+            push_u24_prefixed(&mut chain_data, cert);
+        }
+        let mut extra_data = Vec::new();
+        if let Some(pre_certificate) = &self.pre_certificate {
+            push_u24_prefixed(&mut extra_data, pre_certificate);
+        }
+        push_u24_prefixed(&mut extra_data, &chain_data);
+        Leaf::from_raw(&self.leaf_input, &extra_data)
+    }
+}
+
+/// Parse the entries in a data tile.
+pub fn parse_data_tile(data: &[u8]) -> Result<Vec<TileLeaf>, Error> {
+    /*
+      struct {
+          TimestampedEntry timestamped_entry;
+          select (entry_type) {
+              case x509_entry: Empty;
+              case precert_entry: ASN.1Cert pre_certificate;
+          };
+          Fingerprint certificate_chain<0..2^16-1>;
+      } TileLeaf;
+    */
+    fn err_invalid() -> Error {
+        Error::MalformedResponseBody("Invalid data tile.".to_owned())
+    }
+    fn take<'a>(rest: &mut &'a [u8], len: usize) -> Result<&'a [u8], Error> {
+        if rest.len() < len {
+            return Err(err_invalid());
+        }
+        let (taken, new_rest) = rest.split_at(len);
+        *rest = new_rest;
+        Ok(taken)
+    }
+    fn take_u24_prefixed<'a>(rest: &mut &'a [u8]) -> Result<&'a [u8], Error> {
+        let len = take(rest, 3)?;
+        let len = u32::from_be_bytes([0, len[0], len[1], len[2]]);
+        take(rest, len as usize)
+    }
+    fn take_u16_prefixed<'a>(rest: &mut &'a [u8]) -> Result<&'a [u8], Error> {
+        let len = take(rest, 2)?;
+        take(rest, u16::from_be_bytes([len[0], len[1]]) as usize)
+    }
+
+    let mut entries = Vec::new();
+    let mut rest = data;
+    while !rest.is_empty() {
+        let entry_start = rest;
+        let _timestamp = take(&mut rest, 8)?;
+        let entry_type = take(&mut rest, 2)?;
+        let entry_type = u16::from_be_bytes([entry_type[0], entry_type[1]]);
+        match entry_type {
+            0 => {
+                take_u24_prefixed(&mut rest)?;
+            }
+            1 => {
+                take(&mut rest, 32)?;
+                take_u24_prefixed(&mut rest)?;
+            }
+            _ => return Err(err_invalid()),
+        }
+        take_u16_prefixed(&mut rest)?;
+        let timestamped_entry = &entry_start[..entry_start.len() - rest.len()];
+        let mut leaf_input = Vec::with_capacity(2 + timestamped_entry.len());
+        leaf_input.push(0); // version = 0
+        leaf_input.push(0); // leaf type = timestamped_entry
+        leaf_input.extend_from_slice(timestamped_entry);
+        let pre_certificate = if entry_type == 1 {
+            Some(take_u24_prefixed(&mut rest)?.to_vec())
+        } else {
+            None
+        };
+        let fingerprints = take_u16_prefixed(&mut rest)?;
+        if fingerprints.len() % 32 != 0 {
+            return Err(err_invalid());
+        }
+        entries.push(TileLeaf {
+            leaf_input,
+            pre_certificate,
+            chain_fingerprints: fingerprints
+                .chunks_exact(32)
+                .map(|f| f.try_into().unwrap())
+                .collect(),
+        });
+    }
+    Ok(entries)
+}
+
+/// Request leaf entries in `range` from the data tiles of a tiled log whose tree has size
+/// `tree_size`, fetching chain certificates as needed. Does not verify if these entries are
+/// consistent with the tree or anything like that. Returns an iterator over the leaves and their
+/// index, same as [`get_entries`](crate::internal::get_entries).
+///
+/// After the first Err result, the iterator will not produce anything else.
+pub fn get_entries<'a>(
+    client: &'a reqwest::Client,
+    base_url: &'a reqwest::Url,
+    range: Range<u64>,
+    tree_size: u64,
+) -> impl Stream<Item = Result<(u64, Leaf), Error>> + 'a {
+    try_stream! {
+        if range.end > tree_size {
+            Err(Error::InvalidArgument(format!(
+                "Range {:?} is not in a tree of size {}.",
+                range, tree_size
+            )))?;
+        }
+        let mut issuers: HashMap<[u8; 32], Vec<u8>> = HashMap::new();
+        let mut next_index = range.start;
+
+        while next_index < range.end {
+            let tile_index = next_index / TILE_WIDTH;
+            let tile_start = tile_index * TILE_WIDTH;
+            let width = u64::min(TILE_WIDTH, tree_size - tile_start);
+            let data = fetch_tile(client, base_url, "data", tile_index, width).await?;
+            let entries = parse_data_tile(&data)?;
+            if (entries.len() as u64) < width {
+                Err(Error::MalformedResponseBody(format!(
+                    "Data tile {} has {} entries, expected {}.",
+                    tile_index,
+                    entries.len(),
+                    width
+                )))?;
+            }
+            let tile_end = u64::min(tile_start + width, range.end);
+            for (index, entry) in (next_index..tile_end).zip(entries[(next_index - tile_start) as usize..(tile_end - tile_start) as usize].iter()) {
+                let mut chain = Vec::with_capacity(entry.chain_fingerprints.len());
+                for fingerprint in entry.chain_fingerprints.iter() {
+                    if !issuers.contains_key(fingerprint) {
+                        let cert = get_bytes(
+                            client,
+                            base_url,
+                            &format!("issuer/{}", u8_to_hex(fingerprint)),
+                        )
+                        .await?;
+                        if &sha256(&cert) != fingerprint {
+                            Err(Error::MalformedResponseBody(format!(
+                                "Issuer {} does not match its fingerprint.",
+                                u8_to_hex(fingerprint)
+                            )))?;
+                        }
+                        issuers.insert(*fingerprint, cert);
+                    }
+                    chain.push(issuers[fingerprint].clone());
+                }
+                yield (index, entry.to_leaf(&chain)?);
+            }
+            next_index = tile_end;
+        }
+    }
+}
+
+#[test]
+fn tile_path_test() {
+    assert_eq!(tile_index_path(0), "000");
+    assert_eq!(tile_index_path(1000), "x001/000");
+    assert_eq!(tile_path("0", 1234067, 256), "tile/0/x001/x234/067");
+    assert_eq!(tile_path("data", 1234067, 8), "tile/data/x001/x234/067.p/8");
+}
+
+#[test]
+fn leaf_index_from_extensions_test() {
+    assert_eq!(
+        leaf_index_from_extensions(&[0, 0, 5, 0, 0, 1, 0, 2]),
+        Some(65538)
+    );
+    assert_eq!(leaf_index_from_extensions(&[]), None);
+    assert_eq!(leaf_index_from_extensions(&[1, 0, 1, 0xff]), None);
+    assert_eq!(leaf_index_from_extensions(&[0, 0, 5, 0]), None);
+}
+
+#[cfg(test)]
+mod data_tile_tests {
+    use super::*;
+    use crate::jsons;
+    use futures::StreamExt;
+    use std::convert::TryFrom;
+
+    /// The issuers behind the fingerprints in `test_data/data_tile.bin`, which holds an x509 entry
+    /// for `sct_leaf.pem` and a precert entry for `precert-signing-ca.pem`.
+    fn issuers() -> HashMap<[u8; 32], Vec<u8>> {
+        let leaf = openssl::x509::X509::stack_from_pem(include_bytes!("../test_data/sct_leaf.pem"));
+        let precert = openssl::x509::X509::stack_from_pem(include_bytes!(
+            "../test_data/precert-signing-ca.pem"
+        ));
+        leaf.unwrap()[1..]
+            .iter()
+            .chain(precert.unwrap()[1..].iter())
+            .map(|c| {
+                let der = c.to_der().unwrap();
+                (sha256(&der), der)
+            })
+            .collect()
+    }
+
+    /// The same entries, as a RFC 6962 log would return them from `get-entries`.
+    fn expected_leaves() -> Vec<Leaf> {
+        let json: jsons::GetEntries =
+            serde_json::from_slice(include_bytes!("../test_data/data_tile_entries.json")).unwrap();
+        json.entries
+            .iter()
+            .map(|e| Leaf::try_from(e).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn to_leaf_test() {
+        let tile = include_bytes!("../test_data/data_tile.bin");
+        let entries = parse_data_tile(tile).unwrap();
+        assert_eq!(entries.len(), 2);
+        let issuers = issuers();
+        let leaves: Vec<Leaf> = entries
+            .iter()
+            .map(|e| {
+                let chain: Vec<Vec<u8>> = e
+                    .chain_fingerprints
+                    .iter()
+                    .map(|f| issuers[f].clone())
+                    .collect();
+                e.to_leaf(&chain).unwrap()
+            })
+            .collect();
+        assert!(leaves == expected_leaves());
+
+        assert!(!leaves[0].is_pre_cert);
+        assert_eq!(leaves[0].x509_chain.len(), 2);
+        assert!(leaves[1].is_pre_cert);
+        assert_eq!(
+            leaves[1].tbs_cert.as_deref(),
+            Some(&include_bytes!("../test_data/precert-signing.ca.tbs")[..])
+        );
+        assert_eq!(
+            Some(&leaves[1].x509_chain[0]),
+            entries[1].pre_certificate.as_ref()
+        );
+        assert_eq!(leaf_index_from_extensions(&leaves[1].extensions), Some(1));
+
+        // A truncated tile is invalid, unless it is cut between entries.
+        for len in 1..tile.len() {
+            match parse_data_tile(&tile[..len]) {
+                Ok(entries) => assert_eq!(entries.len(), 1),
+                Err(e) => assert!(matches!(e, Error::MalformedResponseBody(_))),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn get_entries_test() {
+        let log = crate::mock_log::MockLog::new(0);
+        let client = reqwest::Client::new();
+        let base_url = reqwest::Url::parse(&log.url).unwrap();
+        // Only the full tile is served, so the partial one falls back to it.
+        log.serve(
+            "tile/data/000",
+            include_bytes!("../test_data/data_tile.bin").to_vec(),
+        );
+        for (fingerprint, cert) in issuers() {
+            log.serve(&format!("issuer/{}", u8_to_hex(&fingerprint)), cert);
+        }
+        let leaves: Vec<(u64, Leaf)> = get_entries(&client, &base_url, 0..2, 2)
+            .map(|r| r.unwrap())
+            .collect()
+            .await;
+        assert!(leaves.iter().map(|(i, _)| *i).eq(0..2));
+        assert!(leaves.into_iter().map(|(_, l)| l).eq(expected_leaves()));
+        let requests = log.requests();
+        assert_eq!(requests[..2], ["tile/data/000.p/2", "tile/data/000"]);
+        // Each issuer is fetched once.
+        assert_eq!(requests.len(), 2 + issuers().len());
+
+        let leaves: Vec<_> = get_entries(&client, &base_url, 1..2, 2).collect().await;
+        assert!(matches!(&leaves[..], [Ok((1, _))]));
+
+        // The tile has fewer entries than the tree needs.
+        let leaves: Vec<_> = get_entries(&client, &base_url, 0..3, 3).collect().await;
+        assert!(matches!(
+            &leaves[..],
+            [Err(Error::MalformedResponseBody(_))]
+        ));
+
+        // An issuer that does not match its fingerprint.
+        let (fingerprint, _) = issuers().into_iter().next().unwrap();
+        log.serve(&format!("issuer/{}", u8_to_hex(&fingerprint)), vec![0; 10]);
+        let leaves: Vec<_> = get_entries(&client, &base_url, 0..2, 2).collect().await;
+        assert!(matches!(
+            leaves.last(),
+            Some(Err(Error::MalformedResponseBody(e))) if e.contains("fingerprint")
+        ));
+    }
+}
+
+#[cfg(test)]
+#[tokio::test]
+async fn tile_reader_test() {
+    fn mth(leafs: &[[u8; 32]]) -> [u8; 32] {
+        if leafs.len() == 1 {
+            return leafs[0];
+        }
+        let k = largest_power_of_2_smaller_than(leafs.len() as u64) as usize;
+        combine_tree_hash(&mth(&leafs[..k]), &mth(&leafs[k..]))
+    }
+    let tree_size: u64 = 256 * 256 + 300;
+    let leafs: Vec<[u8; 32]> = (0..tree_size).map(|i| sha256(&i.to_be_bytes())).collect();
+    let client = reqwest::Client::new();
+    let base_url = reqwest::Url::parse("http://localhost/").unwrap();
+    let mut reader = TileReader::new(&client, &base_url, tree_size);
+    // Pre-fill the cache with every (partial) tile, so that nothing is fetched.
+    let mut level_hashes = leafs.clone();
+    for level in 0..3 {
+        for (index, tile) in level_hashes.chunks(TILE_WIDTH as usize).enumerate() {
+            reader.cache.insert((level, index as u64), tile.to_vec());
+        }
+        level_hashes = level_hashes
+            .chunks_exact(TILE_WIDTH as usize)
+            .map(mth)
+            .collect();
+    }
+
+    assert_eq!(
+        reader.subtree_hash(0, tree_size).await.unwrap(),
+        mth(&leafs)
+    );
+    for &(start, end) in &[
+        (0, 1),
+        (256, 512),
+        (65536, 65836),
+        (65792, 65836),
+        (512, 1024),
+    ] {
+        assert_eq!(
+            reader.subtree_hash(start, end).await.unwrap(),
+            mth(&leafs[start as usize..end as usize])
+        );
+    }
+    reader.subtree_hash(1, 3).await.expect_err("");
+    reader.subtree_hash(0, tree_size + 1).await.expect_err("");
+
+    for &leaf_index in &[0, 255, 256, 65535, 65536, tree_size - 1] {
+        let parts = inclusion_proof_parts(tree_size, leaf_index);
+        let mut proof = Vec::new();
+        for part in parts.iter() {
+            proof.push(reader.subtree_hash(part.start, part.end).await.unwrap());
+        }
+        assert_eq!(
+            hash_inclusion_proof(&parts, &proof, &leafs[leaf_index as usize], leaf_index),
+            mth(&leafs)
+        );
+    }
+    for &prev_size in &[1, 256, 1000, 65536] {
+        let mut parts = consistency_proof_parts(prev_size, tree_size);
+        if prev_size.is_power_of_two() {
+            parts.remove(0);
+        }
+        let mut proof = Vec::new();
+        for (start, end) in parts.into_iter() {
+            proof.push(reader.subtree_hash(start, end).await.unwrap());
+        }
+        verify_consistency_proof(
+            prev_size,
+            tree_size,
+            &proof,
+            &mth(&leafs[..prev_size as usize]),
+            &mth(&leafs),
+        )
+        .unwrap();
+    }
+}
@@ -31,3 +31,29 @@ pub struct AuditProof {
     pub leaf_index: u64,
     pub audit_path: Vec<String>,
 }
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct EntryAndProof {
+    pub leaf_input: String,
+    pub extra_data: String,
+    pub audit_path: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct AddChainRequest {
+    pub chain: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct AddChainResponse {
+    pub sct_version: u8,
+    pub id: String,
+    pub timestamp: u64,
+    pub extensions: String,
+    pub signature: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct GetRoots {
+    pub certificates: Vec<String>,
+}
@@ -33,7 +33,9 @@ use openssl::pkey::PKey;
 use openssl::x509::X509;
 
 use internal::new_http_client;
-pub use sct::{SctEntry, SignedCertificateTimestamp};
+pub use sct::{
+    SctCheck, SctEntry, SctProblem, SignedCertificateTimestamp, check_cert_scts, check_scts,
+};
 pub use sth::SignedTreeHead;
 
 use crate::internal::openssl_ffi::{x509_clone, x509_make_a_looks_like_issued_by_b};
@@ -44,11 +46,16 @@ use crate::internal::{
 mod sct;
 mod sth;
 
+pub mod auditor;
 pub mod certutils;
+pub mod checkpoint;
+pub mod ct_v2;
 pub mod google_log_list;
 pub mod internal;
 pub mod jsons;
+pub mod policy;
 pub mod utils;
+pub mod witness;
 
 #[cfg(not(any(feature = "native-tls", feature = "rustls-tls")))]
 compile_error!("You must enable either the `native-tls` or `rustls-tls` feature.");
@@ -105,6 +112,30 @@ pub enum Error {
 
     /// We asked for a certain entry expecting it to be there, but the server gave us nothing.
     ExpectedEntry(u64),
+
+    /// A logged certificate chain does not end in a root the log says it accepts.
+    RootNotAccepted(String),
+
+    /// A malformed signed note or checkpoint is given.
+    BadCheckpoint(String),
+
+    /// A checkpoint is not cosigned by enough witnesses, see [`checkpoint::WitnessPolicy`].
+    NotWitnessed(String),
+
+    /// A checkpoint is from a log the witness does not know about.
+    UnknownLog(String),
+
+    /// The witness last cosigned a tree of size `expected`, which is not what the request assumed.
+    WitnessSizeMismatch { expected: u64 },
+
+    /// A log list is older than one accepted before.
+    LogListRollback {
+        previous_timestamp: u64,
+        timestamp: u64,
+    },
+
+    /// The log is retired, so [`CTClient::update`] no longer polls it.
+    LogRetired,
 }
 
 /// Either a fetched and checked [`SignedTreeHead`], or a [`SignedTreeHead`] that has a valid signature
@@ -226,10 +257,48 @@ impl fmt::Display for Error {
                 "The server did not return the leaf with index {}, even though we believe it should be there.",
                 leaf_index
             ),
+            Error::RootNotAccepted(desc) => write!(
+                f,
+                "The certificate chain does not end in a root accepted by the log: {}",
+                desc
+            ),
+            Error::BadCheckpoint(desc) => write!(f, "The checkpoint is invalid: {}", desc),
+            Error::NotWitnessed(desc) => {
+                write!(f, "The tree head is not witnessed: {}", desc)
+            }
+            Error::UnknownLog(origin) => write!(f, "Unknown log {}", origin),
+            Error::LogListRollback {
+                previous_timestamp,
+                timestamp,
+            } => write!(
+                f,
+                "Log list timestamp {} is older than the previously accepted {}",
+                timestamp, previous_timestamp
+            ),
+            Error::WitnessSizeMismatch { expected } => write!(
+                f,
+                "The witness's latest cosigned checkpoint has size {}",
+                expected
+            ),
+            Error::LogRetired => write!(f, "The log has been retired"),
         }
     }
 }
 
+/// Which API a CT log serves.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogApi {
+    /// The `ct/v1/*` JSON endpoints of [RFC 6962](https://tools.ietf.org/html/rfc6962).
+    Rfc6962,
+    /// The checkpoint, hash tiles and data tiles of the
+    /// [Static CT API](https://c2sp.org/static-ct-api). See [`internal::tiles`].
+    StaticCt,
+}
+
+/// The commit interval [`CTClient::update_with_commit_hook`] uses when none is set, as it holds
+/// the certificates of each interval in memory until they are committed.
+pub const DEFAULT_HELD_COMMIT_INTERVAL: u64 = 1000;
+
 /// A stateful CT monitor.
 ///
 /// One instance of this struct only concerns with one particular log. To monitor multiple
@@ -241,8 +310,20 @@ pub struct CTClient {
     base_url: reqwest::Url,
     pub_key: PKey<openssl::pkey::Public>,
     http_client: reqwest::Client,
+    api: LogApi,
     latest_size: u64,
     latest_tree_hash: [u8; 32],
+    accepted_roots: Option<internal::AcceptedRoots>,
+    reject_unaccepted_roots: bool,
+    witness_policy: Option<checkpoint::WitnessPolicy>,
+    witnessed_checkpoint_url: Option<reqwest::Url>,
+    log_state: Option<google_log_list::LogState>,
+    final_tree_head: Option<google_log_list::FinalTreeHead>,
+    /// Compact range of the tree of `latest_size`, if all its leaves have been checked.
+    compact_range: Option<internal::CompactRange>,
+    commit_interval: Option<u64>,
+    get_entries_batch_size: u64,
+    get_entries_parallelism: usize,
 }
 
 impl fmt::Debug for CTClient {
@@ -270,10 +351,10 @@ impl CTClient {
     ///
     /// ```
     /// use ctclient_async::CTClient;
-    /// use base64::decode;
+    /// use base64::prelude::{BASE64_STANDARD, Engine as _};
     /// # tokio_test::block_on(async {
     /// // URL and public key copy-pasted from https://www.gstatic.com/ct/log_list/v3/all_logs_list.json .
-    /// let public_key = decode("MFkwEwYHKoZIzj0CAQYIKoZIzj0DAQcDQgAEGoAaFRkZI3m0+qB5jo3VwdzCtZaSfpTgw34UfAoNLUaonRuxQWUMX5jEWhd5gVtKFEHsr6ldDqsSGXHNQ++7lw==").unwrap();
+    /// let public_key = BASE64_STANDARD.decode("MFkwEwYHKoZIzj0CAQYIKoZIzj0DAQcDQgAEGoAaFRkZI3m0+qB5jo3VwdzCtZaSfpTgw34UfAoNLUaonRuxQWUMX5jEWhd5gVtKFEHsr6ldDqsSGXHNQ++7lw==").unwrap();
     /// let client = CTClient::new_from_latest_th("https://ct.cloudflare.com/logs/nimbus2025/", &public_key).await.unwrap();
     /// # });
     /// ```
@@ -291,8 +372,19 @@ impl CTClient {
             base_url,
             pub_key: evp_pkey,
             http_client,
+            api: LogApi::Rfc6962,
             latest_size: sth.tree_size,
             latest_tree_hash: sth.root_hash,
+            accepted_roots: None,
+            reject_unaccepted_roots: false,
+            witness_policy: None,
+            witnessed_checkpoint_url: None,
+            log_state: None,
+            final_tree_head: None,
+            compact_range: None,
+            commit_interval: None,
+            get_entries_batch_size: 500,
+            get_entries_parallelism: 1,
         })
     }
 
@@ -305,9 +397,9 @@ impl CTClient {
     ///
     /// ```
     /// use ctclient_async::{CTClient, utils};
-    /// use base64::decode;
+    /// use base64::prelude::{BASE64_STANDARD, Engine as _};
     /// // URL and public key copy-pasted from https://www.gstatic.com/ct/log_list/v3/all_logs_list.json .
-    /// let public_key = decode("MFkwEwYHKoZIzj0CAQYIKoZIzj0DAQcDQgAE01EAhx4o0zPQrXTcYjgCt4MVFsT0Pwjzb1RwrM0lhWDlxAYPP6/gyMCXNkOn/7KFsjL7rwk78tHMpY8rXn8AYg==").unwrap();
+    /// let public_key = BASE64_STANDARD.decode("MFkwEwYHKoZIzj0CAQYIKoZIzj0DAQcDQgAE01EAhx4o0zPQrXTcYjgCt4MVFsT0Pwjzb1RwrM0lhWDlxAYPP6/gyMCXNkOn/7KFsjL7rwk78tHMpY8rXn8AYg==").unwrap();
     /// use std::convert::TryInto;
     /// // Tree captured on 2020-05-12 15:34:11 UTC
     /// let th: [u8; 32] = (&utils::hex_to_u8("63875e88a3e37dc5b6cdbe213fe1df490d40193e4777f79467958ee157de70d6")[..]).try_into().unwrap();
@@ -331,11 +423,60 @@ impl CTClient {
             base_url,
             pub_key: evp_pkey,
             http_client,
+            api: LogApi::Rfc6962,
             latest_size: tree_size,
             latest_tree_hash: tree_hash,
+            accepted_roots: None,
+            reject_unaccepted_roots: false,
+            witness_policy: None,
+            witnessed_checkpoint_url: None,
+            log_state: None,
+            final_tree_head: None,
+            compact_range: None,
+            commit_interval: None,
+            get_entries_batch_size: 500,
+            get_entries_parallelism: 1,
         })
     }
 
+    /// Like [`Self::new_from_latest_th`], but for a log serving the
+    /// [Static CT API](https://c2sp.org/static-ct-api).
+    ///
+    /// `base_url` is the log's monitoring prefix, and must end with `/`.
+    pub async fn new_static_ct_from_latest_th(
+        base_url: &str,
+        pub_key: &[u8],
+    ) -> Result<Self, Error> {
+        let mut client = Self::new_static_ct_from_perv_tree_hash(base_url, pub_key, [0u8; 32], 0)?;
+        let sth = match client.fetch_tree_head().await {
+            SthResult::Ok(sth) => sth,
+            other => return Err(other.unwrap_err()),
+        };
+        client.latest_size = sth.tree_size;
+        client.latest_tree_hash = sth.root_hash;
+        Ok(client)
+    }
+
+    /// Like [`Self::new_from_perv_tree_hash`], but for a log serving the
+    /// [Static CT API](https://c2sp.org/static-ct-api).
+    ///
+    /// `base_url` is the log's monitoring prefix, and must end with `/`.
+    pub fn new_static_ct_from_perv_tree_hash(
+        base_url: &str,
+        pub_key: &[u8],
+        tree_hash: [u8; 32],
+        tree_size: u64,
+    ) -> Result<Self, Error> {
+        let mut client = Self::new_from_perv_tree_hash(base_url, pub_key, tree_hash, tree_size)?;
+        client.api = LogApi::StaticCt;
+        Ok(client)
+    }
+
+    /// Which API this client uses to talk to the log.
+    pub fn get_log_api(&self) -> LogApi {
+        self.api
+    }
+
     /// Get the last checked tree head. Returns `(tree_size, root_hash)`.
     pub fn get_checked_tree_head(&self) -> (u64, [u8; 32]) {
         (self.latest_size, self.latest_tree_hash)
@@ -353,6 +494,438 @@ impl CTClient {
         &self.base_url
     }
 
+    /// Fetch the set of roots this log accepts with `get-roots`, and remember it for
+    /// [`Self::check_leaf`].
+    ///
+    /// If a root set has been fetched (or set) before and the new one is different, the change
+    /// is returned. Logs are expected to only accept roots listed in their published policy, so
+    /// a change is worth looking into.
+    pub async fn fetch_accepted_roots(&mut self) -> Result<Option<internal::RootsChange>, Error> {
+        self.require_rfc6962_api("get-roots")?;
+        let roots = internal::get_roots(&self.http_client, &self.base_url).await?;
+        let change = match &self.accepted_roots {
+            Some(old) => Some(old.diff(&roots)).filter(|c| !c.is_empty()),
+            None => None,
+        };
+        if let Some(c) = &change {
+            warn!(
+                "{}: accepted roots changed: {} added, {} removed",
+                self.base_url.as_str(),
+                c.added.len(),
+                c.removed.len()
+            );
+        }
+        self.accepted_roots = Some(roots);
+        Ok(change)
+    }
+
+    /// Get the last fetched set of accepted roots, if any.
+    pub fn get_accepted_roots(&self) -> Option<&internal::AcceptedRoots> {
+        self.accepted_roots.as_ref()
+    }
+
+    /// Replace the remembered set of accepted roots, for example with one fetched earlier.
+    pub fn set_accepted_roots(&mut self, roots: Option<internal::AcceptedRoots>) {
+        self.accepted_roots = roots;
+    }
+
+    /// If set to true, [`Self::check_leaf`] will reject leafs whose chain does not end in (or
+    /// is not directly issued by) a root in the set fetched with [`Self::fetch_accepted_roots`].
+    ///
+    /// Default is false.
+    pub fn set_reject_unaccepted_roots(&mut self, reject: bool) {
+        self.reject_unaccepted_roots = reject;
+    }
+
+    /// Static CT logs take submissions (and serve `get-roots`) on a different URL prefix than the
+    /// one they are monitored on, so these are only available on RFC 6962 logs.
+    fn require_rfc6962_api(&self, endpoint: &str) -> Result<(), Error> {
+        match self.api {
+            LogApi::Rfc6962 => Ok(()),
+            LogApi::StaticCt => Err(Error::InvalidArgument(format!(
+                "{} is not served on the monitoring prefix of a Static CT log. Call the function in `internal` with the submission prefix instead.",
+                endpoint
+            ))),
+        }
+    }
+
+    /// Fetch the latest tree head, or with a witness policy, the latest witnessed checkpoint.
+    async fn fetch_tree_head(&self) -> SthResult {
+        let policy = match &self.witness_policy {
+            Some(p) => p,
+            None => {
+                let res = match self.api {
+                    LogApi::Rfc6962 => {
+                        internal::check_tree_head(&self.http_client, &self.base_url, &self.pub_key)
+                            .await
+                    }
+                    LogApi::StaticCt => {
+                        internal::tiles::check_tree_head(
+                            &self.http_client,
+                            &self.base_url,
+                            &self.pub_key,
+                        )
+                        .await
+                    }
+                };
+                return match res {
+                    Ok(sth) => SthResult::Ok(sth),
+                    Err(e) => SthResult::Err(e),
+                };
+            }
+        };
+        let (url, path) = match &self.witnessed_checkpoint_url {
+            Some(url) => (url, ""),
+            None => (&self.base_url, "checkpoint"),
+        };
+        let note = match internal::get_bytes(&self.http_client, url, path)
+            .await
+            .and_then(|note| {
+                String::from_utf8(note).map_err(|_| {
+                    Error::MalformedResponseBody("Checkpoint is not valid UTF-8.".to_owned())
+                })
+            })
+            .and_then(|note| checkpoint::SignedNote::parse(&note))
+        {
+            Ok(n) => n,
+            Err(Error::BadCheckpoint(desc)) => {
+                return SthResult::Err(Error::MalformedResponseBody(desc));
+            }
+            Err(e) => return SthResult::Err(e),
+        };
+        let sth = match note.verify_rfc6962(&self.pub_key) {
+            Ok(s) => s,
+            Err(e) => return SthResult::Err(e),
+        };
+        match policy.check(&note) {
+            Ok(witnesses) => {
+                info!(
+                    "{}: tree size {} cosigned by {}",
+                    self.base_url.as_str(),
+                    sth.tree_size,
+                    witnesses.join(", ")
+                );
+                SthResult::Ok(sth)
+            }
+            Err(e) => SthResult::ErrWithSth(e, sth),
+        }
+    }
+
+    async fn fetch_consistency_proof(
+        &self,
+        prev_size: u64,
+        next_size: u64,
+        perv_root: &[u8; 32],
+        next_root: &[u8; 32],
+    ) -> Result<Vec<internal::ConsistencyProofPart>, Error> {
+        match self.api {
+            LogApi::Rfc6962 => {
+                check_consistency_proof(
+                    &self.http_client,
+                    &self.base_url,
+                    prev_size,
+                    next_size,
+                    perv_root,
+                    next_root,
+                )
+                .await
+            }
+            LogApi::StaticCt => {
+                internal::tiles::check_consistency_proof(
+                    &self.http_client,
+                    &self.base_url,
+                    prev_size,
+                    next_size,
+                    perv_root,
+                    next_root,
+                )
+                .await
+            }
+        }
+    }
+
+    /// Stream leafs in `range`, with their index, out of a tree of size `tree_size`. `batch_size`
+    /// and `parallelism` only apply to RFC 6962 logs, since tiled logs always serve 256 entries at
+    /// a time.
+    fn get_entries(
+        &self,
+        range: std::ops::Range<u64>,
+        tree_size: u64,
+        batch_size: u64,
+        parallelism: usize,
+    ) -> impl futures::Stream<Item = Result<(u64, Leaf), Error>> + '_ {
+        use futures::future::Either;
+        match self.api {
+            LogApi::Rfc6962 if parallelism > 1 => {
+                Either::Left(Either::Left(internal::get_entries_parallel(
+                    &self.http_client,
+                    &self.base_url,
+                    range,
+                    batch_size,
+                    parallelism,
+                )))
+            }
+            LogApi::Rfc6962 => Either::Left(Either::Right(internal::get_entries(
+                &self.http_client,
+                &self.base_url,
+                range,
+                batch_size,
+            ))),
+            LogApi::StaticCt => Either::Right(internal::tiles::get_entries(
+                &self.http_client,
+                &self.base_url,
+                range,
+                tree_size,
+            )),
+        }
+    }
+
+    /// Number of entries [`Self::update`] asks for in each `get-entries` request to a RFC 6962
+    /// log. Defaults to 500. Logs may return less, in which case the rest is requested again.
+    ///
+    /// The log's own limit is only learned with a [parallelism](Self::set_get_entries_parallelism)
+    /// above 1, where batches are then planned with the size the log returns. Sequential updates
+    /// keep asking for `batch_size` entries from the first one not returned yet.
+    pub fn set_get_entries_batch_size(&mut self, batch_size: u64) {
+        self.get_entries_batch_size = batch_size.max(1);
+    }
+
+    /// Number of `get-entries` requests [`Self::update`] keeps in flight at once for a RFC 6962
+    /// log, see [`internal::get_entries_parallel`]. Defaults to 1, which fetches batches one after
+    /// another.
+    pub fn set_get_entries_parallelism(&mut self, parallelism: usize) {
+        self.get_entries_parallelism = parallelism.max(1);
+    }
+
+    /// Only accept tree heads that are cosigned by witnesses according to `policy` in
+    /// [`Self::update`]. A tree head that is signed by the log but not witnessed is returned as an
+    /// [`ErrWithSth`](SthResult::ErrWithSth) with [`Error::NotWitnessed`].
+    ///
+    /// With a policy set, tree heads are read from the cosigned checkpoint at `checkpoint_url`
+    /// (such as one served by a witness network) instead of from the log. It can be omitted for
+    /// Static CT logs, which may serve cosigned checkpoints themselves.
+    ///
+    /// Pass `None` as `policy` to go back to trusting the log's own tree heads.
+    pub fn set_witness_policy(
+        &mut self,
+        policy: Option<checkpoint::WitnessPolicy>,
+        checkpoint_url: Option<&str>,
+    ) -> Result<(), Error> {
+        let checkpoint_url = match checkpoint_url {
+            Some(url) => Some(
+                reqwest::Url::parse(url)
+                    .map_err(|e| Error::InvalidArgument(format!("Unable to parse url: {}", &e)))?,
+            ),
+            None => None,
+        };
+        if policy.is_some() && checkpoint_url.is_none() && self.api == LogApi::Rfc6962 {
+            return Err(Error::InvalidArgument(
+                "RFC 6962 logs don't serve checkpoints, so a checkpoint_url is needed.".to_owned(),
+            ));
+        }
+        self.witness_policy = policy;
+        self.witnessed_checkpoint_url = checkpoint_url;
+        Ok(())
+    }
+
+    /// Tell this client which state the log is in, usually from [`google_log_list::Log::state`]
+    /// and [`google_log_list::Log::final_tree_head`].
+    ///
+    /// * For a [`Readonly`](google_log_list::LogState::Readonly) log with a final tree head,
+    ///   [`Self::update`] only accepts tree heads consistent with it, and treats any growth past it
+    ///   (or a different root hash at its size) as misbehavior.
+    /// * For a [`Retired`](google_log_list::LogState::Retired) log, [`Self::update`] returns
+    ///   [`Error::LogRetired`] without contacting the log.
+    ///
+    /// Returns an error if the already checked tree head contradicts `final_tree_head`, in which
+    /// case nothing is changed.
+    ///
+    /// This is not saved by [`Self::as_bytes`], so set it again after restoring a client.
+    pub fn set_log_state(
+        &mut self,
+        state: Option<google_log_list::LogState>,
+        final_tree_head: Option<google_log_list::FinalTreeHead>,
+    ) -> Result<(), Error> {
+        if let Some(fth) = &final_tree_head {
+            if self.latest_size > fth.tree_size {
+                return Err(Error::InvalidConsistencyProof {
+                    prev_size: fth.tree_size,
+                    new_size: self.latest_size,
+                    desc: format!(
+                        "Already checked tree size {} is beyond the final tree size {}",
+                        self.latest_size, fth.tree_size
+                    ),
+                });
+            }
+            if self.latest_size == fth.tree_size && self.latest_tree_hash != fth.root_hash {
+                return Err(Error::InvalidConsistencyProof {
+                    prev_size: fth.tree_size,
+                    new_size: self.latest_size,
+                    desc: format!(
+                        "Server forked! Final tree head {} and checked {} both correspond to tree_size {}",
+                        &utils::u8_to_hex(&fth.root_hash),
+                        &utils::u8_to_hex(&self.latest_tree_hash),
+                        fth.tree_size
+                    ),
+                });
+            }
+        }
+        self.log_state = state;
+        self.final_tree_head = final_tree_head;
+        Ok(())
+    }
+
+    /// The state set with [`Self::set_log_state`], if any.
+    pub fn get_log_state(
+        &self,
+    ) -> (
+        Option<google_log_list::LogState>,
+        Option<google_log_list::FinalTreeHead>,
+    ) {
+        (self.log_state, self.final_tree_head)
+    }
+
+    /// The compact range of the last checked tree head (see [`Self::get_checked_tree_head`]), if
+    /// [`Self::update`] has checked the leaves up to it.
+    pub fn get_compact_range(&self) -> Option<&internal::CompactRange> {
+        self.compact_range
+            .as_ref()
+            .filter(|r| r.size() == self.latest_size)
+    }
+
+    /// Give this client the compact range of its last checked tree head, for example one saved
+    /// from [`Self::get_compact_range`] of another client. This saves [`Self::update`] from
+    /// fetching a consistency proof to start checking leaves.
+    ///
+    /// Returns an error if `range` is not the range of the last checked tree head.
+    pub fn set_compact_range(&mut self, range: internal::CompactRange) -> Result<(), Error> {
+        if range.size() != self.latest_size || range.root() != self.latest_tree_hash {
+            return Err(Error::InvalidArgument(format!(
+                "Compact range of size {} with root {} is not the checked tree head {} {}.",
+                range.size(),
+                utils::u8_to_hex(&range.root()),
+                self.latest_size,
+                utils::u8_to_hex(&self.latest_tree_hash)
+            )));
+        }
+        self.compact_range = Some(range);
+        Ok(())
+    }
+
+    /// While catching up with [`Self::update`], commit the checked tree head every `interval`
+    /// leaves, so that progress is not lost if the update fails later. Each intermediate tree head
+    /// is computed from the leaves, and the log has to prove that it is consistent with the tree
+    /// head being caught up to, which costs one consistency proof per commit.
+    ///
+    /// `None` (the default) only commits once all leaves are checked, except in
+    /// [`Self::update_with_commit_hook`], which then commits every
+    /// [`DEFAULT_HELD_COMMIT_INTERVAL`] leaves.
+    ///
+    /// Intermediate tree heads are not signed by the log, so the checked tree head is not always
+    /// one that the log has signed.
+    pub fn set_commit_interval(&mut self, interval: Option<u64>) {
+        self.commit_interval = interval;
+    }
+
+    /// The compact range of the last checked tree head, either the one we already have, or
+    /// recovered from a consistency proof to `next_size`.
+    async fn fetch_compact_range(
+        &self,
+        next_size: u64,
+        next_root: &[u8; 32],
+    ) -> Result<internal::CompactRange, Error> {
+        if let Some(range) = &self.compact_range
+            && range.size() == self.latest_size
+            && range.root() == self.latest_tree_hash
+        {
+            return Ok(range.clone());
+        }
+        if self.latest_size == 0 {
+            return Ok(internal::CompactRange::new());
+        }
+        let proof = match self.api {
+            LogApi::Rfc6962 => {
+                internal::fetch_consistency_proof(
+                    &self.http_client,
+                    &self.base_url,
+                    self.latest_size,
+                    next_size,
+                )
+                .await?
+            }
+            LogApi::StaticCt => {
+                internal::tiles::fetch_consistency_proof(
+                    &self.http_client,
+                    &self.base_url,
+                    self.latest_size,
+                    next_size,
+                )
+                .await?
+            }
+        };
+        internal::CompactRange::from_consistency_proof(
+            self.latest_size,
+            next_size,
+            &proof,
+            &self.latest_tree_hash,
+            next_root,
+        )
+        .map_err(|e| Error::InvalidConsistencyProof {
+            prev_size: self.latest_size,
+            new_size: next_size,
+            desc: e,
+        })
+    }
+
+    /// Check a new tree head of a read-only log against its final tree head.
+    async fn check_against_final_tree_head(&self, sth: &SignedTreeHead) -> Result<(), Error> {
+        let fth = match (self.log_state, &self.final_tree_head) {
+            (Some(google_log_list::LogState::Readonly), Some(fth)) => fth,
+            _ => return Ok(()),
+        };
+        use std::cmp::Ordering;
+        match sth.tree_size.cmp(&fth.tree_size) {
+            Ordering::Greater => Err(Error::InvalidConsistencyProof {
+                prev_size: fth.tree_size,
+                new_size: sth.tree_size,
+                desc: format!(
+                    "Read-only log grew past its final tree size {}",
+                    fth.tree_size
+                ),
+            }),
+            Ordering::Equal if sth.root_hash != fth.root_hash => {
+                Err(Error::InvalidConsistencyProof {
+                    prev_size: fth.tree_size,
+                    new_size: sth.tree_size,
+                    desc: format!(
+                        "Server forked! Final tree head {} and {} both correspond to tree_size {}",
+                        &utils::u8_to_hex(&fth.root_hash),
+                        &utils::u8_to_hex(&sth.root_hash),
+                        fth.tree_size
+                    ),
+                })
+            }
+            Ordering::Equal => Ok(()),
+            Ordering::Less => self
+                .fetch_consistency_proof(
+                    sth.tree_size,
+                    fth.tree_size,
+                    &sth.root_hash,
+                    &fth.root_hash,
+                )
+                .await
+                .map(|_| ())
+                .map_err(|e| Error::InvalidConsistencyProof {
+                    prev_size: sth.tree_size,
+                    new_size: fth.tree_size,
+                    desc: format!(
+                        "Tree head is not consistent with the final tree head: {}",
+                        e
+                    ),
+                }),
+        }
+    }
+
     /// Calls `self.update()` with `None` as `cert_handler`.
     pub async fn light_update(&mut self) -> SthResult {
         self.update(None::<fn(&[X509])>).await
@@ -370,18 +943,84 @@ impl CTClient {
     /// of database (even when error). This can be used to prove a misconduct (such as a non-extending-only tree)
     /// in the future.
     ///
-    /// Will only update the stored latest tree head if an [`Ok`](SthResult::Ok) is returned.
-    pub async fn update<H>(&mut self, mut cert_handler: Option<H>) -> SthResult
+    /// Without a [commit interval](Self::set_commit_interval), the stored latest tree head is
+    /// only updated if an [`Ok`](SthResult::Ok) is returned.
+    ///
+    /// When `cert_handler` is a Some, the new leaves are streamed into the
+    /// [compact range](internal::CompactRange) of the last checked tree, so memory use does not
+    /// grow with the number of new leaves. The range is kept for the next update (and saved by
+    /// [`Self::as_bytes`]), otherwise it is recovered from a consistency proof.
+    ///
+    /// With a [commit interval](Self::set_commit_interval), progress is also committed
+    /// while catching up, so a failed update does not have to start over. See
+    /// [`Self::update_with_commit_hook`] to save it.
+    ///
+    /// `cert_handler` is called as soon as each leaf is checked, before it is committed. If the
+    /// update then fails, the next one starts again from the last commit and passes those leaves
+    /// again. Use [`Self::update_with_commit_hook`] to have each leaf passed exactly once across
+    /// restarts.
+    ///
+    /// See [`Self::set_log_state`] for how read-only and retired logs are handled.
+    pub async fn update<H>(&mut self, cert_handler: Option<H>) -> SthResult
     where
         H: FnMut(&[X509]),
     {
-        let mut delaycheck = std::time::Instant::now();
-        let sth = match internal::check_tree_head(&self.http_client, &self.base_url, &self.pub_key)
+        let cert_handler = cert_handler.map(|mut h| move |_: u64, chain: &[X509]| h(chain));
+        self.update_inner(cert_handler, |_: &CTClient| Ok(()), false)
             .await
-        {
-            Ok(s) => s,
-            Err(e) => return SthResult::Err(e),
+    }
+
+    /// Like [`Self::update`], but call `commit_hook` every time the checked tree head (see
+    /// [`Self::get_checked_tree_head`]) advances, including at each
+    /// [commit interval](Self::set_commit_interval) while catching up. If it returns an error,
+    /// the update stops with that error, but what is committed so far stays committed.
+    ///
+    /// `cert_handler` is called with the index and certificate chain of each new leaf, but only
+    /// once the leaf is committed: the leaves of each commit interval are checked and held in
+    /// memory until the log has proven them part of its tree, then passed to `cert_handler` right
+    /// before the checked tree head advances and `commit_hook` is called. So a failed update never
+    /// passes a leaf that the next update will pass again. Only if the process dies after the
+    /// handler calls and before `commit_hook` has saved [`Self::as_bytes`] will the leaves of
+    /// that interval be passed again, which the index can be used to detect.
+    ///
+    /// To bound the memory held, a commit interval of [`DEFAULT_HELD_COMMIT_INTERVAL`] leaves is
+    /// used if none is set with [`Self::set_commit_interval`].
+    pub async fn update_with_commit_hook<H, C>(
+        &mut self,
+        cert_handler: Option<H>,
+        commit_hook: C,
+    ) -> SthResult
+    where
+        H: FnMut(u64, &[X509]),
+        C: FnMut(&CTClient) -> Result<(), Error>,
+    {
+        self.update_inner(cert_handler, commit_hook, true).await
+    }
+
+    /// The implementation of [`Self::update`] and [`Self::update_with_commit_hook`]. If
+    /// `deliver_on_commit` is false, leaves are passed to `cert_handler` as soon as they are
+    /// checked instead of being held until they are committed.
+    async fn update_inner<H, C>(
+        &mut self,
+        mut cert_handler: Option<H>,
+        mut commit_hook: C,
+        deliver_on_commit: bool,
+    ) -> SthResult
+    where
+        H: FnMut(u64, &[X509]),
+        C: FnMut(&CTClient) -> Result<(), Error>,
+    {
+        if self.log_state == Some(google_log_list::LogState::Retired) {
+            return SthResult::Err(Error::LogRetired);
+        }
+        let mut delaycheck = std::time::Instant::now();
+        let sth = match self.fetch_tree_head().await {
+            SthResult::Ok(s) => s,
+            other => return other,
         };
+        if let Err(e) = self.check_against_final_tree_head(&sth).await {
+            return SthResult::ErrWithSth(e, sth);
+        }
         let new_tree_size = sth.tree_size;
         let new_tree_root = sth.root_hash;
         use std::cmp::Ordering;
@@ -408,15 +1047,14 @@ impl CTClient {
             }
             Ordering::Less => {
                 // Make sure server isn't doing trick with us.
-                match internal::check_consistency_proof(
-                    &self.http_client,
-                    &self.base_url,
-                    new_tree_size,
-                    self.latest_size,
-                    &new_tree_root,
-                    &self.latest_tree_hash,
-                )
-                .await
+                match self
+                    .fetch_consistency_proof(
+                        new_tree_size,
+                        self.latest_size,
+                        &new_tree_root,
+                        &self.latest_tree_hash,
+                    )
+                    .await
                 {
                     Ok(_) => {
                         warn!(
@@ -441,80 +1079,157 @@ impl CTClient {
                 }
             }
             Ordering::Greater => {
-                let consistency_proof_parts = match internal::check_consistency_proof(
-                    &self.http_client,
-                    &self.base_url,
-                    self.latest_size,
-                    new_tree_size,
-                    &self.latest_tree_hash,
-                    &new_tree_root,
-                )
-                .await
-                {
-                    Ok(k) => k,
-                    Err(e) => return SthResult::ErrWithSth(e, sth),
-                };
-
                 if cert_handler.is_some() {
+                    // Rather than keeping all the new leaf hashes around to check them against a
+                    // consistency proof, we append them to the compact range of the old tree as
+                    // they come, and compare the resulting root with the signed one. This also
+                    // proves that the new tree is consistent with the old one.
+                    let mut range = match self
+                        .fetch_compact_range(new_tree_size, &new_tree_root)
+                        .await
+                    {
+                        Ok(r) => r,
+                        Err(e) => return SthResult::ErrWithSth(e, sth),
+                    };
                     let i_start = self.latest_size;
-                    let leafs = internal::get_entries(
-                        &self.http_client,
-                        &self.base_url,
-                        i_start..new_tree_size,
-                        500,
-                    );
-                    // `get_entries` returns a stream backed by an async block which is !Unpin.
-                    // Pin it on the stack so we can `.next().await` without requiring `Unpin`.
-                    pin_mut!(leafs);
-                    let mut leaf_hashes: Vec<[u8; 32]> =
-                        Vec::with_capacity((new_tree_size - i_start) as usize);
-                    for i in i_start..new_tree_size {
-                        match leafs.next().await {
-                            Some(Ok(leaf)) => {
-                                leaf_hashes.push(leaf.hash);
-                                if let Err(e) = self.check_leaf(&leaf, &mut cert_handler) {
-                                    return SthResult::ErrWithSth(e, sth);
-                                }
+                    // Checked leaves not yet passed to `cert_handler`, with their index.
+                    let mut pending: Vec<(u64, Vec<X509>)> = Vec::new();
+                    while self.latest_size < new_tree_size {
+                        let chunk_start = self.latest_size;
+                        let commit_interval = match self.commit_interval {
+                            None if deliver_on_commit => Some(DEFAULT_HELD_COMMIT_INTERVAL),
+                            interval => interval,
+                        };
+                        let chunk_end = match commit_interval {
+                            Some(interval) => {
+                                new_tree_size.min(chunk_start.saturating_add(interval.max(1)))
                             }
-                            Some(Err(e)) => {
-                                return SthResult::ErrWithSth(
-                                    if let Error::MalformedResponseBody(inner_e) = e {
-                                        Error::MalformedResponseBody(format!(
-                                            "While parsing leaf #{}: {}",
-                                            i, &inner_e
-                                        ))
-                                    } else {
-                                        e
-                                    },
-                                    sth,
-                                );
-                            }
-                            None => {
-                                return SthResult::ErrWithSth(Error::ExpectedEntry(i), sth);
+                            None => new_tree_size,
+                        };
+                        {
+                            let leafs = self.get_entries(
+                                chunk_start..chunk_end,
+                                new_tree_size,
+                                self.get_entries_batch_size,
+                                self.get_entries_parallelism,
+                            );
+                            // `get_entries` returns a stream backed by an async block which is !Unpin.
+                            // Pin it on the stack so we can `.next().await` without requiring `Unpin`.
+                            pin_mut!(leafs);
+                            for i in chunk_start..chunk_end {
+                                match leafs.next().await {
+                                    Some(Ok((index, _))) if index != i => {
+                                        return SthResult::ErrWithSth(Error::ExpectedEntry(i), sth);
+                                    }
+                                    Some(Ok((_, leaf))) => {
+                                        range.append(&leaf.hash);
+                                        let checked = if deliver_on_commit {
+                                            self.check_leaf(
+                                                &leaf,
+                                                &mut Some(|chain: &[X509]| {
+                                                    pending.push((i, chain.to_vec()))
+                                                }),
+                                            )
+                                        } else {
+                                            self.check_leaf(
+                                                &leaf,
+                                                &mut cert_handler
+                                                    .as_mut()
+                                                    .map(|h| move |chain: &[X509]| h(i, chain)),
+                                            )
+                                        };
+                                        if let Err(e) = checked {
+                                            return SthResult::ErrWithSth(e, sth);
+                                        }
+                                    }
+                                    Some(Err(e)) => {
+                                        return SthResult::ErrWithSth(
+                                            if let Error::MalformedResponseBody(inner_e) = e {
+                                                Error::MalformedResponseBody(format!(
+                                                    "While parsing leaf #{}: {}",
+                                                    i, &inner_e
+                                                ))
+                                            } else {
+                                                e
+                                            },
+                                            sth,
+                                        );
+                                    }
+                                    None => {
+                                        return SthResult::ErrWithSth(Error::ExpectedEntry(i), sth);
+                                    }
+                                }
+                                if delaycheck.elapsed() > std::time::Duration::from_secs(1) {
+                                    info!(
+                                        "{}: Catching up: {} / {} ({}%)",
+                                        self.base_url.as_str(),
+                                        i,
+                                        new_tree_size,
+                                        ((i - i_start) * 1000 / (new_tree_size - i_start)) as f32
+                                            / 10f32
+                                    );
+                                    delaycheck = std::time::Instant::now();
+                                }
                             }
                         }
-                        if delaycheck.elapsed() > std::time::Duration::from_secs(1) {
-                            info!(
-                                "{}: Catching up: {} / {} ({}%)",
-                                self.base_url.as_str(),
-                                i,
+                        if chunk_end == new_tree_size {
+                            break;
+                        }
+                        // The tree of size `chunk_end` is only computed by us from the leaves,
+                        // so ask the log to prove that it is part of the signed one before
+                        // committing it.
+                        let chunk_root = range.root();
+                        if let Err(e) = self
+                            .fetch_consistency_proof(
+                                chunk_end,
                                 new_tree_size,
-                                ((i - i_start) * 1000 / (new_tree_size - i_start)) as f32 / 10f32
-                            );
-                            delaycheck = std::time::Instant::now();
+                                &chunk_root,
+                                &new_tree_root,
+                            )
+                            .await
+                        {
+                            return SthResult::ErrWithSth(e, sth);
                         }
-                    }
-                    assert_eq!(leaf_hashes.len(), (new_tree_size - i_start) as usize);
-                    for proof_part in consistency_proof_parts.into_iter() {
-                        assert!(proof_part.subtree.0 >= i_start);
-                        assert!(proof_part.subtree.1 <= new_tree_size);
-                        if let Err(e) = proof_part.verify(
-                            &leaf_hashes[(proof_part.subtree.0 - i_start) as usize
-                                ..(proof_part.subtree.1 - i_start) as usize],
-                        ) {
-                            return SthResult::ErrWithSth(Error::CannotVerifyTreeData(e), sth);
+                        if let Some(handler) = cert_handler.as_mut() {
+                            for (index, chain) in pending.drain(..) {
+                                handler(index, &chain);
+                            }
+                        }
+                        self.latest_size = chunk_end;
+                        self.latest_tree_hash = chunk_root;
+                        self.compact_range = Some(range.clone());
+                        info!(
+                            "{}: committed {} {}",
+                            self.base_url.as_str(),
+                            chunk_end,
+                            &utils::u8_to_hex(&chunk_root)
+                        );
+                        if let Err(e) = commit_hook(self) {
+                            return SthResult::ErrWithSth(e, sth);
                         }
                     }
+                    if range.size() != new_tree_size {
+                        return SthResult::ErrWithSth(
+                            Error::Unknown(format!(
+                                "Checked a tree of size {}, but expected {}.",
+                                range.size(),
+                                new_tree_size
+                            )),
+                            sth,
+                        );
+                    }
+                    let calculated_root = range.root();
+                    if calculated_root != new_tree_root {
+                        return SthResult::ErrWithSth(
+                            Error::CannotVerifyTreeData(format!(
+                                "Calculated that tree hash at size {} should be {}, but the server signed {}.",
+                                new_tree_size,
+                                utils::u8_to_hex(&calculated_root),
+                                utils::u8_to_hex(&new_tree_root)
+                            )),
+                            sth,
+                        );
+                    }
                     info!(
                         "{} updated to {} {} (read {} leaves)",
                         self.base_url.as_str(),
@@ -522,7 +1237,24 @@ impl CTClient {
                         &utils::u8_to_hex(&new_tree_root),
                         new_tree_size - i_start
                     );
+                    if let Some(handler) = cert_handler.as_mut() {
+                        for (index, chain) in pending.drain(..) {
+                            handler(index, &chain);
+                        }
+                    }
+                    self.compact_range = Some(range);
                 } else {
+                    if let Err(e) = self
+                        .fetch_consistency_proof(
+                            self.latest_size,
+                            new_tree_size,
+                            &self.latest_tree_hash,
+                            &new_tree_root,
+                        )
+                        .await
+                    {
+                        return SthResult::ErrWithSth(e, sth);
+                    }
                     info!(
                         "{} light updated to {} {}",
                         self.base_url.as_str(),
@@ -533,6 +1265,9 @@ impl CTClient {
 
                 self.latest_size = new_tree_size;
                 self.latest_tree_hash = new_tree_root;
+                if let Err(e) = commit_hook(self) {
+                    return SthResult::ErrWithSth(e, sth);
+                }
                 SthResult::Ok(sth)
             }
         }
@@ -580,6 +1315,18 @@ impl CTClient {
                 ));
             }
         }
+        if self.reject_unaccepted_roots {
+            let roots = self.accepted_roots.as_ref().ok_or_else(|| {
+                Error::InvalidArgument(
+                    "Asked to reject unaccepted roots, but the accepted roots are not fetched."
+                        .to_owned(),
+                )
+            })?;
+            let last = chain.last().unwrap();
+            if !roots.is_accepted_chain_end(last)? {
+                return Err(Error::RootNotAccepted(format!("{:?}", last.subject_name())));
+            }
+        }
         if let Some(tbs) = &leaf.tbs_cert {
             use internal::openssl_ffi::{x509_remove_poison, x509_to_tbs};
             let cert = chain[0].as_ref();
@@ -632,14 +1379,94 @@ impl CTClient {
         sct: &SignedCertificateTimestamp,
     ) -> Result<u64, Error> {
         let th = self.get_checked_tree_head();
-        check_inclusion_proof(
-            self.get_reqwest_client(),
-            &self.base_url,
-            th.0,
-            &th.1,
-            &sct.derive_leaf_hash(),
-        )
-        .await
+        match self.api {
+            LogApi::Rfc6962 => {
+                check_inclusion_proof(
+                    self.get_reqwest_client(),
+                    &self.base_url,
+                    th.0,
+                    &th.1,
+                    &sct.derive_leaf_hash(),
+                )
+                .await
+            }
+            LogApi::StaticCt => {
+                let leaf_index = internal::tiles::leaf_index_from_extensions(&sct.extensions_data)
+                    .ok_or_else(|| Error::BadSct("SCT has no leaf_index extension.".to_owned()))?;
+                internal::tiles::check_inclusion_proof(
+                    self.get_reqwest_client(),
+                    &self.base_url,
+                    th.0,
+                    &th.1,
+                    leaf_index,
+                    &sct.derive_leaf_hash(),
+                )
+                .await
+            }
+        }
+    }
+
+    /// Fetch the leaf at `leaf_index` with `get-entry-and-proof`, and check that it is included in
+    /// the last checked tree head (see [`Self::get_checked_tree_head`]).
+    ///
+    /// Does not check the certificates in the leaf. Call [`Self::check_leaf`] for that.
+    pub async fn get_entry_and_proof(&self, leaf_index: u64) -> Result<Leaf, Error> {
+        let th = self.get_checked_tree_head();
+        match self.api {
+            LogApi::Rfc6962 => {
+                internal::check_entry_and_proof(
+                    &self.http_client,
+                    &self.base_url,
+                    th.0,
+                    &th.1,
+                    leaf_index,
+                )
+                .await
+            }
+            LogApi::StaticCt => {
+                if leaf_index >= th.0 {
+                    return Err(Error::InvalidArgument(format!(
+                        "leaf_index {} is not in a tree of size {}.",
+                        leaf_index, th.0
+                    )));
+                }
+                let entries = self.get_entries(leaf_index..leaf_index + 1, th.0, 1, 1);
+                pin_mut!(entries);
+                let leaf = match entries.next().await {
+                    Some(r) => r?.1,
+                    None => return Err(Error::ExpectedEntry(leaf_index)),
+                };
+                internal::tiles::check_inclusion_proof(
+                    &self.http_client,
+                    &self.base_url,
+                    th.0,
+                    &th.1,
+                    leaf_index,
+                    &leaf.hash,
+                )
+                .await?;
+                Ok(leaf)
+            }
+        }
+    }
+
+    /// Submit a certificate chain to this log with `add-chain`, and return the SCT it issued.
+    ///
+    /// `chain[0]` is the end-entity certificate, followed by its issuers. The returned SCT has been
+    /// checked against the log's public key.
+    pub async fn add_chain(&self, chain: &[X509]) -> Result<SignedCertificateTimestamp, Error> {
+        self.require_rfc6962_api("add-chain")?;
+        internal::add_chain(&self.http_client, &self.base_url, &self.pub_key, chain).await
+    }
+
+    /// Submit a precertificate chain to this log with `add-pre-chain`, and return the SCT it issued.
+    ///
+    /// `chain[0]` is the precertificate, followed by its issuers. If it is issued by a
+    /// precertificate signing certificate, the CA it is signing on behalf of must also be
+    /// present. The returned SCT has been checked against the log's public key.
+    pub async fn add_pre_chain(&self, chain: &[X509]) -> Result<SignedCertificateTimestamp, Error> {
+        self.require_rfc6962_api("add-pre-chain")?;
+        internal::add_pre_chain(&self.http_client, &self.base_url, &self.pub_key, chain).await
     }
 
     pub async fn first_leaf_after(&self, timestamp: u64) -> Result<Option<(u64, Leaf)>, Error> {
@@ -648,14 +1475,13 @@ impl CTClient {
         let mut last_leaf: Option<(u64, Leaf)> = None;
         while low < high {
             let mid = (low + high - 1) / 2;
-            let entries_iter =
-                internal::get_entries(&self.http_client, &self.base_url, mid..mid + 1, 1);
+            let entries_iter = self.get_entries(mid..mid + 1, self.latest_size, 1, 1);
             // Pin the async-stream-backed iterator so it can be polled across await points.
             pin_mut!(entries_iter);
             match entries_iter.next().await {
                 None => return Err(Error::ExpectedEntry(mid)),
                 Some(Err(e)) => return Err(e),
-                Some(Ok(got_entry)) => {
+                Some(Ok((_, got_entry))) => {
                     let got_timestamp = got_entry.timestamp;
                     use std::cmp::Ordering::*;
                     match got_timestamp.cmp(&timestamp) {
@@ -688,8 +1514,21 @@ impl CTClient {
         }
         let fla = fla.unwrap();
         let tsize = fla.0 + 1;
-        let inclusion_res =
-            fetch_inclusion_proof(&self.http_client, &self.base_url, tsize, &fla.1.hash).await?;
+        let inclusion_res = match self.api {
+            LogApi::Rfc6962 => {
+                fetch_inclusion_proof(&self.http_client, &self.base_url, tsize, &fla.1.hash).await?
+            }
+            LogApi::StaticCt => {
+                internal::tiles::fetch_inclusion_proof(
+                    &self.http_client,
+                    &self.base_url,
+                    tsize,
+                    fla.0,
+                    &fla.1.hash,
+                )
+                .await?
+            }
+        };
         if inclusion_res.leaf_index != fla.0 {
             return Err(Error::Unknown(
                 "inclusion result.leaf_index != expected".to_owned(),
@@ -705,15 +1544,8 @@ impl CTClient {
         }
         let (tsize, thash) = res.unwrap();
         if tsize < self.latest_size {
-            check_consistency_proof(
-                &self.http_client,
-                &self.base_url,
-                tsize,
-                self.latest_size,
-                &thash,
-                &self.latest_tree_hash,
-            )
-            .await?;
+            self.fetch_consistency_proof(tsize, self.latest_size, &thash, &self.latest_tree_hash)
+                .await?;
             self.latest_size = tsize;
             self.latest_tree_hash = thash;
             info!(
@@ -726,12 +1558,17 @@ impl CTClient {
         Ok(())
     }
 
-    /// Serialize the state of this client into bytes
+    /// Serialize the state of this client into bytes, including the compact range of the last
+    /// checked tree head if there is one (see [`Self::get_compact_range`]).
     pub fn as_bytes(&self) -> Result<Vec<u8>, Error> {
         // Scheme: (All integers are in big-endian, fixed array don't specify length)
-        // [Version: u8] [base_url in UTF-8] 0x00 [tree_size: u64] [tree_hash: [u8; 32]] [len of pub_key: u32] [pub_key: [u8]: DER public key for this log] [sha256 of everything seen before: [u8; 32]]
+        // [Version: u8] [api: u8, 0 = RFC 6962, 1 = Static CT] [base_url in UTF-8] 0x00 [tree_size: u64] [tree_hash: [u8; 32]] [len of pub_key: u32] [pub_key: [u8]: DER public key for this log] [has compact range: u8] [compact range nodes: [[u8; 32]; number of 1 bits in tree_size], only if has compact range is 1] [sha256 of everything seen before: [u8; 32]]
         let mut v = Vec::new();
-        v.push(0u8); // Version = development
+        v.push(2u8); // Version
+        v.push(match self.api {
+            LogApi::Rfc6962 => 0u8,
+            LogApi::StaticCt => 1u8,
+        });
         let url_bytes = self.base_url.as_str().as_bytes();
         assert!(!url_bytes.contains(&0u8));
         v.extend_from_slice(url_bytes);
@@ -746,6 +1583,15 @@ impl CTClient {
         assert!(pub_key.len() < u32::MAX as usize);
         v.extend_from_slice(&u32::to_be_bytes(pub_key.len() as u32));
         v.extend_from_slice(&pub_key);
+        match self.get_compact_range() {
+            Some(range) => {
+                v.push(1u8);
+                for node in range.nodes() {
+                    v.extend_from_slice(node);
+                }
+            }
+            None => v.push(0u8),
+        }
         v.extend_from_slice(&utils::sha256(&v));
         Ok(v)
     }
@@ -762,11 +1608,26 @@ impl CTClient {
         }
         let version = input[0];
         input = &input[1..];
-        if version != 0 {
+        if version > 2 {
             return Err(Error::InvalidArgument(
                 "The bytes are encoded by a ctclient of higher version.".to_owned(),
             ));
         }
+        // Version 0 predates Static CT support.
+        let api = if version == 0 {
+            LogApi::Rfc6962
+        } else {
+            if input.is_empty() {
+                return e_inval();
+            }
+            let api = match input[0] {
+                0 => LogApi::Rfc6962,
+                1 => LogApi::StaticCt,
+                _ => return e_inval(),
+            };
+            input = &input[1..];
+            api
+        };
         let base_url_len = match input.iter().position(|x| *x == 0) {
             Some(k) => k,
             None => return e_inval(),
@@ -794,6 +1655,32 @@ impl CTClient {
         }
         let pub_key = &input[..len_pub_key as usize];
         input = &input[len_pub_key as usize..];
+        // Version 1 predates compact ranges.
+        let mut compact_range_nodes = None;
+        if version >= 2 {
+            if input.is_empty() {
+                return e_inval();
+            }
+            let has_compact_range = input[0];
+            input = &input[1..];
+            match has_compact_range {
+                0 => {}
+                1 => {
+                    let nb_nodes = tree_size.count_ones() as usize;
+                    if input.len() < nb_nodes * 32 {
+                        return e_inval();
+                    }
+                    compact_range_nodes = Some(
+                        input[..nb_nodes * 32]
+                            .chunks_exact(32)
+                            .map(|c| c.try_into().unwrap())
+                            .collect::<Vec<[u8; 32]>>(),
+                    );
+                    input = &input[nb_nodes * 32..];
+                }
+                _ => return e_inval(),
+            }
+        }
         if input.len() < 32 {
             return e_inval();
         }
@@ -809,6 +1696,16 @@ impl CTClient {
                 return e_inval();
             }
         }
+        let compact_range = match compact_range_nodes {
+            Some(nodes) => {
+                let range = internal::CompactRange::from_nodes(tree_size, nodes)?;
+                if range.root() != tree_hash {
+                    return e_inval();
+                }
+                Some(range)
+            }
+            None => None,
+        };
         let pub_key = openssl::pkey::PKey::<openssl::pkey::Public>::public_key_from_der(pub_key)
             .map_err(|e| Error::InvalidArgument(format!("Can't parse public key: {}", &e)))?;
         Ok(CTClient {
@@ -816,8 +1713,19 @@ impl CTClient {
                 .map_err(|e| Error::InvalidArgument(format!("Unable to parse base_url: {}", &e)))?,
             pub_key,
             http_client: new_http_client()?,
+            api,
             latest_size: tree_size,
             latest_tree_hash: tree_hash,
+            accepted_roots: None,
+            reject_unaccepted_roots: false,
+            witness_policy: None,
+            witnessed_checkpoint_url: None,
+            log_state: None,
+            final_tree_head: None,
+            compact_range,
+            commit_interval: None,
+            get_entries_batch_size: 500,
+            get_entries_parallelism: 1,
         })
     }
 }
@@ -825,12 +1733,13 @@ impl CTClient {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use base64::prelude::{BASE64_STANDARD, Engine as _};
 
     #[tokio::test]
     async fn as_bytes_test() {
         let c = CTClient::new_from_latest_th("https://ct.googleapis.com/logs/argon2019/", &utils::hex_to_u8("3059301306072a8648ce3d020106082a8648ce3d030107034200042373109be1f35ef6986b6995961078ce49dbb404fc712c5a92606825c04a1aa1b0612d1b8714a9baf00133591d0530e94215e755d72af8b4a2ba45c946918756")).await.unwrap();
         let mut bytes = c.as_bytes().unwrap();
-        println!("bytes: {}", &base64::encode(&bytes));
+        println!("bytes: {}", &BASE64_STANDARD.encode(&bytes));
         let mut c_clone = CTClient::from_bytes(&bytes).unwrap();
         assert_eq!(c.latest_size, c_clone.latest_size);
         assert_eq!(c.latest_tree_hash, c_clone.latest_tree_hash);
@@ -840,7 +1749,467 @@ mod tests {
         bytes[len - 1] ^= 1;
         CTClient::from_bytes(&bytes).expect_err("");
     }
+
+    #[tokio::test]
+    async fn log_state_test() {
+        use google_log_list::{FinalTreeHead, LogState};
+        let key = PKey::ec_gen("prime256v1").unwrap();
+        let mut c = CTClient {
+            base_url: reqwest::Url::parse("https://ct.invalid/").unwrap(),
+            pub_key: PKey::public_key_from_der(&key.public_key_to_der().unwrap()).unwrap(),
+            http_client: new_http_client().unwrap(),
+            api: LogApi::Rfc6962,
+            latest_size: 1000,
+            latest_tree_hash: [0x33; 32],
+            accepted_roots: None,
+            reject_unaccepted_roots: false,
+            witness_policy: None,
+            witnessed_checkpoint_url: None,
+            log_state: None,
+            final_tree_head: None,
+            compact_range: None,
+            commit_interval: None,
+            get_entries_batch_size: 500,
+            get_entries_parallelism: 1,
+        };
+        let fth = |tree_size, root_hash| {
+            Some(FinalTreeHead {
+                tree_size,
+                root_hash,
+            })
+        };
+        c.set_log_state(Some(LogState::Readonly), fth(999, [0x33; 32]))
+            .expect_err("");
+        c.set_log_state(Some(LogState::Readonly), fth(1000, [0x34; 32]))
+            .expect_err("");
+        assert_eq!(c.get_log_state(), (None, None));
+        c.set_log_state(Some(LogState::Readonly), fth(1000, [0x33; 32]))
+            .unwrap();
+        c.set_log_state(Some(LogState::Readonly), fth(1001, [0x33; 32]))
+            .unwrap();
+
+        c.set_log_state(Some(LogState::Retired), None).unwrap();
+        assert!(matches!(
+            c.light_update().await,
+            SthResult::Err(Error::LogRetired)
+        ));
+    }
+
+    #[test]
+    fn compact_range_bytes_test() {
+        let key = test_logs::key_der(0);
+        let mut range = internal::CompactRange::new();
+        for i in 0..11u8 {
+            range.append(&utils::sha256(&[i]));
+        }
+        let mut c =
+            CTClient::new_from_perv_tree_hash("https://ct.invalid/", &key, range.root(), 11)
+                .unwrap();
+        assert!(c.get_compact_range().is_none());
+        let bytes = c.as_bytes().unwrap();
+        assert!(
+            CTClient::from_bytes(&bytes)
+                .unwrap()
+                .get_compact_range()
+                .is_none()
+        );
+
+        c.set_compact_range(internal::CompactRange::new())
+            .expect_err("");
+        c.set_compact_range(range.clone()).unwrap();
+        let bytes = c.as_bytes().unwrap();
+        let c_clone = CTClient::from_bytes(&bytes).unwrap();
+        assert_eq!(c_clone.get_compact_range(), Some(&range));
+        assert_eq!(c_clone.get_checked_tree_head(), (11, range.root()));
+    }
+
+    #[tokio::test]
+    async fn resumable_update_test() {
+        let log = mock_log::MockLog::new(1000);
+
+        let mut c = log.client();
+        let mut nb_leaves = 0;
+        let sth = c.update(Some(|_: &[X509]| nb_leaves += 1)).await.unwrap();
+        assert_eq!(nb_leaves, 1000);
+        assert_eq!(c.get_checked_tree_head(), (1000, sth.root_hash));
+
+        // Leaves 400 to 449 are checked, but never committed, so not passed to the handler.
+        log.set_fail_entries_from(Some(450));
+        let mut c = log.client();
+        c.set_commit_interval(Some(100));
+        c.set_get_entries_batch_size(25);
+        let mut handled = Vec::new();
+        let mut commits = Vec::new();
+        let mut saved = None;
+        c.update_with_commit_hook(
+            Some(|index: u64, _: &[X509]| handled.push(index)),
+            |c: &CTClient| {
+                commits.push(c.get_checked_tree_head().0);
+                saved = Some(c.as_bytes()?);
+                Ok(())
+            },
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(handled, (0..400).collect::<Vec<u64>>());
+        assert_eq!(commits, vec![100, 200, 300, 400]);
+        assert_eq!(c.get_checked_tree_head().0, 400);
+
+        log.set_fail_entries_from(None);
+        let mut c = CTClient::from_bytes(&saved.unwrap()).unwrap();
+        assert_eq!(c.get_compact_range().unwrap().size(), 400);
+        c.set_commit_interval(Some(300));
+        let nb_requests = log.requests().len();
+        let mut commits = Vec::new();
+        let sth = c
+            .update_with_commit_hook(
+                Some(|index: u64, _: &[X509]| handled.push(index)),
+                |c: &CTClient| {
+                    commits.push(c.get_checked_tree_head().0);
+                    Ok(())
+                },
+            )
+            .await
+            .unwrap();
+        // Each leaf was handled exactly once across both runs.
+        assert_eq!(handled, (0..1000).collect::<Vec<u64>>());
+        assert_eq!(commits, vec![700, 1000]);
+        assert_eq!(c.get_checked_tree_head(), (1000, sth.root_hash));
+        assert_eq!(
+            log.requests()[nb_requests + 1],
+            "ct/v1/get-entries?start=400&end=699"
+        );
+
+        // Without an interval, leaves are still held for at most the default one.
+        let log = mock_log::MockLog::new(2500);
+        let mut c = log.client();
+        let mut commits = Vec::new();
+        c.update_with_commit_hook(Some(|_: u64, _: &[X509]| {}), |c: &CTClient| {
+            commits.push(c.get_checked_tree_head().0);
+            Ok(())
+        })
+        .await
+        .unwrap();
+        assert_eq!(commits, vec![1000, 2000, 2500]);
+    }
+
+    #[tokio::test]
+    async fn parallel_update_test() {
+        let log = mock_log::MockLog::new(1000);
+        let mut c = log.client();
+        c.set_get_entries_batch_size(64);
+        c.set_get_entries_parallelism(4);
+        let mut nb_leaves = 0;
+        let sth = c.update(Some(|_: &[X509]| nb_leaves += 1)).await.unwrap();
+        assert_eq!(nb_leaves, 1000);
+        assert_eq!(c.get_checked_tree_head(), (1000, sth.root_hash));
+        let mut starts: Vec<u64> = log
+            .requests()
+            .iter()
+            .filter_map(|r| r.strip_prefix("ct/v1/get-entries?start="))
+            .map(|r| r.split('&').next().unwrap().parse().unwrap())
+            .collect();
+        starts.sort_unstable();
+        assert_eq!(starts, (0..1000).step_by(64).collect::<Vec<u64>>());
+    }
+
+    #[tokio::test]
+    async fn add_chain_test() {
+        let log = mock_log::MockLog::new(5);
+        let c = log.client();
+        let chain = X509::stack_from_pem(include_bytes!("test_data/sct_leaf.pem")).unwrap();
+        let sct = c.add_chain(&chain).await.unwrap();
+        assert_eq!(sct.log_id, utils::sha256(&log.pub_key));
+        assert_eq!(sct.timestamp, 1735689600005);
+        assert_eq!(
+            sct.derive_leaf_hash(),
+            internal::leaf_hash_constructors::with_x509(
+                &chain[0].to_der().unwrap(),
+                sct.timestamp,
+                &[]
+            )
+        );
+        c.add_chain(&[]).await.expect_err("");
+
+        // The log rewrites the TBS to be issued by the CA behind the precertificate signing
+        // certificate, and so must we to verify the SCT.
+        let chain =
+            X509::stack_from_pem(include_bytes!("test_data/precert-signing-ca.pem")).unwrap();
+        let sct = c.add_pre_chain(&chain).await.unwrap();
+        match &sct.entry {
+            SctEntry::PreCert { tbs, .. } => assert_eq!(
+                &tbs[..],
+                &include_bytes!("test_data/precert-signing.ca.tbs")[..]
+            ),
+            SctEntry::X509(_) => panic!("Expected a precert entry."),
+        }
+        assert!(matches!(
+            c.add_pre_chain(&chain[..2]).await,
+            Err(Error::InvalidArgument(_))
+        ));
+        assert_eq!(
+            log.requests()
+                .iter()
+                .filter(|r| r.starts_with("ct/v1/add-"))
+                .collect::<Vec<_>>(),
+            ["ct/v1/add-chain", "ct/v1/add-pre-chain"]
+        );
+
+        // A SCT from another log is refused.
+        let other = mock_log::MockLog::new(5);
+        let c = CTClient::new_from_perv_tree_hash(&log.url, &other.pub_key, [0u8; 32], 0).unwrap();
+        let chain = X509::stack_from_pem(include_bytes!("test_data/sct_leaf.pem")).unwrap();
+        assert!(matches!(c.add_chain(&chain).await, Err(Error::BadSct(_))));
+    }
+
+    #[tokio::test]
+    async fn accepted_roots_test() {
+        let log = mock_log::MockLog::new(5);
+        let get_roots = |certs: &[&X509]| {
+            let certificates = certs
+                .iter()
+                .map(|c| BASE64_STANDARD.encode(c.to_der().unwrap()))
+                .collect();
+            serde_json::to_vec(&jsons::GetRoots { certificates }).unwrap()
+        };
+        // The mock leaves are issued by the CA in sct_leaf.pem, the other root is unrelated.
+        let ca = X509::stack_from_pem(include_bytes!("test_data/sct_leaf.pem")).unwrap()[1].clone();
+        let other = X509::stack_from_pem(include_bytes!("test_data/precert-signing-ca.pem"))
+            .unwrap()
+            .pop()
+            .unwrap();
+
+        let mut c = log.client();
+        c.set_reject_unaccepted_roots(true);
+        assert!(matches!(
+            c.update(Some(|_: &[X509]| {})).await,
+            SthResult::ErrWithSth(Error::InvalidArgument(_), _)
+        ));
+
+        log.serve("ct/v1/get-roots", get_roots(&[&other]));
+        assert!(c.fetch_accepted_roots().await.unwrap().is_none());
+        assert_eq!(c.get_accepted_roots().unwrap().len(), 1);
+        match c.update(Some(|_: &[X509]| {})).await {
+            SthResult::ErrWithSth(Error::RootNotAccepted(_), sth) => assert_eq!(sth.tree_size, 5),
+            other => panic!("Expected RootNotAccepted, got {:?}", other),
+        }
+        assert_eq!(c.get_checked_tree_head().0, 0);
+
+        // Without the option, the root is not looked at.
+        c.set_reject_unaccepted_roots(false);
+        let mut nb_leaves = 0;
+        c.update(Some(|_: &[X509]| nb_leaves += 1)).await.unwrap();
+        assert_eq!(nb_leaves, 5);
+
+        log.serve("ct/v1/get-roots", get_roots(&[&other, &ca]));
+        let change = c.fetch_accepted_roots().await.unwrap().unwrap();
+        assert_eq!(change.added.len(), 1);
+        assert_eq!(change.added[0].to_der().unwrap(), ca.to_der().unwrap());
+        assert!(change.removed.is_empty());
+        let mut c = log.client();
+        c.set_accepted_roots(Some(
+            internal::AcceptedRoots::from_der_certs([ca.to_der().unwrap()]).unwrap(),
+        ));
+        c.set_reject_unaccepted_roots(true);
+        c.update(Some(|_: &[X509]| {})).await.unwrap();
+        assert_eq!(c.get_checked_tree_head().0, 5);
+    }
+
+    #[tokio::test]
+    async fn entry_and_proof_test() {
+        let log = mock_log::MockLog::new(1000);
+        let c = CTClient::new_from_latest_th(&log.url, &log.pub_key)
+            .await
+            .unwrap();
+        let (tree_size, tree_hash) = c.get_checked_tree_head();
+        let check = |leaf_index| {
+            internal::check_entry_and_proof(
+                c.get_reqwest_client(),
+                &c.base_url,
+                tree_size,
+                &tree_hash,
+                leaf_index,
+            )
+        };
+        for leaf_index in [0, 1, 500, 999] {
+            let leaf = check(leaf_index).await.unwrap();
+            assert_eq!(leaf.timestamp, 1735689600000 + leaf_index);
+        }
+        check(1000).await.unwrap_err();
+
+        log.set_tamper_proofs(true);
+        assert!(matches!(
+            check(500).await,
+            Err(Error::InvalidInclusionProof {
+                leaf_index: 500,
+                ..
+            })
+        ));
+        log.set_tamper_proofs(false);
+        // A valid proof for another leaf than the one returned.
+        log.set_wrong_entries(true);
+        assert!(matches!(
+            check(500).await,
+            Err(Error::InvalidInclusionProof {
+                leaf_index: 500,
+                ..
+            })
+        ));
+    }
+
+    #[tokio::test]
+    async fn witness_policy_test() {
+        let log = mock_log::MockLog::new(10);
+        let names = ["w0.example", "w1.example", "w2.example"];
+        let keys: Vec<_> = (0..3).map(|_| PKey::generate_ed25519().unwrap()).collect();
+        let witnesses = names
+            .iter()
+            .zip(keys.iter())
+            .map(|(n, k)| checkpoint::WitnessKey::new(n, &k.raw_public_key().unwrap()).unwrap())
+            .collect();
+        let policy = checkpoint::WitnessPolicy::new(witnesses, 2).unwrap();
+        let checkpoint_url = format!("{}checkpoint", log.url);
+        let mut c = log.client();
+        // RFC 6962 logs don't serve checkpoints.
+        c.set_witness_policy(Some(policy.clone()), None)
+            .expect_err("");
+        c.set_witness_policy(Some(policy.clone()), Some("not a url"))
+            .expect_err("");
+        c.set_witness_policy(Some(policy), Some(&checkpoint_url))
+            .unwrap();
+
+        // Not enough witnesses. The invalid cosignature is ignored.
+        log.add_witness(names[0], keys[0].clone(), true);
+        log.add_witness(names[1], keys[1].clone(), false);
+        match c.update(Some(|_: &[X509]| {})).await {
+            SthResult::ErrWithSth(Error::NotWitnessed(_), sth) => assert_eq!(sth.tree_size, 10),
+            other => panic!("Expected NotWitnessed, got {:?}", other),
+        }
+        assert_eq!(c.get_checked_tree_head().0, 0);
+
+        log.add_witness(names[2], keys[2].clone(), true);
+        let sth = c.update(Some(|_: &[X509]| {})).await.unwrap();
+        assert_eq!(c.get_checked_tree_head(), (10, sth.root_hash));
+        assert!(log.requests().iter().all(|r| r != "ct/v1/get-sth"));
+
+        // Without a policy, the log's own tree head is trusted again.
+        c.set_witness_policy(None, None).unwrap();
+        c.light_update().await.unwrap();
+        assert!(log.requests().iter().any(|r| r == "ct/v1/get-sth"));
+    }
+
+    #[tokio::test]
+    async fn short_batch_test() {
+        let log = mock_log::MockLog::new(1000);
+        log.set_max_batch(37);
+        let mut c = log.client();
+        c.set_get_entries_batch_size(100);
+        let mut nb_leaves = 0;
+        let sth = c.update(Some(|_: &[X509]| nb_leaves += 1)).await.unwrap();
+        assert_eq!(nb_leaves, 1000);
+        assert_eq!(c.get_checked_tree_head(), (1000, sth.root_hash));
+        let requests = log.requests();
+        let ranges: Vec<&str> = requests
+            .iter()
+            .filter_map(|r| r.strip_prefix("ct/v1/get-entries?"))
+            .collect();
+        // Each request starts from the first entry not returned yet.
+        assert_eq!(
+            ranges[..4],
+            [
+                "start=0&end=99",
+                "start=37&end=136",
+                "start=74&end=173",
+                "start=111&end=210"
+            ]
+        );
+
+        let nb_requests = requests.len();
+        let mut c = log.client();
+        c.set_get_entries_batch_size(100);
+        c.set_get_entries_parallelism(4);
+        let mut nb_leaves = 0;
+        let sth = c.update(Some(|_: &[X509]| nb_leaves += 1)).await.unwrap();
+        assert_eq!(nb_leaves, 1000);
+        assert_eq!(c.get_checked_tree_head(), (1000, sth.root_hash));
+        // Once the first batch got a short response, batches are planned with the log's limit.
+        let requests = log.requests();
+        assert!(
+            requests[nb_requests..].contains(&"ct/v1/get-entries?start=407&end=443".to_owned())
+        );
+
+        log.set_max_batch(1000);
+        log.set_overfill_entries(true);
+        for parallelism in [1, 4] {
+            let mut c = log.client();
+            c.set_get_entries_batch_size(100);
+            c.set_get_entries_parallelism(parallelism);
+            match c.update(Some(|_: &[X509]| {})).await {
+                SthResult::ErrWithSth(Error::MalformedResponseBody(_), _) => {}
+                r => panic!("Expected a malformed response, got {:?}", r),
+            }
+            assert_eq!(c.get_checked_tree_head().0, 0);
+        }
+    }
+
+    /// Parse the `get-entries` requests made to `log` after the first `skip` requests.
+    fn get_entries_requests(log: &mock_log::MockLog, skip: usize) -> Vec<(u64, u64)> {
+        log.requests()[skip..]
+            .iter()
+            .filter_map(|r| r.strip_prefix("ct/v1/get-entries?start="))
+            .map(|r| {
+                let (start, end) = r.split_once("&end=").unwrap();
+                (start.parse().unwrap(), end.parse().unwrap())
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn aligned_batch_test() {
+        // The log returns at most 64 entries, and stops at multiples of 64.
+        let log = mock_log::MockLog::new(1000);
+        log.set_max_batch(64);
+        log.set_align_batches(true);
+        let mut c = log.client();
+        c.set_commit_interval(Some(300));
+        c.set_get_entries_batch_size(100);
+        let mut nb_leaves = 0;
+        let sth = c.update(Some(|_: &[X509]| nb_leaves += 1)).await.unwrap();
+        assert_eq!(nb_leaves, 1000);
+        assert_eq!(c.get_checked_tree_head(), (1000, sth.root_hash));
+        let requests = get_entries_requests(&log, 0);
+        assert_eq!(
+            requests[..5],
+            [(0, 99), (64, 163), (128, 227), (192, 291), (256, 299)]
+        );
+        // Requests never get smaller because of a response cut at a multiple of 64.
+        for (start, end) in requests {
+            let chunk_end = ((start / 300 + 1) * 300).min(1000);
+            assert_eq!(end + 1, (start + 100).min(chunk_end), "{}", start);
+        }
+
+        let nb_requests = log.requests().len();
+        let mut c = log.client();
+        c.set_get_entries_batch_size(100);
+        c.set_get_entries_parallelism(4);
+        let mut nb_leaves = 0;
+        let sth = c.update(Some(|_: &[X509]| nb_leaves += 1)).await.unwrap();
+        assert_eq!(nb_leaves, 1000);
+        assert_eq!(c.get_checked_tree_head(), (1000, sth.root_hash));
+        // The first batches are cut at 28, 56 and 20 entries, but the limit is learned as 64, and
+        // batches planned after that line up with the log.
+        let requests = get_entries_requests(&log, nb_requests);
+        assert!(requests.contains(&(400, 447)));
+        for (start, end) in requests.into_iter().filter(|(start, _)| *start >= 448) {
+            assert_eq!(start % 64, 0);
+            assert_eq!(end, (start + 63).min(999));
+        }
+    }
 }
 
 #[cfg(test)]
 mod long_tests;
+#[cfg(test)]
+mod mock_log;
+#[cfg(test)]
+mod test_logs;
@@ -0,0 +1,489 @@
+//! A small RFC 6962 log served over plain HTTP on localhost, to test [`CTClient`] without the
+//! network. All leaves are the same certificate with different timestamps.
+
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::{Arc, Mutex};
+
+use base64::prelude::{BASE64_STANDARD, Engine as _};
+use openssl::pkey::{PKey, Private};
+use openssl::x509::X509;
+
+use crate::checkpoint::{
+    NoteSignature, SIG_TYPE_COSIGNATURE, SignedNote, cosignature_message, key_id,
+};
+use crate::internal::{
+    CompactRange, SignatureAlgorithm, consistency_proof_parts, encode_dss, inclusion_proof_parts,
+};
+use crate::{CTClient, SctEntry, SignedCertificateTimestamp, SignedTreeHead, jsons, utils};
+
+struct MockState {
+    key: PKey<Private>,
+    cert: Vec<u8>,
+    leaves: Vec<(Vec<u8>, Vec<u8>)>,
+    leaf_hashes: Vec<[u8; 32]>,
+    tree_size: u64,
+    max_batch: u64,
+    align_batches: bool,
+    overfill_entries: bool,
+    fail_entries_from: Option<u64>,
+    fail_proofs: bool,
+    tamper_proofs: bool,
+    wrong_entries: bool,
+    witnesses: Vec<(String, PKey<Private>, bool)>,
+    files: HashMap<String, Vec<u8>>,
+    requests: Vec<String>,
+}
+
+pub struct MockLog {
+    pub url: String,
+    pub pub_key: Vec<u8>,
+    state: Arc<Mutex<MockState>>,
+}
+
+fn u24(n: usize) -> [u8; 3] {
+    let b = (n as u32).to_be_bytes();
+    [b[1], b[2], b[3]]
+}
+
+impl MockLog {
+    /// Start a log with `nb_leaves` leaves, all in its tree head.
+    pub fn new(nb_leaves: u64) -> Self {
+        let chain = X509::stack_from_pem(include_bytes!("test_data/sct_leaf.pem")).unwrap();
+        let cert = chain[0].to_der().unwrap();
+        let ca = chain[1].to_der().unwrap();
+        let mut extra_data = u24(3 + ca.len()).to_vec();
+        extra_data.extend_from_slice(&u24(ca.len()));
+        extra_data.extend_from_slice(&ca);
+        let mut leaves = Vec::new();
+        let mut leaf_hashes = Vec::new();
+        for i in 0..nb_leaves {
+            let mut leaf_input = vec![0u8, 0u8];
+            leaf_input.extend_from_slice(&(1735689600000 + i).to_be_bytes());
+            leaf_input.extend_from_slice(&[0u8, 0u8]);
+            leaf_input.extend_from_slice(&u24(cert.len()));
+            leaf_input.extend_from_slice(&cert);
+            leaf_input.extend_from_slice(&[0u8, 0u8]);
+            let mut hash_data = vec![0u8];
+            hash_data.extend_from_slice(&leaf_input);
+            leaf_hashes.push(utils::sha256(&hash_data));
+            leaves.push((leaf_input, extra_data.clone()));
+        }
+        let key = PKey::ec_gen("prime256v1").unwrap();
+        let pub_key = key.public_key_to_der().unwrap();
+        let state = Arc::new(Mutex::new(MockState {
+            key,
+            cert,
+            leaves,
+            leaf_hashes,
+            tree_size: nb_leaves,
+            max_batch: 1000,
+            align_batches: false,
+            overfill_entries: false,
+            fail_entries_from: None,
+            fail_proofs: false,
+            tamper_proofs: false,
+            wrong_entries: false,
+            witnesses: Vec::new(),
+            files: HashMap::new(),
+            requests: Vec::new(),
+        }));
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/", listener.local_addr().unwrap());
+        let server_state = state.clone();
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                let state = server_state.clone();
+                std::thread::spawn(move || handle(stream.unwrap(), &state));
+            }
+        });
+        MockLog {
+            url,
+            pub_key,
+            state,
+        }
+    }
+
+    /// A client that has not checked any leaf yet.
+    pub fn client(&self) -> CTClient {
+        CTClient::new_from_perv_tree_hash(&self.url, &self.pub_key, utils::sha256(b""), 0).unwrap()
+    }
+
+    /// Return at most `max_batch` entries from get-entries.
+    pub fn set_max_batch(&self, max_batch: u64) {
+        self.state.lock().unwrap().max_batch = max_batch;
+    }
+
+    /// Make get-entries stop at multiples of `max_batch`, like some logs do.
+    pub fn set_align_batches(&self, align: bool) {
+        self.state.lock().unwrap().align_batches = align;
+    }
+
+    /// Make get-entries return one more entry than asked for, when there is one.
+    pub fn set_overfill_entries(&self, overfill: bool) {
+        self.state.lock().unwrap().overfill_entries = overfill;
+    }
+
+    /// Make get-entries fail for ranges including `index` or anything after it.
+    pub fn set_fail_entries_from(&self, index: Option<u64>) {
+        self.state.lock().unwrap().fail_entries_from = index;
+    }
+
+    /// Make get-proof-by-hash fail.
+    pub fn set_fail_proofs(&self, fail: bool) {
+        self.state.lock().unwrap().fail_proofs = fail;
+    }
+
+    /// Make get-entry-and-proof flip a bit in the first hash of the proof.
+    pub fn set_tamper_proofs(&self, tamper: bool) {
+        self.state.lock().unwrap().tamper_proofs = tamper;
+    }
+
+    /// Make get-entry-and-proof return the next leaf along with the proof for the requested one.
+    pub fn set_wrong_entries(&self, wrong: bool) {
+        self.state.lock().unwrap().wrong_entries = wrong;
+    }
+
+    /// Cosign the checkpoint served at `checkpoint` as the witness `name` with the Ed25519 `key`.
+    /// If not `valid`, the cosignature is over another checkpoint.
+    pub fn add_witness(&self, name: &str, key: PKey<Private>, valid: bool) {
+        self.state
+            .lock()
+            .unwrap()
+            .witnesses
+            .push((name.to_owned(), key, valid));
+    }
+
+    /// Serve `body` at `path`, for what the log itself does not serve, like the tiles of a Static
+    /// CT log.
+    pub fn serve(&self, path: &str, body: Vec<u8>) {
+        self.state
+            .lock()
+            .unwrap()
+            .files
+            .insert(path.to_owned(), body);
+    }
+
+    /// A SCT signed by this log for its certificate at `timestamp`. The leaf `i` of the log has
+    /// the timestamp `1735689600000 + i`.
+    pub fn sct(&self, timestamp: u64) -> SignedCertificateTimestamp {
+        let state = self.state.lock().unwrap();
+        sign_sct(&state, SctEntry::X509(state.cert.clone()), timestamp)
+    }
+
+    /// Paths requested so far, without the leading `/`.
+    pub fn requests(&self) -> Vec<String> {
+        self.state.lock().unwrap().requests.clone()
+    }
+}
+
+fn tree_hash(leaf_hashes: &[[u8; 32]]) -> [u8; 32] {
+    let mut range = CompactRange::new();
+    for h in leaf_hashes {
+        range.append(h);
+    }
+    range.root()
+}
+
+fn query_param(query: &str, name: &str) -> u64 {
+    query
+        .split('&')
+        .find_map(|kv| kv.strip_prefix(name)?.strip_prefix('='))
+        .unwrap()
+        .parse()
+        .unwrap()
+}
+
+/// Sign a SCT for `entry`. The signed data is laid out like the leaf, with the signature type
+/// instead of the leaf type, both 0.
+fn sign_sct(state: &MockState, entry: SctEntry, timestamp: u64) -> SignedCertificateTimestamp {
+    let mut signed_data = vec![0u8, 0u8];
+    signed_data.extend_from_slice(&timestamp.to_be_bytes());
+    match &entry {
+        SctEntry::X509(der) => {
+            signed_data.extend_from_slice(&[0u8, 0u8]);
+            signed_data.extend_from_slice(&u24(der.len()));
+            signed_data.extend_from_slice(der);
+        }
+        SctEntry::PreCert {
+            tbs,
+            issuer_key_hash,
+        } => {
+            signed_data.extend_from_slice(&[0u8, 1u8]);
+            signed_data.extend_from_slice(issuer_key_hash);
+            signed_data.extend_from_slice(&u24(tbs.len()));
+            signed_data.extend_from_slice(tbs);
+        }
+    }
+    signed_data.extend_from_slice(&[0u8, 0u8]);
+    let mut signer =
+        openssl::sign::Signer::new(openssl::hash::MessageDigest::sha256(), &state.key).unwrap();
+    signer.update(&signed_data).unwrap();
+    SignedCertificateTimestamp {
+        log_id: utils::sha256(&state.key.public_key_to_der().unwrap()),
+        timestamp,
+        extensions_data: Vec::new(),
+        entry,
+        signature_algorithm: SignatureAlgorithm::Sha256Ecdsa,
+        raw_signature: signer.sign_to_vec().unwrap(),
+    }
+}
+
+/// The entry an `add-chain` or `add-pre-chain` request with `body` is for. The only
+/// precertificate known is the one in `test_data/precert-signing-ca.pem`, whose TBS as rewritten
+/// by a log is in `test_data/precert-signing.ca.tbs`.
+fn submitted_entry(endpoint: &str, body: &[u8]) -> Option<SctEntry> {
+    let request: jsons::AddChainRequest = serde_json::from_slice(body).ok()?;
+    let chain = request
+        .chain
+        .iter()
+        .map(|c| BASE64_STANDARD.decode(c).ok())
+        .collect::<Option<Vec<_>>>()?;
+    match endpoint {
+        "ct/v1/add-chain" => Some(SctEntry::X509(chain.first()?.clone())),
+        _ => {
+            let precert_chain =
+                X509::stack_from_pem(include_bytes!("test_data/precert-signing-ca.pem")).unwrap();
+            let known = precert_chain.iter().map(|c| c.to_der().unwrap());
+            if !chain.iter().cloned().eq(known) {
+                return None;
+            }
+            Some(SctEntry::PreCert {
+                tbs: include_bytes!("test_data/precert-signing.ca.tbs").to_vec(),
+                issuer_key_hash: utils::sha256(
+                    &precert_chain[2]
+                        .public_key()
+                        .unwrap()
+                        .public_key_to_der()
+                        .unwrap(),
+                ),
+            })
+        }
+    }
+}
+
+const CHECKPOINT_ORIGIN: &str = "mock.example/log";
+
+/// The current tree head, signed by the log.
+fn signed_tree_head(state: &MockState) -> SignedTreeHead {
+    let tree_size = state.tree_size;
+    let timestamp = 1735689600000 + tree_size;
+    let root_hash = tree_hash(&state.leaf_hashes[..tree_size as usize]);
+    let mut body = vec![0u8, 1u8];
+    body.extend_from_slice(&timestamp.to_be_bytes());
+    body.extend_from_slice(&tree_size.to_be_bytes());
+    body.extend_from_slice(&root_hash);
+    let mut signer =
+        openssl::sign::Signer::new(openssl::hash::MessageDigest::sha256(), &state.key).unwrap();
+    signer.update(&body).unwrap();
+    SignedTreeHead {
+        tree_size,
+        timestamp,
+        root_hash,
+        signature: encode_dss(
+            SignatureAlgorithm::Sha256Ecdsa,
+            &signer.sign_to_vec().unwrap(),
+        )
+        .unwrap(),
+    }
+}
+
+/// The response body, or the status line of an error.
+fn respond(state: &Mutex<MockState>, path: &str, body: &[u8]) -> Result<Vec<u8>, &'static str> {
+    let mut state = state.lock().unwrap();
+    state.requests.push(path.to_owned());
+    if let Some(file) = state.files.get(path) {
+        return Ok(file.clone());
+    }
+    let (endpoint, query) = path.split_once('?').unwrap_or((path, ""));
+    let json = match endpoint {
+        "ct/v1/get-sth" => {
+            let sth = signed_tree_head(&state);
+            serde_json::to_string(&jsons::STH {
+                tree_size: sth.tree_size,
+                timestamp: sth.timestamp,
+                sha256_root_hash: BASE64_STANDARD.encode(sth.root_hash),
+                tree_head_signature: BASE64_STANDARD.encode(sth.signature),
+            })
+            .map_err(|_| INTERNAL_ERROR)
+        }
+        "checkpoint" => {
+            let pub_key =
+                PKey::public_key_from_der(&state.key.public_key_to_der().unwrap()).unwrap();
+            let mut note =
+                SignedNote::from_sth(CHECKPOINT_ORIGIN, &signed_tree_head(&state), &pub_key)
+                    .unwrap();
+            for (name, key, valid) in state.witnesses.iter() {
+                let timestamp = 1735689600u64;
+                let text = if *valid {
+                    note.text.clone()
+                } else {
+                    note.text.replace(CHECKPOINT_ORIGIN, "other.example/log")
+                };
+                let mut signature = timestamp.to_be_bytes().to_vec();
+                signature.extend_from_slice(
+                    &openssl::sign::Signer::new_without_digest(key)
+                        .unwrap()
+                        .sign_oneshot_to_vec(&cosignature_message(&text, timestamp))
+                        .unwrap(),
+                );
+                note.signatures.push(NoteSignature {
+                    name: name.clone(),
+                    key_id: key_id(name, SIG_TYPE_COSIGNATURE, &key.raw_public_key().unwrap()),
+                    signature,
+                });
+            }
+            Ok(note.to_string())
+        }
+        "ct/v1/get-sth-consistency" => {
+            let first = query_param(query, "first");
+            let second = query_param(query, "second");
+            let mut parts = consistency_proof_parts(first, second);
+            if first.is_power_of_two() {
+                parts.remove(0);
+            }
+            let consistency = parts
+                .into_iter()
+                .map(|(start, end)| {
+                    BASE64_STANDARD
+                        .encode(tree_hash(&state.leaf_hashes[start as usize..end as usize]))
+                })
+                .collect();
+            serde_json::to_string(&jsons::ConsistencyProof { consistency })
+                .map_err(|_| INTERNAL_ERROR)
+        }
+        "ct/v1/get-entries" => {
+            let start = query_param(query, "start");
+            let max_end = if state.align_batches {
+                (start / state.max_batch + 1) * state.max_batch - 1
+            } else {
+                start + state.max_batch - 1
+            };
+            let end = (query_param(query, "end") + state.overfill_entries as u64)
+                .min(max_end)
+                .min(state.leaves.len() as u64 - 1);
+            if state.fail_entries_from.is_some_and(|f| end >= f) {
+                return Err(INTERNAL_ERROR);
+            }
+            let entries = state.leaves[start as usize..=end as usize]
+                .iter()
+                .map(|(leaf_input, extra_data)| jsons::LeafEntry {
+                    leaf_input: BASE64_STANDARD.encode(leaf_input),
+                    extra_data: BASE64_STANDARD.encode(extra_data),
+                })
+                .collect();
+            serde_json::to_string(&jsons::GetEntries { entries }).map_err(|_| INTERNAL_ERROR)
+        }
+        "ct/v1/get-entry-and-proof" => {
+            let leaf_index = query_param(query, "leaf_index");
+            let tree_size = query_param(query, "tree_size");
+            let mut audit_path: Vec<[u8; 32]> = inclusion_proof_parts(tree_size, leaf_index)
+                .into_iter()
+                .map(|r| tree_hash(&state.leaf_hashes[r.start as usize..r.end as usize]))
+                .collect();
+            if state.tamper_proofs {
+                audit_path[0][0] ^= 1;
+            }
+            let (leaf_input, extra_data) =
+                &state.leaves[(leaf_index + state.wrong_entries as u64) as usize];
+            serde_json::to_string(&jsons::EntryAndProof {
+                leaf_input: BASE64_STANDARD.encode(leaf_input),
+                extra_data: BASE64_STANDARD.encode(extra_data),
+                audit_path: audit_path
+                    .iter()
+                    .map(|h| BASE64_STANDARD.encode(h))
+                    .collect(),
+            })
+            .map_err(|_| INTERNAL_ERROR)
+        }
+        "ct/v1/get-proof-by-hash" => {
+            if state.fail_proofs {
+                return Err(INTERNAL_ERROR);
+            }
+            let params: Vec<(String, String)> = serde_urlencoded::from_str(query).unwrap();
+            let param = |name: &str| params.iter().find(|(k, _)| k == name).unwrap().1.clone();
+            let hash = BASE64_STANDARD.decode(param("hash")).unwrap();
+            let tree_size: u64 = param("tree_size").parse().unwrap();
+            let leaf_index = state.leaf_hashes[..tree_size as usize]
+                .iter()
+                .position(|h| h[..] == hash[..])
+                .ok_or("404 Not Found")? as u64;
+            let audit_path = inclusion_proof_parts(tree_size, leaf_index)
+                .into_iter()
+                .map(|r| {
+                    BASE64_STANDARD.encode(tree_hash(
+                        &state.leaf_hashes[r.start as usize..r.end as usize],
+                    ))
+                })
+                .collect();
+            serde_json::to_string(&jsons::AuditProof {
+                leaf_index,
+                audit_path,
+            })
+            .map_err(|_| INTERNAL_ERROR)
+        }
+        "ct/v1/add-chain" | "ct/v1/add-pre-chain" => {
+            let entry = submitted_entry(endpoint, body).ok_or("400 Bad Request")?;
+            let sct = sign_sct(&state, entry, 1735689600000 + state.tree_size);
+            let raw = sct.to_raw();
+            serde_json::to_string(&jsons::AddChainResponse {
+                sct_version: 0,
+                id: BASE64_STANDARD.encode(raw.log_id),
+                timestamp: raw.timestamp,
+                extensions: BASE64_STANDARD.encode(&raw.extensions),
+                signature: BASE64_STANDARD
+                    .encode(encode_dss(raw.signature_algorithm, &raw.raw_signature).unwrap()),
+            })
+            .map_err(|_| INTERNAL_ERROR)
+        }
+        _ => Err("404 Not Found"),
+    };
+    json.map(String::into_bytes)
+}
+
+const INTERNAL_ERROR: &str = "500 Internal Server Error";
+
+fn handle(mut stream: TcpStream, state: &Mutex<MockState>) {
+    let mut reader = BufReader::new(stream.try_clone().unwrap());
+    loop {
+        let mut request_line = String::new();
+        if reader.read_line(&mut request_line).unwrap_or(0) == 0 {
+            return;
+        }
+        let mut content_length = 0;
+        loop {
+            let mut header = String::new();
+            if reader.read_line(&mut header).unwrap_or(0) == 0 || header == "\r\n" {
+                break;
+            }
+            if let Some((name, value)) = header.split_once(':')
+                && name.eq_ignore_ascii_case("content-length")
+            {
+                content_length = value.trim().parse().unwrap_or(0);
+            }
+        }
+        let mut body = vec![0u8; content_length];
+        if reader.read_exact(&mut body).is_err() {
+            return;
+        }
+        let path = request_line
+            .split(' ')
+            .nth(1)
+            .unwrap_or("/")
+            .trim_start_matches('/');
+        let response = match respond(state, path, &body) {
+            Ok(body) => {
+                let mut response =
+                    format!("HTTP/1.1 200 OK\r\nContent-Length: {}\r\n\r\n", body.len())
+                        .into_bytes();
+                response.extend_from_slice(&body);
+                response
+            }
+            Err(status) => format!("HTTP/1.1 {}\r\nContent-Length: 0\r\n\r\n", status).into_bytes(),
+        };
+        if stream.write_all(&response).is_err() {
+            return;
+        }
+    }
+}
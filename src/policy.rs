@@ -0,0 +1,524 @@
+//! Checking certificates against the CT policies of browsers.
+//!
+//! The state of each log is taken from a [`LogList`], which should be recent. For a time before a
+//! log entered its current state, it is assumed to have been in the state before it in the usual
+//! pending → qualified → usable → read-only → retired progression. This is what the lists allow
+//! us to know, but it can be wrong for logs that skipped a state.
+
+use openssl::pkey::PKey;
+use openssl::x509::X509Ref;
+
+use crate::google_log_list::{Log, LogList, LogState};
+use crate::{Error, LogApi, SctProblem, SignedCertificateTimestamp, certutils};
+
+const DAY_MS: u64 = 24 * 60 * 60 * 1000;
+
+/// How a SCT got to the client.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SctDelivery {
+    /// In the certificate, see [`SignedCertificateTimestamp::from_cert_sct_extension`].
+    Embedded,
+    /// In the TLS handshake, see [`SignedCertificateTimestamp::from_tls_extension`].
+    TlsExtension,
+    /// In a stapled OCSP response, see [`SignedCertificateTimestamp::from_ocsp_response`].
+    Ocsp,
+}
+
+/// A SCT to be checked against a policy, along with how it was delivered.
+#[derive(Debug, Clone)]
+pub struct DeliveredSct {
+    pub sct: SignedCertificateTimestamp,
+    pub delivery: SctDelivery,
+}
+
+/// What was found about one SCT.
+#[derive(Debug, Clone)]
+pub struct SctReport {
+    pub log_id: [u8; 32],
+    pub delivery: SctDelivery,
+    pub timestamp: u64,
+    /// Operator of the log, if it is in the list.
+    pub operator: Option<String>,
+    /// The state of the log at the time of check, if known.
+    pub log_state: Option<LogState>,
+    /// Why the SCT does not count. Empty if it does.
+    pub problems: Vec<SctProblem>,
+}
+
+/// Why a set of SCTs does not meet a policy.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PolicyFailure {
+    /// Not enough SCTs from distinct logs.
+    NotEnoughScts { required: usize, found: usize },
+    /// None of the SCTs are from a log that is currently qualified, usable or read-only, i.e.
+    /// they are all from retired logs.
+    NoCurrentLog,
+    /// The SCTs are not from enough distinct log operators.
+    NotEnoughOperators { required: usize, found: usize },
+    /// None of the SCTs are from a log serving the RFC 6962 API.
+    NoRfc6962Log,
+}
+
+impl std::fmt::Display for PolicyFailure {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PolicyFailure::NotEnoughScts { required, found } => write!(
+                f,
+                "SCTs from {} distinct logs are required, but only {} found",
+                required, found
+            ),
+            PolicyFailure::NoCurrentLog => write!(
+                f,
+                "At least one SCT must be from a qualified, usable or read-only log"
+            ),
+            PolicyFailure::NotEnoughOperators { required, found } => write!(
+                f,
+                "SCTs from {} distinct log operators are required, but only {} found",
+                required, found
+            ),
+            PolicyFailure::NoRfc6962Log => {
+                write!(f, "At least one SCT must be from a RFC 6962 log")
+            }
+        }
+    }
+}
+
+/// The result of checking a certificate against a policy.
+///
+/// Embedded SCTs and SCTs delivered by TLS or OCSP are considered separately, and the
+/// certificate is compliant if either set meets the policy.
+#[derive(Debug, Clone)]
+pub struct PolicyReport {
+    /// Every SCT given, in order.
+    pub scts: Vec<SctReport>,
+    /// Why the embedded SCTs are not enough. Empty if they are.
+    pub embedded_failures: Vec<PolicyFailure>,
+    /// Why the SCTs delivered by TLS or OCSP are not enough. Empty if they are.
+    pub non_embedded_failures: Vec<PolicyFailure>,
+}
+
+impl PolicyReport {
+    pub fn is_compliant(&self) -> bool {
+        self.embedded_failures.is_empty() || self.non_embedded_failures.is_empty()
+    }
+}
+
+/// The parts in which the policies of different browsers differ.
+struct Rules {
+    /// Number of embedded SCTs needed for a certificate with the given lifetime in ms.
+    embedded_required: fn(u64) -> usize,
+    non_embedded_required: usize,
+    require_rfc6962: bool,
+}
+
+/// The state `log` was in at `at`, see the module documentation.
+fn state_at(log: &Log, at: u64) -> Option<LogState> {
+    use LogState::*;
+    if log.state_timestamp <= at {
+        return Some(log.state);
+    }
+    match log.state {
+        Pending => None,
+        Qualified | Rejected => Some(Pending),
+        Usable => Some(Qualified),
+        Readonly => Some(Usable),
+        Retired => Some(Usable),
+    }
+}
+
+/// The checks on a single SCT shared by the policies here and [`check_scts`](crate::check_scts):
+/// the log must be known and trusted at `at`, the SCT must be signed by it, and must have been
+/// issued while the log was taking submissions and not after `at`. SCTs from retired logs are
+/// only accepted if `allow_retired`.
+///
+/// Returns the log, if known, and its state at `at`.
+pub(crate) fn check_sct<'a>(
+    sct: &SignedCertificateTimestamp,
+    log_list: &'a LogList,
+    at: u64,
+    allow_retired: bool,
+    problems: &mut Vec<SctProblem>,
+) -> (Option<&'a Log>, Option<LogState>) {
+    let log = log_list.find_by_id(&sct.log_id);
+    let mut state = None;
+    match log {
+        None => problems.push(SctProblem::UnknownLog),
+        Some(log) => {
+            let verified = PKey::public_key_from_der(&log.pub_key)
+                .map_err(|e| Error::BadSct(format!("Invalid log key: {}", e)))
+                .and_then(|key| sct.verify(&key));
+            if let Err(e) = verified {
+                problems.push(SctProblem::InvalidSignature(e.to_string()));
+            }
+            state = state_at(log, at);
+            match state {
+                Some(LogState::Qualified | LogState::Usable | LogState::Readonly) => {}
+                Some(LogState::Retired) if allow_retired => {}
+                state => problems.push(SctProblem::LogState(state)),
+            }
+            if matches!(
+                log.state,
+                LogState::Readonly | LogState::Retired | LogState::Rejected
+            ) && sct.timestamp >= log.state_timestamp
+            {
+                problems.push(SctProblem::LogNotActive {
+                    state: log.state,
+                    since: log.state_timestamp,
+                });
+            }
+        }
+    }
+    if sct.timestamp > at {
+        problems.push(SctProblem::FutureTimestamp);
+    }
+    (log, state)
+}
+
+fn sct_report(sct: &DeliveredSct, log_list: &LogList, at: u64, allow_retired: bool) -> SctReport {
+    let mut problems = Vec::new();
+    let (log, log_state) = check_sct(&sct.sct, log_list, at, allow_retired, &mut problems);
+    SctReport {
+        log_id: sct.sct.log_id,
+        delivery: sct.delivery,
+        timestamp: sct.sct.timestamp,
+        operator: log.map(|l| l.operator.clone()),
+        log_state,
+        problems,
+    }
+}
+
+/// Check the SCTs in `reports` (which all count) against the policy for one delivery path.
+fn check_path(
+    reports: &[&SctReport],
+    log_list: &LogList,
+    required: usize,
+    require_rfc6962: bool,
+) -> Vec<PolicyFailure> {
+    let mut failures = Vec::new();
+    let mut log_ids: Vec<&[u8; 32]> = reports.iter().map(|r| &r.log_id).collect();
+    log_ids.sort();
+    log_ids.dedup();
+    if log_ids.len() < required {
+        failures.push(PolicyFailure::NotEnoughScts {
+            required,
+            found: log_ids.len(),
+        });
+    }
+    if !reports.is_empty()
+        && !reports
+            .iter()
+            .any(|r| r.log_state.is_some_and(|s| s != LogState::Retired))
+    {
+        failures.push(PolicyFailure::NoCurrentLog);
+    }
+    let mut operators: Vec<&str> = reports
+        .iter()
+        .filter_map(|r| r.operator.as_deref())
+        .collect();
+    operators.sort();
+    operators.dedup();
+    if operators.len() < 2 {
+        failures.push(PolicyFailure::NotEnoughOperators {
+            required: 2,
+            found: operators.len(),
+        });
+    }
+    if require_rfc6962
+        && !reports.iter().any(|r| {
+            log_list
+                .find_by_id(&r.log_id)
+                .is_some_and(|l| l.api == LogApi::Rfc6962)
+        })
+    {
+        failures.push(PolicyFailure::NoRfc6962Log);
+    }
+    failures
+}
+
+fn evaluate(
+    rules: &Rules,
+    cert: &X509Ref,
+    scts: &[DeliveredSct],
+    log_list: &LogList,
+    at: u64,
+) -> Result<PolicyReport, Error> {
+    let lifetime =
+        certutils::get_not_after(&cert)?.saturating_sub(certutils::get_not_before(&cert)?);
+    let reports: Vec<SctReport> = scts
+        .iter()
+        .map(|sct| sct_report(sct, log_list, at, sct.delivery == SctDelivery::Embedded))
+        .collect();
+    let counting = |embedded: bool| -> Vec<&SctReport> {
+        reports
+            .iter()
+            .filter(|r| r.problems.is_empty())
+            .filter(|r| (r.delivery == SctDelivery::Embedded) == embedded)
+            .collect()
+    };
+    let embedded_failures = check_path(
+        &counting(true),
+        log_list,
+        (rules.embedded_required)(lifetime),
+        rules.require_rfc6962,
+    );
+    let non_embedded_failures = check_path(
+        &counting(false),
+        log_list,
+        rules.non_embedded_required,
+        rules.require_rfc6962,
+    );
+    Ok(PolicyReport {
+        scts: reports,
+        embedded_failures,
+        non_embedded_failures,
+    })
+}
+
+/// Check `cert` and its `scts` against
+/// [Chrome's CT policy](https://googlechrome.github.io/CertificateTransparency/ct_policy.html)
+/// at time `at` (ms since the epoch), with log states from the Google `log_list`. The rules are
+/// those of the revision for certificates issued since April 15, 2022:
+///
+/// * Embedded SCTs must be from 2 distinct logs if the certificate lifetime is at most 180 days,
+///   or 3 otherwise. Retired logs count if the SCT is from before their retirement, but at
+///   least one log must not be retired.
+/// * SCTs delivered by TLS or OCSP must be from 2 distinct logs that are not retired.
+/// * Either way, the logs must be run by at least 2 distinct operators, and at least one of them
+///   must be a RFC 6962 log.
+///
+/// Only returns an error if the validity period of `cert` can't be read.
+pub fn evaluate_chrome(
+    cert: &X509Ref,
+    scts: &[DeliveredSct],
+    log_list: &LogList,
+    at: u64,
+) -> Result<PolicyReport, Error> {
+    evaluate(
+        &Rules {
+            embedded_required: |lifetime| if lifetime <= 180 * DAY_MS { 2 } else { 3 },
+            non_embedded_required: 2,
+            require_rfc6962: true,
+        },
+        cert,
+        scts,
+        log_list,
+        at,
+    )
+}
+
+/// Check `cert` and its `scts` against
+/// [Apple's CT policy](https://support.apple.com/en-us/103214) at time `at` (ms since the
+/// epoch), with log states from Apple's `log_list` (see
+/// [`LogList::from_apple_json_bytes`](crate::google_log_list::LogList::from_apple_json_bytes)).
+///
+/// The current revision of Apple's policy, which replaced the SCT counts by lifetime ranges of
+/// 15, 27 and 39 months with a single 180-day threshold, has the same thresholds as the Chrome
+/// revision checked by [`evaluate_chrome`]. The only difference is that Apple doesn't require
+/// a RFC 6962 log, so a certificate with SCTs only from static CT logs can comply.
+///
+/// Only returns an error if the validity period of `cert` can't be read.
+pub fn evaluate_apple(
+    cert: &X509Ref,
+    scts: &[DeliveredSct],
+    log_list: &LogList,
+    at: u64,
+) -> Result<PolicyReport, Error> {
+    evaluate(
+        &Rules {
+            embedded_required: |lifetime| if lifetime <= 180 * DAY_MS { 2 } else { 3 },
+            non_embedded_required: 2,
+            require_rfc6962: false,
+        },
+        cert,
+        scts,
+        log_list,
+        at,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_logs;
+    use openssl::x509::X509;
+
+    fn test_scts(delivery: SctDelivery) -> (X509, Vec<DeliveredSct>) {
+        let (cert, scts) = test_logs::scts();
+        let scts = scts
+            .into_iter()
+            .map(|sct| DeliveredSct { sct, delivery })
+            .collect();
+        (cert, scts)
+    }
+
+    // The SCTs are from 2025-01-01.
+    const AT: u64 = 1740000000000;
+
+    #[test]
+    fn chrome_tls_test() {
+        let (cert, scts) = test_scts(SctDelivery::TlsExtension);
+        let list = test_logs::list(vec![
+            test_logs::log(test_logs::key_der(0), "A", LogState::Usable, 0),
+            test_logs::log(test_logs::key_der(1), "B", LogState::Qualified, 0),
+        ]);
+        let report = evaluate_chrome(&cert, &scts, &list, AT).unwrap();
+        assert!(report.is_compliant());
+        assert!(report.non_embedded_failures.is_empty());
+        assert_eq!(
+            report.embedded_failures,
+            vec![
+                PolicyFailure::NotEnoughScts {
+                    required: 3,
+                    found: 0
+                },
+                PolicyFailure::NotEnoughOperators {
+                    required: 2,
+                    found: 0
+                },
+                PolicyFailure::NoRfc6962Log,
+            ]
+        );
+        assert!(report.scts.iter().all(|r| r.problems.is_empty()));
+
+        // Same operator
+        let list = test_logs::list(vec![
+            test_logs::log(test_logs::key_der(0), "A", LogState::Usable, 0),
+            test_logs::log(test_logs::key_der(1), "A", LogState::Usable, 0),
+        ]);
+        let report = evaluate_chrome(&cert, &scts, &list, AT).unwrap();
+        assert!(!report.is_compliant());
+        assert_eq!(
+            report.non_embedded_failures,
+            vec![PolicyFailure::NotEnoughOperators {
+                required: 2,
+                found: 1
+            }]
+        );
+
+        // Retired logs don't count for TLS, unknown logs and bad signatures never do.
+        let list = test_logs::list(vec![test_logs::log(
+            test_logs::key_der(0),
+            "A",
+            LogState::Retired,
+            0,
+        )]);
+        let mut scts = scts;
+        scts[1].sct.raw_signature[10] ^= 1;
+        let report = evaluate_chrome(&cert, &scts, &list, AT).unwrap();
+        assert!(
+            report.scts[0]
+                .problems
+                .contains(&SctProblem::LogState(Some(LogState::Retired)))
+        );
+        assert_eq!(report.scts[1].problems, vec![SctProblem::UnknownLog]);
+        let list = test_logs::list(vec![test_logs::log(
+            test_logs::key_der(1),
+            "B",
+            LogState::Usable,
+            0,
+        )]);
+        let report = evaluate_chrome(&cert, &scts, &list, AT).unwrap();
+        assert!(matches!(
+            report.scts[1].problems[..],
+            [SctProblem::InvalidSignature(_)]
+        ));
+        assert!(
+            evaluate_chrome(&cert, &scts, &list, AT - DAY_MS * 100)
+                .unwrap()
+                .scts[1]
+                .problems
+                .contains(&SctProblem::FutureTimestamp)
+        );
+    }
+
+    #[test]
+    fn chrome_embedded_test() {
+        let (cert, scts) = test_scts(SctDelivery::Embedded);
+        // A log retired after the SCT still counts, but the 10-year certificate needs 3.
+        let retired_at = AT - DAY_MS;
+        let list = test_logs::list(vec![
+            test_logs::log(test_logs::key_der(0), "A", LogState::Retired, retired_at),
+            test_logs::log(test_logs::key_der(1), "B", LogState::Usable, 0),
+        ]);
+        let report = evaluate_chrome(&cert, &scts, &list, AT).unwrap();
+        assert!(report.scts.iter().all(|r| r.problems.is_empty()));
+        assert_eq!(
+            report.embedded_failures,
+            vec![PolicyFailure::NotEnoughScts {
+                required: 3,
+                found: 2
+            }]
+        );
+        // Before it retired, it was usable.
+        let report = evaluate_chrome(&cert, &scts, &list, retired_at - 1).unwrap();
+        assert_eq!(report.scts[0].log_state, Some(LogState::Usable));
+
+        let list = test_logs::list(vec![
+            test_logs::log(test_logs::key_der(0), "A", LogState::Retired, 1735689600000),
+            test_logs::log(test_logs::key_der(1), "B", LogState::Retired, retired_at),
+        ]);
+        let report = evaluate_chrome(&cert, &scts, &list, AT).unwrap();
+        assert_eq!(
+            report.scts[0].problems,
+            vec![SctProblem::LogNotActive {
+                state: LogState::Retired,
+                since: 1735689600000
+            }]
+        );
+        assert_eq!(
+            report.embedded_failures,
+            vec![
+                PolicyFailure::NotEnoughScts {
+                    required: 3,
+                    found: 1
+                },
+                PolicyFailure::NoCurrentLog,
+                PolicyFailure::NotEnoughOperators {
+                    required: 2,
+                    found: 1
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn apple_test() {
+        let (cert, scts) = test_scts(SctDelivery::TlsExtension);
+        let mut tiled = test_logs::log(test_logs::key_der(1), "B", LogState::Usable, 0);
+        tiled.api = LogApi::StaticCt;
+        let list = test_logs::list(vec![
+            test_logs::log(test_logs::key_der(0), "A", LogState::Usable, 0),
+            tiled,
+        ]);
+        let report = evaluate_apple(&cert, &scts, &list, AT).unwrap();
+        assert!(report.is_compliant());
+        // Chrome wants a RFC 6962 log in the mix.
+        let mut tiled_only = list.clone();
+        for log in tiled_only.map_id_to_log.values_mut() {
+            log.api = LogApi::StaticCt;
+        }
+        assert!(
+            evaluate_apple(&cert, &scts, &tiled_only, AT)
+                .unwrap()
+                .is_compliant()
+        );
+        assert_eq!(
+            evaluate_chrome(&cert, &scts, &tiled_only, AT)
+                .unwrap()
+                .non_embedded_failures,
+            vec![PolicyFailure::NoRfc6962Log]
+        );
+
+        // The 10-year certificate needs 3 embedded SCTs.
+        let (cert, scts) = test_scts(SctDelivery::Embedded);
+        let report = evaluate_apple(&cert, &scts, &list, AT).unwrap();
+        assert_eq!(
+            report.embedded_failures,
+            vec![PolicyFailure::NotEnoughScts {
+                required: 3,
+                found: 2
+            }]
+        );
+        assert!(!report.is_compliant());
+    }
+}
@@ -1,16 +1,20 @@
 use std::convert::TryInto;
 
+use base64::prelude::{BASE64_STANDARD, Engine as _};
 use openssl::pkey::PKey;
 use openssl::sha::sha256;
-use openssl::x509::X509Ref;
+use openssl::x509::{X509, X509Ref};
 
-use crate::Error;
-use crate::internal::leaf_hash_constructors;
+use crate::google_log_list::{Log, LogList, LogState, TemporalInterval};
 use crate::internal::openssl_ffi::{
-    SCTVersion, SignatureAlgorithm, sct_list_from_x509, x509_clone, x509_remove_sct_list,
-    x509_to_tbs,
+    sct_list_from_ocsp, sct_list_from_x509, x509_clone, x509_is_precert_signing_cert,
+    x509_make_a_looks_like_issued_by_b, x509_remove_poison, x509_remove_sct_list, x509_to_tbs,
 };
-use crate::internal::verify_dss_raw;
+use crate::internal::{
+    RawSct, SCTVersion, SignatureAlgorithm, encode_dss, encode_sct_list, encode_sct_list_extension,
+    leaf_hash_constructors, parse_dss, parse_sct_list, verify_dss_raw,
+};
+use crate::{Error, certutils, jsons, policy};
 
 fn to_unknown_err(openssl_err: openssl::error::ErrorStack) -> Error {
     Error::Unknown(format!("{}", openssl_err))
@@ -40,6 +44,50 @@ pub enum SctEntry {
     },
 }
 
+impl SctEntry {
+    /// Construct the entry a log will sign over when `chain` is submitted to it with
+    /// `add-pre-chain`.
+    ///
+    /// `chain[0]` is the precertificate, and `chain[1]` is its issuer. If `chain[1]` is a
+    /// precertificate signing certificate, `chain[2]` must be the CA that will issue the final
+    /// certificate, and the TBS is adjusted to look like it is issued by that CA (RFC 6962,
+    /// section 3.2).
+    pub fn from_precert_chain(chain: &[X509]) -> Result<SctEntry, Error> {
+        if chain.len() < 2 {
+            return Err(Error::InvalidArgument(
+                "Expected at least the precertificate and its issuer.".to_owned(),
+            ));
+        }
+        let mut cert_clone = x509_clone(&chain[0]).map_err(to_unknown_err)?;
+        x509_remove_poison(&mut cert_clone).map_err(to_unknown_err)?;
+        let issuer = if x509_is_precert_signing_cert(&chain[1])? {
+            if chain.len() < 3 {
+                return Err(Error::InvalidArgument(
+                    "Precertificate is signed by a precertificate signing certificate, but the chain does not contain the true issuer.".to_owned(),
+                ));
+            }
+            x509_make_a_looks_like_issued_by_b(&mut cert_clone, &chain[2])?;
+            &chain[2]
+        } else {
+            &chain[1]
+        };
+        let tbs = x509_to_tbs(&cert_clone).map_err(to_unknown_err)?;
+        let issuer_key_hash = sha256(
+            &issuer
+                .public_key()
+                .map_err(|e| {
+                    Error::BadCertificate(format!("Can't parse public key from issuer: {}", e))
+                })?
+                .public_key_to_der()
+                .map_err(to_unknown_err)?,
+        );
+        Ok(SctEntry::PreCert {
+            tbs,
+            issuer_key_hash,
+        })
+    }
+}
+
 impl SignedCertificateTimestamp {
     /// Extract a list of SCTs from the SCT List extension of the given openssl-parsed certificate,
     /// if the extension is there.
@@ -71,28 +119,160 @@ impl SignedCertificateTimestamp {
                 .map_err(to_unknown_err)?;
             sha256(&k)
         };
-        let mut scts = Vec::with_capacity(sctlist.len());
-        for raw_sct in sctlist.into_iter() {
-            if raw_sct.version() != Some(SCTVersion::V1) {
-                return Err(Error::BadCertificate("Invalid SCT version.".to_owned()));
-            }
-            scts.push(SignedCertificateTimestamp {
-                log_id: raw_sct.log_id().try_into().map_err(|_| {
-                    Error::BadCertificate("Expected log_id to have len 32".to_owned())
-                })?,
-                timestamp: raw_sct.timestamp(),
-                extensions_data: raw_sct.extensions().to_vec(),
-                entry: SctEntry::PreCert {
-                    tbs: tbs.clone(),
-                    issuer_key_hash,
-                },
-                signature_algorithm: raw_sct
-                    .signature_algorithm()
-                    .ok_or_else(|| Error::BadSct("Unknown signature algorithm.".to_owned()))?,
-                raw_signature: raw_sct.raw_signature().to_vec(),
-            });
+        Self::from_sct_list(
+            sctlist,
+            SctEntry::PreCert {
+                tbs,
+                issuer_key_hash,
+            },
+        )
+    }
+
+    /// Parse a TLS-encoded `SignedCertificateTimestampList`, as sent by a server in the
+    /// `signed_certificate_timestamp` TLS extension, for the (final) certificate `cert`.
+    ///
+    /// Will not verify the signature. Call [`self.verify`](Self::verify) with the log's public key to verify.
+    pub fn from_tls_extension(
+        sct_list: &[u8],
+        cert: &X509Ref,
+    ) -> Result<Vec<SignedCertificateTimestamp>, Error> {
+        let sctlist = parse_sct_list(sct_list)?;
+        Self::from_sct_list(
+            sctlist,
+            SctEntry::X509(cert.to_der().map_err(to_unknown_err)?),
+        )
+    }
+
+    /// Extract the SCTs for the (final) certificate `cert` from a DER-encoded OCSP response,
+    /// as stapled by a server. `issuer` is used to find the response about `cert`.
+    ///
+    /// If the response has no SCT list extension, `Ok(vec![])` is returned. The OCSP response
+    /// itself is not verified, since the SCTs are signed by the logs anyway.
+    ///
+    /// Will not verify the signature. Call [`self.verify`](Self::verify) with the log's public key to verify.
+    pub fn from_ocsp_response(
+        ocsp_response: &[u8],
+        cert: &X509Ref,
+        issuer: &X509Ref,
+    ) -> Result<Vec<SignedCertificateTimestamp>, Error> {
+        use openssl::ocsp::{OcspCertId, OcspResponse, OcspResponseStatus};
+        let resp = OcspResponse::from_der(ocsp_response)
+            .map_err(|e| Error::BadSct(format!("Invalid OCSP response: {}", e)))?;
+        if resp.status() != OcspResponseStatus::SUCCESSFUL {
+            return Err(Error::BadSct(format!(
+                "OCSP response has status {}",
+                resp.status().as_raw()
+            )));
         }
-        Ok(scts)
+        let basic = resp
+            .basic()
+            .map_err(|e| Error::BadSct(format!("Invalid OCSP response: {}", e)))?;
+        let cert_id = OcspCertId::from_cert(openssl::hash::MessageDigest::sha1(), cert, issuer)
+            .map_err(to_unknown_err)?;
+        let sctlist = match sct_list_from_ocsp(&basic, &cert_id)? {
+            Some(l) => l,
+            None => return Ok(Vec::new()),
+        };
+        Self::from_sct_list(
+            sctlist,
+            SctEntry::X509(cert.to_der().map_err(to_unknown_err)?),
+        )
+    }
+
+    /// All SCTs in `sctlist` are over `entry`.
+    fn from_sct_list(
+        sctlist: Vec<RawSct>,
+        entry: SctEntry,
+    ) -> Result<Vec<SignedCertificateTimestamp>, Error> {
+        Ok(sctlist
+            .into_iter()
+            .map(|raw_sct| SignedCertificateTimestamp {
+                log_id: raw_sct.log_id,
+                timestamp: raw_sct.timestamp,
+                extensions_data: raw_sct.extensions,
+                entry: entry.clone(),
+                signature_algorithm: raw_sct.signature_algorithm,
+                raw_signature: raw_sct.raw_signature,
+            })
+            .collect())
+    }
+
+    /// Parse the JSON response of an `add-chain` or `add-pre-chain` call.
+    ///
+    /// `entry` is what has been submitted. Will not verify the signature.
+    pub fn from_add_chain_response(
+        response: &jsons::AddChainResponse,
+        entry: SctEntry,
+    ) -> Result<SignedCertificateTimestamp, Error> {
+        fn b64_dec_err(e: base64::DecodeError) -> Error {
+            Error::BadSct(format!("Unable to decode base64: {}", e))
+        }
+        if response.sct_version != 0 {
+            return Err(Error::BadSct("Invalid SCT version.".to_owned()));
+        }
+        let log_id = BASE64_STANDARD.decode(&response.id).map_err(b64_dec_err)?;
+        let extensions_data = BASE64_STANDARD
+            .decode(&response.extensions)
+            .map_err(b64_dec_err)?;
+        let dss = BASE64_STANDARD
+            .decode(&response.signature)
+            .map_err(b64_dec_err)?;
+        let (signature_algorithm, raw_signature) = parse_dss(&dss)?;
+        Ok(SignedCertificateTimestamp {
+            log_id: log_id[..]
+                .try_into()
+                .map_err(|_| Error::BadSct("Expected log_id to have len 32".to_owned()))?,
+            timestamp: response.timestamp,
+            extensions_data,
+            entry,
+            signature_algorithm,
+            raw_signature: raw_signature.to_vec(),
+        })
+    }
+
+    /// This SCT as it appears in a `SignedCertificateTimestampList`, i.e. without the entry.
+    pub fn to_raw(&self) -> RawSct {
+        RawSct {
+            version: SCTVersion::V1,
+            log_id: self.log_id,
+            timestamp: self.timestamp,
+            extensions: self.extensions_data.clone(),
+            signature_algorithm: self.signature_algorithm,
+            raw_signature: self.raw_signature.clone(),
+        }
+    }
+
+    /// Serialize this SCT into its TLS wire form (RFC 6962, section 3.2), which is one item in a
+    /// `SignedCertificateTimestampList`.
+    pub fn to_tls_bytes(&self) -> Result<Vec<u8>, Error> {
+        self.to_raw().to_bytes()
+    }
+
+    /// Serialize this SCT into the JSON form returned by `add-chain` (RFC 6962, section 4.1).
+    /// The reverse of [`Self::from_add_chain_response`].
+    pub fn to_add_chain_response(&self) -> Result<jsons::AddChainResponse, Error> {
+        Ok(jsons::AddChainResponse {
+            sct_version: 0,
+            id: BASE64_STANDARD.encode(self.log_id),
+            timestamp: self.timestamp,
+            extensions: BASE64_STANDARD.encode(&self.extensions_data),
+            signature: BASE64_STANDARD
+                .encode(encode_dss(self.signature_algorithm, &self.raw_signature)?),
+        })
+    }
+
+    /// Encode `scts` as a TLS `SignedCertificateTimestampList`, as sent in the
+    /// `signed_certificate_timestamp` TLS extension. The reverse of [`Self::from_tls_extension`].
+    pub fn to_tls_list(scts: &[SignedCertificateTimestamp]) -> Result<Vec<u8>, Error> {
+        encode_sct_list(&scts.iter().map(|s| s.to_raw()).collect::<Vec<_>>())
+    }
+
+    /// Build the DER-encoded value of the X.509 SCT list extension (OID
+    /// 1.3.6.1.4.1.11129.2.4.2) holding `scts`, to embed them in the final certificate.
+    ///
+    /// This is what [`Self::from_cert_sct_extension`] reads.
+    pub fn build_sct_list_extension(scts: &[SignedCertificateTimestamp]) -> Result<Vec<u8>, Error> {
+        encode_sct_list_extension(&scts.iter().map(|s| s.to_raw()).collect::<Vec<_>>())
     }
 
     /// Derive the corresponding Merkle leaf hash from this SCTs.
@@ -178,3 +358,351 @@ impl SignedCertificateTimestamp {
         )
     }
 }
+
+/// Something wrong with a SCT found by [`check_scts`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SctProblem {
+    /// The log is not in the log list.
+    UnknownLog,
+    /// The signature does not verify with the log's key.
+    InvalidSignature(String),
+    /// The log was not trusted at the time of check: it was pending or rejected, or it is retired
+    /// and retired logs don't count. `None` if it was not even pending yet.
+    LogState(Option<LogState>),
+    /// The SCT is from after the log stopped taking submissions, by becoming read-only, retired
+    /// or rejected at `since`.
+    LogNotActive { state: LogState, since: u64 },
+    /// The log is a temporal shard, and the certificate's notAfter is outside its interval.
+    NotAfterOutsideTemporalInterval(TemporalInterval),
+    /// The SCT is from after the time of check.
+    FutureTimestamp,
+    /// The SCT is from before the certificate's notBefore.
+    BeforeNotBefore { not_before: u64 },
+}
+
+impl std::fmt::Display for SctProblem {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SctProblem::UnknownLog => write!(f, "log is not known"),
+            SctProblem::InvalidSignature(e) => write!(f, "invalid signature: {}", e),
+            SctProblem::LogState(Some(state)) => write!(f, "log is {:?}", state),
+            SctProblem::LogState(None) => write!(f, "log was not pending yet"),
+            SctProblem::LogNotActive { state, since } => {
+                write!(f, "log has been {:?} since {}", state, since)
+            }
+            SctProblem::NotAfterOutsideTemporalInterval(t) => write!(
+                f,
+                "log only accepts certificates expiring in [{}, {})",
+                t.start_inclusive, t.end_exclusive
+            ),
+            SctProblem::FutureTimestamp => write!(f, "timestamp is in the future"),
+            SctProblem::BeforeNotBefore { not_before } => {
+                write!(f, "timestamp is before the notBefore {}", not_before)
+            }
+        }
+    }
+}
+
+/// What [`check_scts`] found about one SCT.
+#[derive(Debug, Clone)]
+pub struct SctCheck {
+    pub sct: SignedCertificateTimestamp,
+    /// The log that issued the SCT, if it is in the list.
+    pub log: Option<Log>,
+    /// Empty if the SCT is fine.
+    pub problems: Vec<SctProblem>,
+}
+
+impl SctCheck {
+    pub fn is_ok(&self) -> bool {
+        self.problems.is_empty()
+    }
+}
+
+/// Check each of `scts` for `cert` (the final certificate) against the logs in `log_list`, at
+/// time `at` (ms since the epoch). Does not contact the logs, so inclusion is not checked. Use
+/// [`CTClient::check_inclusion_proof_for_sct`](crate::CTClient::check_inclusion_proof_for_sct)
+/// with [`SctCheck::log`] for that.
+///
+/// Only returns an error if the validity period of `cert` can't be read.
+pub fn check_scts(
+    scts: Vec<SignedCertificateTimestamp>,
+    cert: &X509Ref,
+    log_list: &LogList,
+    at: u64,
+) -> Result<Vec<SctCheck>, Error> {
+    let not_before = certutils::get_not_before(&cert)?;
+    let not_after = certutils::get_not_after(&cert)?;
+    Ok(scts
+        .into_iter()
+        .map(|sct| {
+            let mut problems = Vec::new();
+            let (log, _) = policy::check_sct(&sct, log_list, at, true, &mut problems);
+            if let Some(interval) = log.and_then(|l| l.temporal_interval)
+                && !interval.contains(not_after)
+            {
+                problems.push(SctProblem::NotAfterOutsideTemporalInterval(interval));
+            }
+            if sct.timestamp < not_before {
+                problems.push(SctProblem::BeforeNotBefore { not_before });
+            }
+            SctCheck {
+                log: log.cloned(),
+                sct,
+                problems,
+            }
+        })
+        .collect())
+}
+
+/// [`check_scts`] for the SCTs embedded in `cert`, which is issued by `issuer`.
+///
+/// Returns an empty list if `cert` has no embedded SCTs.
+pub fn check_cert_scts(
+    cert: &X509Ref,
+    issuer: &X509Ref,
+    log_list: &LogList,
+    at: u64,
+) -> Result<Vec<SctCheck>, Error> {
+    let scts = SignedCertificateTimestamp::from_cert_sct_extension(cert, issuer)?;
+    check_scts(scts, cert, log_list, at)
+}
+
+#[test]
+fn precert_signing_cert_entry_test() {
+    let chain = X509::stack_from_pem(include_bytes!("test_data/precert-signing-ca.pem")).unwrap();
+    assert!(!x509_is_precert_signing_cert(&chain[0]).unwrap());
+    assert!(x509_is_precert_signing_cert(&chain[1]).unwrap());
+    match SctEntry::from_precert_chain(&chain).unwrap() {
+        SctEntry::PreCert {
+            tbs,
+            issuer_key_hash,
+        } => {
+            assert_eq!(
+                &tbs[..],
+                &include_bytes!("test_data/precert-signing.ca.tbs")[..]
+            );
+            let expected_key_hash =
+                sha256(&chain[2].public_key().unwrap().public_key_to_der().unwrap());
+            assert_eq!(issuer_key_hash, expected_key_hash);
+        }
+        SctEntry::X509(_) => panic!("Expected a precert entry."),
+    }
+    SctEntry::from_precert_chain(&chain[..2]).expect_err("");
+}
+
+#[test]
+fn tls_and_ocsp_sct_test() {
+    let chain = X509::stack_from_pem(include_bytes!("test_data/sct_leaf.pem")).unwrap();
+    let log_keys = [crate::test_logs::key(0), crate::test_logs::key(1)];
+    let check = |scts: Vec<SignedCertificateTimestamp>| {
+        assert_eq!(scts.len(), 2);
+        for (sct, key) in scts.iter().zip(log_keys.iter()) {
+            assert!(
+                matches!(&sct.entry, SctEntry::X509(der) if *der == chain[0].to_der().unwrap())
+            );
+            assert_eq!(sct.log_id, sha256(&key.public_key_to_der().unwrap()));
+            sct.verify(key).unwrap();
+            assert_eq!(
+                sct.derive_leaf_hash(),
+                leaf_hash_constructors::with_x509(&chain[0].to_der().unwrap(), sct.timestamp, &[])
+            );
+        }
+        scts[0].verify(&log_keys[1]).expect_err("");
+    };
+
+    let tls = include_bytes!("test_data/sct_list.bin");
+    check(SignedCertificateTimestamp::from_tls_extension(tls, &chain[0]).unwrap());
+    SignedCertificateTimestamp::from_tls_extension(&tls[..tls.len() - 1], &chain[0]).expect_err("");
+    // SCTs for another certificate don't verify.
+    let scts = SignedCertificateTimestamp::from_tls_extension(tls, &chain[1]).unwrap();
+    scts[0].verify(&log_keys[0]).expect_err("");
+
+    let ocsp = include_bytes!("test_data/ocsp_sct.der");
+    check(SignedCertificateTimestamp::from_ocsp_response(ocsp, &chain[0], &chain[1]).unwrap());
+    SignedCertificateTimestamp::from_ocsp_response(ocsp, &chain[1], &chain[1]).expect_err("");
+    SignedCertificateTimestamp::from_ocsp_response(&ocsp[1..], &chain[0], &chain[1]).expect_err("");
+}
+
+#[test]
+fn add_chain_response_test() {
+    let chain = X509::stack_from_pem(include_bytes!("test_data/sct_leaf.pem")).unwrap();
+    let key = crate::test_logs::key(0);
+    let response: jsons::AddChainResponse =
+        serde_json::from_slice(include_bytes!("test_data/add_chain_response.json")).unwrap();
+    let entry = SctEntry::X509(chain[0].to_der().unwrap());
+    let sct =
+        SignedCertificateTimestamp::from_add_chain_response(&response, entry.clone()).unwrap();
+    assert_eq!(sct.log_id, sha256(&key.public_key_to_der().unwrap()));
+    assert_eq!(sct.timestamp, 1735689600000);
+    assert!(sct.extensions_data.is_empty());
+    assert_eq!(sct.signature_algorithm, SignatureAlgorithm::Sha256Ecdsa);
+    sct.verify(&key).unwrap();
+    // It is the same SCT as the one the log put in the TLS extension.
+    let tls = SignedCertificateTimestamp::from_tls_extension(
+        include_bytes!("test_data/sct_list.bin"),
+        &chain[0],
+    )
+    .unwrap();
+    assert_eq!(sct.to_raw(), tls[0].to_raw());
+
+    // Signed over a different certificate.
+    let other = SctEntry::X509(chain[1].to_der().unwrap());
+    SignedCertificateTimestamp::from_add_chain_response(&response, other)
+        .unwrap()
+        .verify(&key)
+        .expect_err("");
+    let mut bad = response.clone();
+    bad.sct_version = 1;
+    SignedCertificateTimestamp::from_add_chain_response(&bad, entry.clone()).expect_err("");
+    let mut bad = response.clone();
+    bad.id = BASE64_STANDARD.encode([0u8; 31]);
+    SignedCertificateTimestamp::from_add_chain_response(&bad, entry.clone()).expect_err("");
+    let mut bad = response;
+    bad.signature.insert(0, '!');
+    SignedCertificateTimestamp::from_add_chain_response(&bad, entry).expect_err("");
+}
+
+#[test]
+fn embedded_sct_test() {
+    let cert = X509::from_pem(include_bytes!("test_data/sct_embedded.pem")).unwrap();
+    let scts = SignedCertificateTimestamp::from_cert_sct_extension(&cert, &cert).unwrap();
+    let raw = parse_sct_list(include_bytes!("test_data/sct_list.bin")).unwrap();
+    assert_eq!(scts.len(), 2);
+    for (sct, raw) in scts.iter().zip(raw.iter()) {
+        assert_eq!(sct.log_id, raw.log_id);
+        assert_eq!(sct.timestamp, raw.timestamp);
+        assert_eq!(sct.raw_signature, raw.raw_signature);
+        match &sct.entry {
+            SctEntry::PreCert { tbs, .. } => {
+                // The SCT list is not part of what the log signed.
+                let mut cert_clone = x509_clone(&cert).unwrap();
+                x509_remove_sct_list(&mut cert_clone).unwrap();
+                assert_eq!(*tbs, x509_to_tbs(&cert_clone).unwrap());
+                assert_ne!(*tbs, x509_to_tbs(&cert).unwrap());
+            }
+            SctEntry::X509(_) => panic!("Expected a precert entry."),
+        }
+    }
+    let issuer = X509::stack_from_pem(include_bytes!("test_data/sct_leaf.pem")).unwrap();
+    assert!(
+        SignedCertificateTimestamp::from_cert_sct_extension(&issuer[0], &issuer[1])
+            .unwrap()
+            .is_empty()
+    );
+}
+
+#[test]
+fn serialize_test() {
+    let chain = X509::stack_from_pem(include_bytes!("test_data/sct_leaf.pem")).unwrap();
+    let tls = include_bytes!("test_data/sct_list.bin");
+    let scts = SignedCertificateTimestamp::from_tls_extension(tls, &chain[0]).unwrap();
+    assert_eq!(SignedCertificateTimestamp::to_tls_list(&scts).unwrap(), tls);
+    assert_eq!(
+        scts[0].to_tls_bytes().unwrap(),
+        parse_sct_list(tls).unwrap()[0].to_bytes().unwrap()
+    );
+
+    let json = scts[1].to_add_chain_response().unwrap();
+    assert_eq!(json.timestamp, 1735689600001);
+    let parsed = SignedCertificateTimestamp::from_add_chain_response(
+        &json,
+        SctEntry::X509(chain[0].to_der().unwrap()),
+    )
+    .unwrap();
+    assert_eq!(parsed.to_raw(), scts[1].to_raw());
+    parsed.verify(&crate::test_logs::key(1)).unwrap();
+
+    // The extension built from the SCTs is byte for byte the one in the certificate the log
+    // issued them for: the extnValue OCTET STRING following the SCT list OID.
+    let ext = SignedCertificateTimestamp::build_sct_list_extension(&scts).unwrap();
+    let embedded = X509::from_pem(include_bytes!("test_data/sct_embedded.pem")).unwrap();
+    let der = embedded.to_der().unwrap();
+    let oid = [
+        0x06, 0x0a, 0x2b, 0x06, 0x01, 0x04, 0x01, 0xd6, 0x79, 0x02, 0x04, 0x02,
+    ];
+    let pos = der.windows(oid.len()).position(|w| w == oid).unwrap() + oid.len();
+    assert_eq!(der[pos], 0x04);
+    let (len, value) = crate::internal::openssl_ffi::der_read_length(&der[pos + 1..]).unwrap();
+    assert_eq!(ext, &value[..len]);
+
+    // Embedding the extension gives the same SCTs back.
+    let embedded_scts =
+        SignedCertificateTimestamp::from_cert_sct_extension(&embedded, &embedded).unwrap();
+    assert_eq!(
+        SignedCertificateTimestamp::build_sct_list_extension(&embedded_scts).unwrap(),
+        ext
+    );
+    assert_eq!(
+        embedded_scts.iter().map(|s| s.to_raw()).collect::<Vec<_>>(),
+        scts.iter().map(|s| s.to_raw()).collect::<Vec<_>>()
+    );
+}
+
+#[test]
+fn check_scts_test() {
+    use crate::google_log_list::LogList;
+    let chain = X509::stack_from_pem(include_bytes!("test_data/sct_leaf.pem")).unwrap();
+    let scts = SignedCertificateTimestamp::from_tls_extension(
+        include_bytes!("test_data/sct_list.bin"),
+        &chain[0],
+    )
+    .unwrap();
+    let mut ll = LogList::from_json_bytes(include_bytes!("test_data/log_list_v3.json")).unwrap();
+    // Put the first test log in the list as a read-only shard for 2025.
+    let mut log = ll.find_by_id(&[0x22; 32]).unwrap().clone();
+    log.pub_key = crate::test_logs::key_der(0);
+    log.log_id = sha256(&log.pub_key).to_vec();
+    log.state_timestamp = 1735689600000;
+    log.temporal_interval = ll.find_by_id(&[0x11; 32]).unwrap().temporal_interval;
+    ll.map_id_to_log.insert(log.log_id.clone(), log);
+
+    let at = 1740000000000;
+    let checks = check_scts(scts.clone(), &chain[0], &ll, at).unwrap();
+    assert_eq!(checks.len(), 2);
+    assert!(checks[0].log.is_some());
+    assert_eq!(
+        checks[0].problems,
+        vec![
+            SctProblem::LogNotActive {
+                state: LogState::Readonly,
+                since: 1735689600000
+            },
+            SctProblem::NotAfterOutsideTemporalInterval(TemporalInterval {
+                start_inclusive: 1735689600000,
+                end_exclusive: 1767225600000
+            }),
+        ]
+    );
+    assert!(checks[1].log.is_none());
+    assert_eq!(checks[1].problems, vec![SctProblem::UnknownLog]);
+
+    // SCTs from logs that are not yet trusted don't count either.
+    let mut pending = ll.clone();
+    let log_id = &checks[0].log.as_ref().unwrap().log_id;
+    let log = pending.map_id_to_log.get_mut(log_id).unwrap();
+    log.state = LogState::Pending;
+    log.temporal_interval = None;
+    let checks = check_scts(scts.clone(), &chain[0], &pending, at).unwrap();
+    assert_eq!(
+        checks[0].problems,
+        vec![SctProblem::LogState(Some(LogState::Pending))]
+    );
+
+    let mut tampered = scts;
+    tampered[0].raw_signature[10] ^= 1;
+    let checks = check_scts(tampered, &chain[0], &ll, 1735689600000).unwrap();
+    assert!(matches!(
+        checks[0].problems[0],
+        SctProblem::InvalidSignature(_)
+    ));
+    assert!(checks[1].problems.contains(&SctProblem::FutureTimestamp));
+    assert!(!checks[1].is_ok());
+
+    // The test certificate has no embedded SCTs.
+    assert!(
+        check_cert_scts(&chain[0], &chain[1], &ll, at)
+            .unwrap()
+            .is_empty()
+    );
+}
@@ -0,0 +1,72 @@
+//! Log lists and SCTs from the test data, shared by the tests of the modules checking SCTs
+//! against a log list.
+
+use std::collections::HashMap;
+
+use openssl::pkey::{PKey, Public};
+use openssl::x509::X509;
+
+use crate::google_log_list::{Log, LogList, LogState, LogType};
+use crate::{LogApi, SignedCertificateTimestamp, utils};
+
+/// DER public keys of the two logs that signed the SCTs in `test_data/sct_list.bin`. The same
+/// logs signed the SCTs embedded in `sct_embedded.pem` and stapled in `ocsp_sct.der`.
+pub const LOG_KEYS: [&str; 2] = [
+    "3059301306072a8648ce3d020106082a8648ce3d030107034200046168bf03c4c5378d98fb032c34db7872abf132f0023564a3f0f5e53980eccad1f92661ba7e2b08b5e136f3828dbb68ebabc76562f86474792e5c117a0937bbf3",
+    "3059301306072a8648ce3d020106082a8648ce3d03010703420004dbabb387414e6e8e29d4a2b8abbb5fe31ecf9c58c624adf07a8591e429849a608b36a2d202223a6f144b33f1c9ba8ca3b5ce3c360f332fc3750e6a7ac0614b40",
+];
+
+/// The DER public key of log `i` in [`LOG_KEYS`].
+pub fn key_der(i: usize) -> Vec<u8> {
+    utils::hex_to_u8(LOG_KEYS[i])
+}
+
+pub fn key(i: usize) -> PKey<Public> {
+    PKey::public_key_from_der(&key_der(i)).unwrap()
+}
+
+/// A production RFC 6962 log with `pub_key`, in `state` since `state_timestamp`, with an MMD of
+/// one day.
+pub fn log(pub_key: Vec<u8>, operator: &str, state: LogState, state_timestamp: u64) -> Log {
+    Log {
+        log_id: utils::sha256(&pub_key).to_vec(),
+        pub_key,
+        base_url: "https://ct.example.com/".to_owned(),
+        submission_url: "https://ct.example.com/".to_owned(),
+        api: LogApi::Rfc6962,
+        state,
+        state_timestamp,
+        final_tree_head: None,
+        description: String::new(),
+        mmd: 86400,
+        operator: operator.to_owned(),
+        operator_email: Vec::new(),
+        temporal_interval: None,
+        log_type: Some(LogType::Prod),
+        previous_operators: Vec::new(),
+    }
+}
+
+pub fn list(logs: Vec<Log>) -> LogList {
+    LogList {
+        version: "1".to_owned(),
+        log_list_timestamp: 0,
+        operators: Vec::new(),
+        map_id_to_log: logs
+            .into_iter()
+            .map(|l| (l.log_id.clone(), l))
+            .collect::<HashMap<_, _>>(),
+    }
+}
+
+/// The leaf certificate of `sct_leaf.pem`, and the SCTs for it in `sct_list.bin`: one from each
+/// log in [`LOG_KEYS`], issued at 2025-01-01.
+pub fn scts() -> (X509, Vec<SignedCertificateTimestamp>) {
+    let chain = X509::stack_from_pem(include_bytes!("test_data/sct_leaf.pem")).unwrap();
+    let scts = SignedCertificateTimestamp::from_tls_extension(
+        include_bytes!("test_data/sct_list.bin"),
+        &chain[0],
+    )
+    .unwrap();
+    (chain.into_iter().next().unwrap(), scts)
+}
@@ -110,3 +110,87 @@ fn test_largest_power_of_2_smaller_than() {
         1u64 << 33u64
     );
 }
+
+/// Parse a RFC 3339 date-time, like `2024-01-01T00:00:00Z` or `2024-01-01T08:00:00.5+08:00`,
+/// into milliseconds since the Unix epoch. Fractions beyond milliseconds are truncated.
+///
+/// # Example:
+/// ```rust
+/// # use ctclient_async::utils::rfc3339_to_ms;
+/// assert_eq!(rfc3339_to_ms("1970-01-02T00:00:01.5Z"), Some(86401500));
+/// ```
+pub fn rfc3339_to_ms(s: &str) -> Option<u64> {
+    fn num(s: &str) -> Option<i64> {
+        if s.is_empty() || !s.bytes().all(|b| b.is_ascii_digit()) {
+            return None;
+        }
+        s.parse().ok()
+    }
+    if !s.is_ascii() {
+        return None;
+    }
+    let b = s.as_bytes();
+    if b.len() < 20 || b[4] != b'-' || b[7] != b'-' || !matches!(b[10], b'T' | b't' | b' ') {
+        return None;
+    }
+    if b[13] != b':' || b[16] != b':' {
+        return None;
+    }
+    let (year, month, day) = (num(&s[0..4])?, num(&s[5..7])?, num(&s[8..10])?);
+    let (hour, minute, second) = (num(&s[11..13])?, num(&s[14..16])?, num(&s[17..19])?);
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) || hour > 23 || minute > 59 {
+        return None;
+    }
+    // Allow for leap seconds.
+    if second > 60 {
+        return None;
+    }
+    let mut rest = &s[19..];
+    let mut millis = 0i64;
+    if let Some(frac) = rest.strip_prefix('.') {
+        let len = frac.bytes().take_while(|b| b.is_ascii_digit()).count();
+        if len == 0 {
+            return None;
+        }
+        let digits = &frac[..usize::min(len, 3)];
+        millis = num(digits)? * 10i64.pow(3 - digits.len() as u32);
+        rest = &frac[len..];
+    }
+    let offset_minutes = match rest {
+        "Z" | "z" => 0,
+        _ if rest.len() == 6 && rest.as_bytes()[3] == b':' => {
+            let minutes = num(&rest[1..3])? * 60 + num(&rest[4..6])?;
+            match rest.as_bytes()[0] {
+                b'+' => minutes,
+                b'-' => -minutes,
+                _ => return None,
+            }
+        }
+        _ => return None,
+    };
+    // Days from civil, from http://howardhinnant.github.io/date_algorithms.html
+    let y = if month <= 2 { year - 1 } else { year };
+    let era = y.div_euclid(400);
+    let yoe = y - era * 400;
+    let mp = (month + 9) % 12;
+    let doy = (153 * mp + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    let days = era * 146097 + doe - 719468;
+    let secs = days * 86400 + hour * 3600 + minute * 60 + second - offset_minutes * 60;
+    u64::try_from(secs * 1000 + millis).ok()
+}
+
+#[test]
+fn rfc3339_to_ms_test() {
+    assert_eq!(rfc3339_to_ms("1970-01-01T00:00:00Z"), Some(0));
+    assert_eq!(rfc3339_to_ms("2020-05-12T15:34:11Z"), Some(1589297651000));
+    assert_eq!(
+        rfc3339_to_ms("2020-05-12T23:34:11.123456+08:00"),
+        Some(1589297651123)
+    );
+    assert_eq!(rfc3339_to_ms("2024-02-29T00:00:00Z"), Some(1709164800000));
+    assert_eq!(rfc3339_to_ms("1969-12-31T23:59:59Z"), None);
+    assert_eq!(rfc3339_to_ms("2020-13-01T00:00:00Z"), None);
+    assert_eq!(rfc3339_to_ms("2020-01-01T00:00:00"), None);
+    assert_eq!(rfc3339_to_ms("2020-01-01"), None);
+}
@@ -0,0 +1,539 @@
+//! A [transparency log witness](https://c2sp.org/tlog-witness).
+//!
+//! A witness remembers the latest checkpoint it has cosigned for each log, and only cosigns a new
+//! checkpoint after checking a consistency proof from that one. As long as a client requires
+//! cosignatures from enough witnesses (see [`WitnessPolicy`](crate::checkpoint::WitnessPolicy)), a
+//! log can't show it a view of the tree that is not consistent with what everyone else sees.
+//!
+//! [`Witness::handle_add_checkpoint`] implements the `add-checkpoint` endpoint of the witness
+//! protocol without depending on any HTTP server. Mount it on `POST <prefix>/add-checkpoint` with
+//! whatever server you use, and copy the returned [`WitnessResponse`] to the HTTP response.
+
+use std::collections::HashMap;
+use std::convert::TryInto;
+use std::path::PathBuf;
+
+use base64::prelude::{BASE64_STANDARD, Engine as _};
+use log::info;
+use openssl::pkey::PKey;
+
+use crate::checkpoint::{
+    NoteSignature, SIG_TYPE_COSIGNATURE, SignedNote, cosignature_message, key_id,
+};
+use crate::internal::verify_consistency_proof;
+use crate::{Error, utils};
+
+/// The latest checkpoint a witness has cosigned for a log.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WitnessedTreeHead {
+    pub tree_size: u64,
+    pub root_hash: [u8; 32],
+}
+
+/// Where a [`Witness`] keeps its state, which is a [`WitnessedTreeHead`] per log origin.
+///
+/// A state must be persisted before the cosignature on it is handed out, otherwise the witness
+/// could be made to cosign two inconsistent checkpoints after a crash.
+pub trait WitnessStorage {
+    fn load(&self, origin: &str) -> Result<Option<WitnessedTreeHead>, Error>;
+    fn store(&mut self, origin: &str, tree_head: &WitnessedTreeHead) -> Result<(), Error>;
+}
+
+/// A [`WitnessStorage`] that forgets everything when dropped. Mostly useful for tests.
+#[derive(Debug, Clone, Default)]
+pub struct MemoryStorage {
+    tree_heads: HashMap<String, WitnessedTreeHead>,
+}
+
+impl WitnessStorage for MemoryStorage {
+    fn load(&self, origin: &str) -> Result<Option<WitnessedTreeHead>, Error> {
+        Ok(self.tree_heads.get(origin).copied())
+    }
+
+    fn store(&mut self, origin: &str, tree_head: &WitnessedTreeHead) -> Result<(), Error> {
+        self.tree_heads.insert(origin.to_owned(), *tree_head);
+        Ok(())
+    }
+}
+
+/// A [`WitnessStorage`] keeping one small file per log in a directory.
+///
+/// Files are named after the hex SHA-256 of the origin, and contain the tree size and hex root
+/// hash. They are replaced atomically by renaming.
+#[derive(Debug, Clone)]
+pub struct FileStorage {
+    dir: PathBuf,
+}
+
+impl FileStorage {
+    /// Use `dir` to store states, creating it if needed.
+    pub fn new<P: Into<PathBuf>>(dir: P) -> Result<Self, Error> {
+        let dir = dir.into();
+        std::fs::create_dir_all(&dir).map_err(|e| Error::FileIO(dir.clone(), e))?;
+        Ok(FileStorage { dir })
+    }
+
+    fn path_for(&self, origin: &str) -> PathBuf {
+        self.dir
+            .join(utils::u8_to_hex(&utils::sha256(origin.as_bytes())))
+    }
+}
+
+impl WitnessStorage for FileStorage {
+    fn load(&self, origin: &str) -> Result<Option<WitnessedTreeHead>, Error> {
+        let path = self.path_for(origin);
+        let content = match std::fs::read_to_string(&path) {
+            Ok(c) => c,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(Error::FileIO(path, e)),
+        };
+        let parsed = content.trim_end().split_once(' ').and_then(|(size, hash)| {
+            // `hex_to_u8` panics on anything but hex digits.
+            if hash.len() != 64 || !hash.bytes().all(|b| b.is_ascii_hexdigit()) {
+                return None;
+            }
+            Some(WitnessedTreeHead {
+                tree_size: size.parse().ok()?,
+                root_hash: utils::hex_to_u8(hash)[..].try_into().ok()?,
+            })
+        });
+        match parsed {
+            Some(th) => Ok(Some(th)),
+            None => Err(Error::Unknown(format!(
+                "{}: corrupted witness state",
+                path.to_string_lossy()
+            ))),
+        }
+    }
+
+    fn store(&mut self, origin: &str, tree_head: &WitnessedTreeHead) -> Result<(), Error> {
+        let path = self.path_for(origin);
+        let tmp_path = path.with_extension("tmp");
+        let content = format!(
+            "{} {}\n",
+            tree_head.tree_size,
+            utils::u8_to_hex(&tree_head.root_hash)
+        );
+        std::fs::write(&tmp_path, content).map_err(|e| Error::FileIO(tmp_path.clone(), e))?;
+        std::fs::rename(&tmp_path, &path).map_err(|e| Error::FileIO(path, e))
+    }
+}
+
+/// A HTTP response to send back, as returned by [`Witness::handle_add_checkpoint`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WitnessResponse {
+    pub status: u16,
+    pub content_type: &'static str,
+    pub body: Vec<u8>,
+}
+
+impl WitnessResponse {
+    fn text(status: u16, body: String) -> Self {
+        WitnessResponse {
+            status,
+            content_type: "text/plain; charset=utf-8",
+            body: body.into_bytes(),
+        }
+    }
+}
+
+/// A witness cosigning checkpoints of a set of CT logs.
+pub struct Witness<S: WitnessStorage> {
+    name: String,
+    signing_key: PKey<openssl::pkey::Private>,
+    key_id: [u8; 4],
+    logs: HashMap<String, PKey<openssl::pkey::Public>>,
+    storage: S,
+}
+
+impl<S: WitnessStorage> Witness<S> {
+    /// Construct a witness called `name` (usually a domain-like string) that signs with the
+    /// Ed25519 key `signing_key`.
+    pub fn new(
+        name: &str,
+        signing_key: PKey<openssl::pkey::Private>,
+        storage: S,
+    ) -> Result<Self, Error> {
+        if signing_key.id() != openssl::pkey::Id::ED25519 {
+            return Err(Error::InvalidArgument(
+                "Witness key must be an Ed25519 key.".to_owned(),
+            ));
+        }
+        let raw_key = signing_key
+            .raw_public_key()
+            .map_err(|e| Error::Unknown(format!("While encoding public key: {}", &e)))?;
+        // Checks the name.
+        crate::checkpoint::WitnessKey::new(name, &raw_key)?;
+        Ok(Witness {
+            name: name.to_owned(),
+            key_id: key_id(name, SIG_TYPE_COSIGNATURE, &raw_key),
+            signing_key,
+            logs: HashMap::new(),
+            storage,
+        })
+    }
+
+    /// Accept checkpoints from the CT log with this origin and DER public key.
+    pub fn add_log(&mut self, origin: &str, pub_key: &[u8]) -> Result<(), Error> {
+        let pub_key = PKey::public_key_from_der(pub_key)
+            .map_err(|e| Error::InvalidArgument(format!("Error parsing public key: {}", &e)))?;
+        self.logs.insert(origin.to_owned(), pub_key);
+        Ok(())
+    }
+
+    /// The verifier key of this witness, in the `name+keyid+base64` format accepted by
+    /// [`WitnessKey::from_vkey`](crate::checkpoint::WitnessKey::from_vkey).
+    pub fn vkey(&self) -> String {
+        let mut key = vec![SIG_TYPE_COSIGNATURE];
+        key.extend_from_slice(&self.signing_key.raw_public_key().unwrap());
+        format!(
+            "{}+{}+{}",
+            &self.name,
+            utils::u8_to_hex(&self.key_id),
+            BASE64_STANDARD.encode(&key)
+        )
+    }
+
+    /// The stored state of this witness for a log.
+    pub fn get_witnessed_tree_head(
+        &self,
+        origin: &str,
+    ) -> Result<Option<WitnessedTreeHead>, Error> {
+        self.storage.load(origin)
+    }
+
+    fn cosign(&self, text: &str) -> Result<NoteSignature, Error> {
+        let timestamp = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_err(|e| Error::Unknown(format!("Bad system time: {}", &e)))?
+            .as_secs();
+        let mut signer = openssl::sign::Signer::new_without_digest(&self.signing_key)
+            .map_err(|e| Error::Unknown(format!("EVP_DigestSignInit: {}", &e)))?;
+        let mut signature = timestamp.to_be_bytes().to_vec();
+        signature.extend_from_slice(
+            &signer
+                .sign_oneshot_to_vec(&cosignature_message(text, timestamp))
+                .map_err(|e| Error::Unknown(format!("While signing: {}", &e)))?,
+        );
+        Ok(NoteSignature {
+            name: self.name.clone(),
+            key_id: self.key_id,
+            signature,
+        })
+    }
+
+    /// Check that `note` is signed by a known log and consistent with the last checkpoint cosigned
+    /// for it, and cosign it.
+    ///
+    /// `old_size` is the size of the checkpoint the submitter believes we last cosigned, and
+    /// `proof` is the consistency proof from that size to the new one.
+    ///
+    /// # Errors
+    ///
+    /// * [`Error::UnknownLog`] if the origin is not one of the logs added with [`Self::add_log`].
+    /// * [`Error::InvalidSignature`] if the note is not signed by the log.
+    /// * [`Error::WitnessSizeMismatch`] if `old_size` is not the size we last cosigned.
+    /// * [`Error::InvalidConsistencyProof`] if the proof does not check out.
+    /// * [`Error::InvalidArgument`] if `old_size` is larger than the size of the new checkpoint.
+    pub fn add_checkpoint(
+        &mut self,
+        old_size: u64,
+        proof: &[[u8; 32]],
+        note: &SignedNote,
+    ) -> Result<NoteSignature, Error> {
+        let checkpoint = note.checkpoint()?;
+        let pub_key = self
+            .logs
+            .get(&checkpoint.origin)
+            .ok_or_else(|| Error::UnknownLog(checkpoint.origin.clone()))?;
+        note.verify_rfc6962(pub_key)?;
+        if old_size > checkpoint.tree_size {
+            return Err(Error::InvalidArgument(format!(
+                "old size {} is larger than the checkpoint size {}",
+                old_size, checkpoint.tree_size
+            )));
+        }
+        let stored = self.storage.load(&checkpoint.origin)?;
+        let stored_size = stored.map(|th| th.tree_size).unwrap_or(0);
+        if old_size != stored_size {
+            return Err(Error::WitnessSizeMismatch {
+                expected: stored_size,
+            });
+        }
+        let err_inconsistent = |desc: String| Error::InvalidConsistencyProof {
+            prev_size: old_size,
+            new_size: checkpoint.tree_size,
+            desc,
+        };
+        match stored {
+            None => {
+                if !proof.is_empty() {
+                    return Err(err_inconsistent(
+                        "Expected an empty proof from an empty tree.".to_owned(),
+                    ));
+                }
+            }
+            Some(stored) if stored.tree_size == checkpoint.tree_size => {
+                if !proof.is_empty() || stored.root_hash != checkpoint.root_hash {
+                    return Err(err_inconsistent(format!(
+                        "{} and {} both correspond to tree_size {}",
+                        utils::u8_to_hex(&stored.root_hash),
+                        utils::u8_to_hex(&checkpoint.root_hash),
+                        stored.tree_size
+                    )));
+                }
+            }
+            Some(stored) => {
+                verify_consistency_proof(
+                    stored.tree_size,
+                    checkpoint.tree_size,
+                    proof,
+                    &stored.root_hash,
+                    &checkpoint.root_hash,
+                )
+                .map_err(err_inconsistent)?;
+            }
+        }
+        let cosignature = self.cosign(&note.text)?;
+        self.storage.store(
+            &checkpoint.origin,
+            &WitnessedTreeHead {
+                tree_size: checkpoint.tree_size,
+                root_hash: checkpoint.root_hash,
+            },
+        )?;
+        info!(
+            "Witness {}: cosigned {} at {} {}",
+            &self.name,
+            &checkpoint.origin,
+            checkpoint.tree_size,
+            utils::u8_to_hex(&checkpoint.root_hash)
+        );
+        Ok(cosignature)
+    }
+
+    /// Handle the body of a `POST add-checkpoint` request, and return the response to send.
+    ///
+    /// The body is an `old <size>` line, the base64 consistency proof hashes one per line, an
+    /// empty line, and the checkpoint note.
+    pub fn handle_add_checkpoint(&mut self, body: &[u8]) -> WitnessResponse {
+        let (old_size, proof, note) = match parse_add_checkpoint_request(body) {
+            Ok(r) => r,
+            Err(e) => return WitnessResponse::text(400, format!("{}\n", e)),
+        };
+        match self.add_checkpoint(old_size, &proof, &note) {
+            Ok(sig) => WitnessResponse::text(200, sig.to_string()),
+            Err(Error::WitnessSizeMismatch { expected }) => WitnessResponse {
+                status: 409,
+                content_type: "text/x.tlog.size",
+                body: format!("{}\n", expected).into_bytes(),
+            },
+            Err(e @ Error::UnknownLog(_)) => WitnessResponse::text(404, format!("{}\n", e)),
+            Err(e @ Error::InvalidSignature(_)) => WitnessResponse::text(403, format!("{}\n", e)),
+            Err(e @ Error::InvalidConsistencyProof { .. }) => {
+                WitnessResponse::text(422, format!("{}\n", e))
+            }
+            Err(e @ Error::InvalidArgument(_)) | Err(e @ Error::BadCheckpoint(_)) => {
+                WitnessResponse::text(400, format!("{}\n", e))
+            }
+            Err(e) => WitnessResponse::text(500, format!("{}\n", e)),
+        }
+    }
+}
+
+fn parse_add_checkpoint_request(body: &[u8]) -> Result<(u64, Vec<[u8; 32]>, SignedNote), Error> {
+    fn err_invalid(desc: &str) -> Error {
+        Error::InvalidArgument(format!("Invalid add-checkpoint request: {}", desc))
+    }
+    let body = std::str::from_utf8(body).map_err(|_| err_invalid("not UTF-8"))?;
+    let (head, note) = body
+        .split_once("\n\n")
+        .ok_or_else(|| err_invalid("no checkpoint"))?;
+    let mut lines = head.split('\n');
+    let old_size = lines
+        .next()
+        .and_then(|l| l.strip_prefix("old "))
+        .and_then(|s| s.parse().ok())
+        .ok_or_else(|| err_invalid("bad old line"))?;
+    let mut proof = Vec::new();
+    for line in lines {
+        let hash = BASE64_STANDARD
+            .decode(line)
+            .ok()
+            .and_then(|h| <[u8; 32]>::try_from(&h[..]).ok())
+            .ok_or_else(|| err_invalid("bad proof hash"))?;
+        proof.push(hash);
+    }
+    if proof.len() > 63 {
+        return Err(err_invalid("proof too long"));
+    }
+    Ok((old_size, proof, SignedNote::parse(note)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::SignedTreeHead;
+    use crate::checkpoint::WitnessKey;
+    use crate::internal::consistency_proof_parts;
+    use crate::utils::{combine_tree_hash, largest_power_of_2_smaller_than, sha256};
+    use openssl::ec::{EcGroup, EcKey};
+    use openssl::hash::MessageDigest;
+    use openssl::nid::Nid;
+    use openssl::sign::Signer;
+
+    fn mth(leafs: &[[u8; 32]]) -> [u8; 32] {
+        if leafs.len() == 1 {
+            return leafs[0];
+        }
+        let k = largest_power_of_2_smaller_than(leafs.len() as u64) as usize;
+        combine_tree_hash(&mth(&leafs[..k]), &mth(&leafs[k..]))
+    }
+
+    fn checkpoint_note(
+        origin: &str,
+        key: &PKey<openssl::pkey::Private>,
+        leafs: &[[u8; 32]],
+    ) -> SignedNote {
+        let (timestamp, tree_size, root_hash) = (1700000000000u64, leafs.len() as u64, mth(leafs));
+        let mut body = vec![0u8, 1u8];
+        body.extend_from_slice(&timestamp.to_be_bytes());
+        body.extend_from_slice(&tree_size.to_be_bytes());
+        body.extend_from_slice(&root_hash);
+        let raw = Signer::new(MessageDigest::sha256(), key)
+            .unwrap()
+            .sign_oneshot_to_vec(&body)
+            .unwrap();
+        let mut signature = vec![4u8, 3u8];
+        signature.extend_from_slice(&(raw.len() as u16).to_be_bytes());
+        signature.extend_from_slice(&raw);
+        let sth = SignedTreeHead {
+            tree_size,
+            timestamp,
+            root_hash,
+            signature,
+        };
+        let pub_key = PKey::public_key_from_der(&key.public_key_to_der().unwrap()).unwrap();
+        SignedNote::from_sth(origin, &sth, &pub_key).unwrap()
+    }
+
+    fn request(old_size: u64, leafs: &[[u8; 32]], note: &SignedNote) -> Vec<u8> {
+        let mut body = format!("old {}\n", old_size);
+        if old_size > 0 && old_size < leafs.len() as u64 {
+            let mut parts = consistency_proof_parts(old_size, leafs.len() as u64);
+            if old_size.is_power_of_two() {
+                parts.remove(0);
+            }
+            for (start, end) in parts {
+                body.push_str(&BASE64_STANDARD.encode(mth(&leafs[start as usize..end as usize])));
+                body.push('\n');
+            }
+        }
+        body.push('\n');
+        body.push_str(&note.to_string());
+        body.into_bytes()
+    }
+
+    #[test]
+    fn add_checkpoint_test() {
+        let origin = "example.com/log";
+        let log_key = PKey::from_ec_key(
+            EcKey::generate(&EcGroup::from_curve_name(Nid::X9_62_PRIME256V1).unwrap()).unwrap(),
+        )
+        .unwrap();
+        let leafs: Vec<[u8; 32]> = (0..10u64).map(|i| sha256(&i.to_be_bytes())).collect();
+        let mut witness = Witness::new(
+            "witness.example",
+            PKey::generate_ed25519().unwrap(),
+            MemoryStorage::default(),
+        )
+        .unwrap();
+        witness
+            .add_log(origin, &log_key.public_key_to_der().unwrap())
+            .unwrap();
+        let witness_key = WitnessKey::from_vkey(&witness.vkey()).unwrap();
+
+        let note3 = checkpoint_note(origin, &log_key, &leafs[..3]);
+        let res = witness.handle_add_checkpoint(&request(0, &leafs[..3], &note3));
+        assert_eq!(res.status, 200);
+        let mut cosigned = note3.clone();
+        cosigned.signatures.push(
+            SignedNote::parse(&format!(
+                "{}\n{}",
+                &note3.text,
+                String::from_utf8(res.body).unwrap()
+            ))
+            .unwrap()
+            .signatures
+            .remove(0),
+        );
+        assert!(witness_key.verify(&cosigned).unwrap().is_some());
+
+        let note7 = checkpoint_note(origin, &log_key, &leafs[..7]);
+        let res = witness.handle_add_checkpoint(&request(0, &leafs[..7], &note7));
+        assert_eq!((res.status, &res.body[..]), (409, &b"3\n"[..]));
+        // Proof from a different tree of size 3.
+        let mut other_leafs = leafs.clone();
+        other_leafs[1] = [0u8; 32];
+        let res = witness.handle_add_checkpoint(&request(3, &other_leafs[..7], &note7));
+        assert_eq!(res.status, 422);
+        let res = witness.handle_add_checkpoint(&request(3, &leafs[..7], &note7));
+        assert_eq!(res.status, 200);
+        let res = witness.handle_add_checkpoint(&request(7, &leafs[..7], &note7));
+        assert_eq!(res.status, 200);
+        let res = witness.handle_add_checkpoint(&request(7, &leafs[..3], &note3));
+        assert_eq!(res.status, 400);
+        assert_eq!(
+            witness.get_witnessed_tree_head(origin).unwrap(),
+            Some(WitnessedTreeHead {
+                tree_size: 7,
+                root_hash: mth(&leafs[..7])
+            })
+        );
+
+        let unknown = checkpoint_note("example.com/other", &log_key, &leafs[..3]);
+        let res = witness.handle_add_checkpoint(&request(0, &leafs[..3], &unknown));
+        assert_eq!(res.status, 404);
+        let mut forged = checkpoint_note(origin, &log_key, &leafs[..8]);
+        forged.text = forged.text.replace("\n8\n", "\n9\n");
+        let res = witness.handle_add_checkpoint(&request(7, &leafs[..9], &forged));
+        assert_eq!(res.status, 403);
+        assert_eq!(witness.handle_add_checkpoint(b"hello").status, 400);
+    }
+
+    #[test]
+    fn file_storage_test() {
+        let dir =
+            std::env::temp_dir().join(format!("ctclient-witness-test-{}", std::process::id()));
+        let mut storage = FileStorage::new(&dir).unwrap();
+        assert_eq!(storage.load("example.com/log").unwrap(), None);
+        let th = WitnessedTreeHead {
+            tree_size: 42,
+            root_hash: [3u8; 32],
+        };
+        storage.store("example.com/log", &th).unwrap();
+        assert_eq!(
+            FileStorage::new(&dir)
+                .unwrap()
+                .load("example.com/log")
+                .unwrap(),
+            Some(th)
+        );
+        assert_eq!(storage.load("example.com/other").unwrap(), None);
+
+        // A corrupted state is an error, not a panic.
+        let path = storage.path_for("example.com/log");
+        for corrupted in [
+            "42 0303",
+            "42 zz03030303030303030303030303030303030303030303030303030303030303",
+            "42 +303030303030303030303030303030303030303030303030303030303030303",
+            "42 \u{e9}3030303030303030303030303030303030303030303030303030303030303",
+            "x 0303030303030303030303030303030303030303030303030303030303030303",
+            "",
+        ] {
+            std::fs::write(&path, corrupted).unwrap();
+            storage.load("example.com/log").expect_err(corrupted);
+        }
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}